@@ -22,7 +22,7 @@ fn parse_file() -> Result<()> {
 fn criterion_benchmark(c: &mut Criterion) {
     let mut group = c.benchmark_group("parse-big-file");
     group.sample_size(10);
-    group.bench_function("parse-file", |b| b.iter(|| parse_file()));
+    group.bench_function("parse-file", |b| b.iter(parse_file));
     group.finish();
 }
 
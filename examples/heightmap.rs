@@ -0,0 +1,73 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+
+use clap::{Parser, Subcommand};
+use ottd_map_parser::{error::Result, heightmap::Heightmap, map::TileMap, save::LazySave};
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Exports and imports OpenTTD terrain as PNG heightmaps."
+)]
+struct Args {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    Export {
+        #[arg(value_name = "SAVEFILE")]
+        save: PathBuf,
+        #[arg(short, long, default_value_t = String::from("heightmap.png"))]
+        output: String,
+        /// Height that becomes white, defaults to the highest tile
+        #[arg(short, long)]
+        max_height: Option<u8>,
+    },
+    Import {
+        #[arg(value_name = "SAVEFILE")]
+        save: PathBuf,
+        #[arg(value_name = "HEIGHTMAP")]
+        heightmap: PathBuf,
+        #[arg(short, long, default_value_t = String::from("out.sav"))]
+        output: String,
+        /// Height that white becomes
+        #[arg(short, long, default_value_t = 15)]
+        max_height: u8,
+    },
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    match args.action {
+        Action::Export {
+            save,
+            output,
+            max_height,
+        } => {
+            let save = LazySave::from_reader(&mut File::open(save)?)?;
+            let map = TileMap::from_lazy_save(&save)?;
+            let max_height = max_height
+                .unwrap_or_else(|| map.tiles.iter().map(|tile| tile.height).max().unwrap_or(0));
+
+            Heightmap::from_map(&map, max_height).write_png(BufWriter::new(File::create(output)?))
+        }
+        Action::Import {
+            save,
+            heightmap,
+            output,
+            max_height,
+        } => {
+            let mut save = LazySave::from_reader(&mut File::open(save)?)?;
+            let heightmap = Heightmap::read_png(BufReader::new(File::open(heightmap)?))?;
+            heightmap.apply_to_lazy_save(&mut save, max_height)?;
+
+            save.to_writer(&mut BufWriter::new(File::create(output)?))
+        }
+    }
+}
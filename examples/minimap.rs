@@ -0,0 +1,37 @@
+use std::{fs::File, io::BufWriter, path::PathBuf};
+
+use clap::Parser;
+use ottd_map_parser::{
+    error::Result,
+    minimap::{Minimap, MinimapMode},
+    save::LazySave,
+};
+
+#[derive(Parser)]
+#[command(version, about = "Renders a PNG minimap of an OpenTTD savefile.")]
+struct Args {
+    #[arg(value_name = "SAVEFILE")]
+    save: PathBuf,
+    #[arg(short, long, default_value_t = String::from("minimap.png"))]
+    output: String,
+    /// contours, vegetation, owners, routes or industries
+    #[arg(short, long, default_value_t = String::from("contours"))]
+    mode: String,
+    /// Scale the image to this width, keeping the aspect ratio
+    #[arg(short, long)]
+    width: Option<u32>,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let save = LazySave::from_reader(&mut File::open(args.save)?)?;
+    let mut minimap = Minimap::from_lazy_save(&save, args.mode.parse::<MinimapMode>()?)?;
+
+    if let Some(width) = args.width {
+        let height = (minimap.height as u64 * width as u64 / minimap.width as u64).max(1);
+        minimap = minimap.resize(width, height as u32);
+    }
+
+    minimap.write_png(BufWriter::new(File::create(args.output)?))
+}
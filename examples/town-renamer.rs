@@ -1,20 +1,12 @@
-// This only works on JGR patch pack save files for now
-
 use std::{
     fs::File,
-    io::{Cursor, Result, Write},
+    io::{Result, Write},
     path::PathBuf,
 };
 
 use clap::{Parser, Subcommand};
-use ottd_map_parser::{
-    charray::{self, Maps},
-    chtable::TableData,
-    jgr,
-    save::{ChArrayElement, Chunk, ChunkValue, Save},
-};
+use ottd_map_parser::{charray::Maps, save::LazySave, town};
 
-use binrw::{args, BinReaderExt, BinWrite};
 use serde::{Deserialize, Serialize};
 
 #[derive(Parser)]
@@ -62,75 +54,22 @@ fn main() -> Result<()> {
         } => {
             let mut f = File::open(save)?;
 
-            let save: Save = f.read_ne().unwrap();
+            // Only the chunks needed are decoded
+            let save = LazySave::from_reader(&mut f).unwrap();
 
-            let slxi = save
-                .get(b"SLXI")
-                .map(|slxi_chunk| match &slxi_chunk {
-                    ChunkValue::ChRiff { data } => {
-                        let c = &mut Cursor::new(&data);
-                        let slxi: jgr::SLXI = c.read_ne().unwrap();
-                        slxi
-                    }
-                    _ => {
-                        panic!("SLXI wasn't a CH_RIFF")
-                    }
-                })
-                .unwrap_or_default();
-
-            let maps = save.get(b"MAPS").unwrap();
-            let map_info: charray::Maps = match &maps {
-                ChunkValue::ChRiff { data } => Cursor::new(data).read_ne().unwrap(),
-                ChunkValue::ChTable { elements, .. } => {
-                    let dim_x = elements[0]
-                        .data
-                        .iter()
-                        .find(|(k, _)| k == "dim_x")
-                        .and_then(|(_, v)| match &v {
-                            TableData::UInt32(x) => Some(*x),
-                            _ => None,
-                        })
-                        .expect("Something wrong with MAPS");
-
-                    let dim_y = elements[0]
-                        .data
-                        .iter()
-                        .find(|(k, _)| k == "dim_y")
-                        .and_then(|(_, v)| match &v {
-                            TableData::UInt32(x) => Some(*x),
-                            _ => None,
-                        })
-                        .expect("Something wrong with MAPS");
-
-                    Maps { dim_x, dim_y }
-                }
-                _ => {
-                    panic!("Something wrong with MAPS")
-                }
-            };
-
-            let city_chunk = save.chunks.iter().find(|x| &x.tag == b"CITY").unwrap();
-
-            let cities: Vec<charray::City> = match &city_chunk.value {
-                ChunkValue::ChArray { elements } => elements
-                    .iter()
-                    .map(|el| {
-                        Cursor::new(&el.data)
-                            .read_ne_args::<charray::City>(args! { slxi: &slxi })
-                            .unwrap()
-                    })
-                    .collect(),
-                _ => {
-                    panic!("Currently only supports old/JGR maps")
-                }
-            };
-
-            let towns: Vec<Town> = cities
+            let maps = save.get(b"MAPS").unwrap().decode().unwrap();
+            let map_info = Maps::from_chunk(&maps.value).unwrap();
+
+            let towns: Vec<Town> = town::Town::from_lazy_save(&save)
+                .unwrap()
                 .iter()
-                .map(|c| Town {
-                    x: c.xy % map_info.dim_x,
-                    y: c.xy / map_info.dim_x,
-                    name: c.name.to_string(),
+                .map(|t| {
+                    let (x, y) = t.location(map_info.dim_x);
+                    Town {
+                        x,
+                        y,
+                        name: t.name.to_string(),
+                    }
                 })
                 .collect();
 
@@ -138,7 +77,7 @@ fn main() -> Result<()> {
             let json = if pretty {
                 serde_json::to_string_pretty(&towns)
             } else {
-                serde_json::to_string_pretty(&towns)
+                serde_json::to_string(&towns)
             }?;
             new_file.write_all(json.as_bytes())?;
         }
@@ -148,68 +87,18 @@ fn main() -> Result<()> {
             let json_file = File::open(json)?;
             let towns: Vec<Town> = serde_json::from_reader(json_file)?;
 
-            let mut save: Save = f.read_ne().unwrap();
+            let mut save = LazySave::from_reader(&mut f).unwrap();
 
-            let slxi = save
-                .chunks
-                .iter()
-                .find(|x| &x.tag == b"SLXI")
-                .map(|slxi_chunk| match &slxi_chunk.value {
-                    ChunkValue::ChRiff { data } => {
-                        let c = &mut Cursor::new(&data);
-                        let slxi: jgr::SLXI = c.read_ne().unwrap();
-                        slxi
-                    }
-                    _ => {
-                        panic!("SLXI wasn't a CH_RIFF")
-                    }
-                })
-                .unwrap_or_default();
-
-            save.chunks = save
-                .chunks
-                .into_iter()
-                .map(|x| {
-                    if &x.tag == b"CITY" {
-                        Chunk {
-                            value: ChunkValue::ChArray {
-                                elements: match x.value {
-                                    ChunkValue::ChArray { elements } => {
-                                        std::iter::zip(elements, &towns)
-                                            .map(|(e, t)| {
-                                                let mut city = Cursor::new(e.data)
-                                                    .read_ne_args::<charray::City>(
-                                                        args! { slxi: &slxi },
-                                                    )
-                                                    .unwrap();
-
-                                                city.name = t.name.to_string();
-
-                                                let mut new_data = Vec::new();
-                                                city.write_args(
-                                                    &mut Cursor::new(&mut new_data),
-                                                    args! { slxi: &slxi },
-                                                )
-                                                .unwrap();
-                                                ChArrayElement { data: new_data }
-                                            })
-                                            .collect()
-                                    }
-                                    _ => {
-                                        panic!("Currently only supports old/JGR maps")
-                                    }
-                                },
-                            },
-                            ..x
-                        }
-                    } else {
-                        x
-                    }
-                })
-                .collect();
+            let mut cities = town::Town::from_lazy_save(&save).unwrap();
+            for (city, t) in std::iter::zip(&mut cities, &towns) {
+                city.name = t.name.to_string();
+            }
+
+            // Every other chunk is written back unchanged
+            town::Town::write_to_lazy_save(&cities, &mut save).unwrap();
 
             let mut out_file = File::create(output)?;
-            save.write(&mut out_file).unwrap();
+            save.to_writer(&mut out_file).unwrap();
         }
     }
 
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::jgr::SLXI;
+use crate::save::{ChunkValue, LazySave, Save};
+use crate::schema::ChunkSchema;
+use crate::table::from_table;
+
+/// A group of cargo units from the `CAPA` chunk that came from the same place at the same time.
+/// Vehicles and stations refer to the packets they hold by their index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CargoPacket {
+    /// Station the cargo was first loaded at
+    pub source: u16,
+    pub source_xy: u32,
+    pub loaded_at_xy: u32,
+    /// Units of cargo in the packet
+    pub count: u16,
+    pub days_in_transit: u16,
+    /// Money already paid to transfers, in pounds shifted left by 8
+    pub feeder_share: i64,
+    /// What produced the cargo: 0 = industry, 1 = town, 2 = headquarters.
+    /// Saves before version 125 don't have the source.
+    #[serde(default)]
+    pub source_type: u8,
+    #[serde(default)]
+    pub source_id: u16,
+}
+
+impl CargoPacket {
+    pub fn from_save(save: &Save) -> Result<BTreeMap<u32, CargoPacket>> {
+        let chunk = save
+            .get(b"CAPA")
+            .ok_or(Error::MissingChunk { tag: *b"CAPA" })?;
+        CargoPacket::from_chunk(chunk, save.base_version())
+    }
+
+    pub fn from_lazy_save(save: &LazySave) -> Result<BTreeMap<u32, CargoPacket>> {
+        let chunk = save
+            .get(b"CAPA")
+            .ok_or(Error::MissingChunk { tag: *b"CAPA" })?
+            .decode()?;
+        CargoPacket::from_chunk(&chunk.value, save.base_version())
+    }
+
+    /// Parses every cargo packet in the contents of the `CAPA` chunk, by their index in the pool.
+    pub fn from_chunk(chunk: &ChunkValue, version: u16) -> Result<BTreeMap<u32, CargoPacket>> {
+        let packets = match chunk {
+            ChunkValue::ChArray { elements } => {
+                let schema = ChunkSchema::find(*b"CAPA", version)?;
+                elements
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, element)| !element.data.is_empty())
+                    .map(|(index, element)| {
+                        let row = schema.read_all(&element.data, version, &SLXI::default())?;
+                        Ok((index as u32, from_table(&row)?))
+                    })
+                    .collect()
+            }
+            ChunkValue::ChTable { elements, .. } => elements
+                .iter()
+                .enumerate()
+                .filter(|(_, element)| !element.data.is_empty())
+                .map(|(index, element)| Ok((index as u32, from_table(&element.data)?)))
+                .collect(),
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        packets.map_err(|e| e.in_chunk(*b"CAPA", 0))
+    }
+
+    /// Total units of cargo in the referenced packets, where references are the packet's index
+    /// plus one like vehicles and stations store them
+    pub fn total(packets: &BTreeMap<u32, CargoPacket>, refs: &[u32]) -> u32 {
+        refs.iter()
+            .filter_map(|&x| x.checked_sub(1))
+            .filter_map(|index| packets.get(&index))
+            .map(|packet| packet.count as u32)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::cargo::CargoPacket;
+    use crate::error::Result;
+    use crate::save::Save;
+    use crate::vehicle::Vehicle;
+
+    #[test]
+    fn read_cargo_packets() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        assert!(CargoPacket::from_save(&save)?.is_empty());
+
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let packets = CargoPacket::from_save(&save)?;
+        assert_eq!(packets.len(), 26);
+
+        // Cargo in vehicles never exceeds their capacity
+        for vehicle in Vehicle::from_save(&save)?.values() {
+            if let Some(common) = vehicle.common() {
+                let loaded = CargoPacket::total(&packets, &common.cargo_packets);
+                assert!(loaded <= common.cargo_cap as u32);
+            }
+        }
+
+        Ok(())
+    }
+}
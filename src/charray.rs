@@ -1,92 +1,190 @@
-use binrw::binrw;
+use binrw::{binrw, io::Cursor, BinReaderExt};
+use serde::{Deserialize, Serialize};
 
-use crate::{gamma::Gamma, jgr::SLXI};
+use crate::{
+    error::{Error, Result},
+    save::ChunkValue,
+    table::from_table,
+};
 
-const NUM_TE: usize = 6;
-const MAX_COMPANIES: usize = 0x0F;
-const MAX_CARGO: usize = 64;
+pub const NUM_TE: usize = 6;
+pub const MAX_COMPANIES: usize = 0x0F;
+pub const MAX_CARGO: usize = 64;
+/// Cargo slots per industry since savegame version 202
+pub const INDUSTRY_NUM_CARGO: usize = 16;
 
 #[binrw]
 #[brw(big)]
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Maps {
     pub dim_x: u32,
     pub dim_y: u32,
 }
 
-#[binrw]
-#[brw(big)]
-#[brw(import { slxi: &SLXI })]
-#[derive(Debug)]
-pub struct City {
-    pub xy: u32,
-    townnamegrfid: u32,
-    townnametype: u16,
-    townnameparts: u32,
-    #[br(temp)]
-    #[bw(calc = Gamma { value: (name.len()).try_into().unwrap() })]
-    name_size: Gamma,
-    #[br(count = name_size.value, map = |x: Vec<u8>| String::from_utf8_lossy(&x).to_string())]
-    #[bw(map = |x: &String| x.as_bytes())]
-    pub name: String,
-    flags: u8,
-    #[brw(if(slxi.has_feature("town_multi_building")))]
-    church_count: Option<u16>,
-    #[brw(if(slxi.has_feature("town_multi_building")))]
-    stadium_count: Option<u16>,
-    statues: u16,
-    have_ratings: u16,
-    ratings: [u16; MAX_COMPANIES],
-    unwanted: [u8; MAX_COMPANIES],
-    goal: [u32; NUM_TE],
-    #[br(temp)]
-    #[bw(calc = Gamma { value: (text.len()).try_into().unwrap() })]
-    text_size: Gamma,
-    #[br(count = text_size.value, map = |x: Vec<u8>| String::from_utf8_lossy(&x).to_string())]
-    #[bw(map = |x: &String| x.as_bytes())]
-    text: String,
-    time_until_rebuild: u16,
-    grow_counter: u16,
-    growth_rate: u16,
-    fund_buildings_months: u8,
-    road_build_months: u8,
-    exclusivity: u8,
-    exlusive_counter: u8,
-    larger_town: i8,
-    layout: u8,
-    #[br(temp)]
-    #[bw(calc = psa_list.len().try_into().unwrap())]
-    psa_list_size: u32,
-    #[br(count = psa_list_size)]
-    psa_list: Vec<u32>,
-    #[brw(if(slxi.has_feature("town_setting_override")))]
-    override_flags: u8,
-    #[brw(if(slxi.has_feature("town_setting_override")))]
-    override_values: u8,
-    #[brw(if(slxi.has_feature("town_setting_override")))]
-    build_tunnels: u8,
-    #[brw(if(slxi.has_feature("town_setting_override")))]
-    max_road_slope: u8,
-    supplied: [Supplied; MAX_CARGO],
-    received: [Received; NUM_TE],
+impl Maps {
+    /// Parses the contents of the `MAPS` chunk, which is a RIFF in older saves and a table in newer ones
+    pub fn from_chunk(chunk: &ChunkValue) -> Result<Maps> {
+        match chunk {
+            ChunkValue::ChRiff { data } => Ok(Cursor::new(data).read_be()?),
+            ChunkValue::ChTable { elements, .. } => elements
+                .first()
+                .ok_or_else(|| Error::Malformed {
+                    message: "MAPS has no elements".to_string(),
+                })
+                .and_then(|element| from_table(&element.data))
+                .map_err(|e| e.in_chunk(*b"MAPS", 0)),
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }
+            .in_chunk(*b"MAPS", 0)),
+        }
+    }
 }
 
-#[binrw]
-#[brw(big)]
-#[derive(Debug)]
-pub struct Supplied {
-    old_max: u32,
-    new_max: u32,
-    old_act: u32,
-    new_act: u32,
+#[allow(deprecated)]
+pub use city::{City, Received, Supplied};
+
+// The binrw impls of the deprecated types name them, so they live apart with the lint allowed.
+mod city {
+    #![allow(deprecated)]
+
+    use binrw::binrw;
+
+    use super::{MAX_CARGO, MAX_COMPANIES, NUM_TE};
+    use crate::{gamma::Gamma, jgr::SLXI};
+
+    /// A town in the array layout of the `CITY` chunk, only for savegame versions 165 to 292 without
+    /// other JGR features. Kept for code that read towns before the schema registry.
+    #[binrw]
+    #[brw(big)]
+    #[brw(import { slxi: &SLXI })]
+    #[derive(Debug, Clone)]
+    #[deprecated(
+        note = "use `town::Town`, which reads every `CITY` layout the schema registry knows"
+    )]
+    pub struct City {
+        pub xy: u32,
+        pub townnamegrfid: u32,
+        pub townnametype: u16,
+        pub townnameparts: u32,
+        #[br(temp)]
+        #[bw(try_calc = Gamma::try_from(name.len()))]
+        name_size: Gamma,
+        #[br(count = name_size.value, map = |x: Vec<u8>| String::from_utf8_lossy(&x).to_string())]
+        #[bw(map = |x: &String| x.as_bytes())]
+        pub name: String,
+        pub flags: u8,
+        #[brw(if(slxi.has_feature("town_multi_building")))]
+        pub church_count: Option<u16>,
+        #[brw(if(slxi.has_feature("town_multi_building")))]
+        pub stadium_count: Option<u16>,
+        pub statues: u16,
+        pub have_ratings: u16,
+        pub ratings: [u16; MAX_COMPANIES],
+        pub unwanted: [u8; MAX_COMPANIES],
+        pub goal: [u32; NUM_TE],
+        #[br(temp)]
+        #[bw(try_calc = Gamma::try_from(text.len()))]
+        text_size: Gamma,
+        #[br(count = text_size.value, map = |x: Vec<u8>| String::from_utf8_lossy(&x).to_string())]
+        #[bw(map = |x: &String| x.as_bytes())]
+        pub text: String,
+        pub time_until_rebuild: u16,
+        pub grow_counter: u16,
+        pub growth_rate: u16,
+        pub fund_buildings_months: u8,
+        pub road_build_months: u8,
+        pub exclusivity: u8,
+        pub exclusive_counter: u8,
+        pub larger_town: i8,
+        pub layout: u8,
+        #[br(temp)]
+        #[bw(calc = psa_list.len() as u32)]
+        psa_list_size: u32,
+        #[br(count = psa_list_size)]
+        pub psa_list: Vec<u32>,
+        #[brw(if(slxi.has_feature("town_setting_override")))]
+        pub override_flags: u8,
+        #[brw(if(slxi.has_feature("town_setting_override")))]
+        pub override_values: u8,
+        #[brw(if(slxi.has_feature("town_setting_override")))]
+        pub build_tunnels: u8,
+        #[brw(if(slxi.has_feature("town_setting_override")))]
+        pub max_road_slope: u8,
+        pub supplied: [Supplied; MAX_CARGO],
+        pub received: [Received; NUM_TE],
+    }
+
+    #[binrw]
+    #[brw(big)]
+    #[derive(Debug, Clone)]
+    #[deprecated(
+        note = "use `town::Town`, which reads every `CITY` layout the schema registry knows"
+    )]
+    pub struct Supplied {
+        pub old_max: u32,
+        pub new_max: u32,
+        pub old_act: u32,
+        pub new_act: u32,
+    }
+
+    #[binrw]
+    #[brw(big)]
+    #[derive(Debug, Clone)]
+    #[deprecated(
+        note = "use `town::Town`, which reads every `CITY` layout the schema registry knows"
+    )]
+    pub struct Received {
+        pub old_max: u16,
+        pub new_max: u16,
+        pub old_act: u16,
+        pub new_act: u16,
+    }
 }
 
-#[binrw]
-#[brw(big)]
-#[derive(Debug)]
-pub struct Received {
-    old_max: u16,
-    new_max: u16,
-    old_act: u16,
-    new_act: u16,
+/// Number of livery schemes per company
+pub const LS_END: usize = 23;
+/// Quarters of economy history kept per company
+pub const MAX_HISTORY_QUARTERS: usize = 24;
+
+#[cfg(test)]
+mod tests {
+    #![allow(deprecated)]
+
+    use std::fs::File;
+
+    use binrw::BinRead;
+
+    use super::*;
+    use crate::{jgr::SLXI, save::Save, town::Town};
+
+    #[test]
+    fn read_deprecated_cities() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let slxi = SLXI::from_save(&save)?;
+        let Some(ChunkValue::ChArray { elements }) = save.get(b"CITY") else {
+            panic!("tiny.sav should store its towns as an array");
+        };
+
+        // The old struct still reads the same towns as the schema does
+        let towns = Town::from_save(&save)?;
+        let cities = elements
+            .iter()
+            .filter(|element| !element.data.is_empty())
+            .map(|element| {
+                City::read_args(
+                    &mut Cursor::new(&element.data),
+                    binrw::args! { slxi: &slxi },
+                )
+            })
+            .collect::<binrw::BinResult<Vec<City>>>()?;
+        assert_eq!(cities.len(), towns.len());
+        for (city, town) in cities.iter().zip(&towns) {
+            assert_eq!(city.xy, town.xy);
+            assert_eq!(city.name, town.name);
+            assert_eq!(city.townnameparts, town.townnameparts);
+            assert_eq!(city.growth_rate, town.growth_rate);
+        }
+        Ok(())
+    }
 }
@@ -1,3 +1,4 @@
+use crate::error::Error;
 use crate::gamma::{gamma_length, parse_gamma, write_gamma, Gamma};
 use crate::helpers::{new_args_iter_with, until_magic};
 use binrw::{
@@ -10,53 +11,70 @@ use serde::{Deserialize, Serialize};
 #[binrw]
 #[brw(big)]
 #[br(import(header: &Vec<TableHeaderProperty>))]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ChTableElement {
     // Actual length = size - 1
     #[br(temp)]
-    #[bw(calc = Gamma { value: (data.iter().map(|x| x.1.byte_len()).sum::<usize>() + leftover.len() + 1).try_into().unwrap() })]
+    #[bw(try_calc = Gamma::try_from(data_len(data) + leftover.len() + 1))]
     pub size: Gamma,
     #[br(if(size.value > 1), parse_with = new_args_iter_with(header, |r,e,props| -> BinResult<(String, TableData)> {
         Ok((props.key.to_string(), TableData::read_options(r, e, (props,))?))
     }))]
+    #[br(assert(data_len(&data) < size.value as usize, length_mismatch(size.value as usize - 1, data_len(&data))))]
     #[bw(map = |data| -> Vec<TableData> { data.iter().map(|x| x.1.clone()).collect() })]
     #[serde(with = "tuple_vec_map")]
     pub data: Vec<(String, TableData)>,
     // Sometimes there's some leftover data here
-    #[br(count(size.value as usize - 1 - (data.iter().map(|x| x.1.byte_len()).sum::<usize>())))]
+    #[br(count(size.value as usize - 1 - data_len(&data)))]
     pub leftover: Vec<u8>,
 }
 
+impl ChTableElement {
+    /// The value stored for a key
+    pub fn get(&self, key: &str) -> Option<&TableData> {
+        field(&self.data, key)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut TableData> {
+        field_mut(&mut self.data, key)
+    }
+}
+
 #[binrw]
 #[brw(big)]
 #[br(import(header: &Vec<TableHeaderProperty>))]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ChSparseTableElement {
     // Actual length = size - 1
     #[br(temp)]
-    #[bw(calc = Gamma { value: (data.iter().map(|x| x.1.byte_len()).sum::<usize>() + leftover.len() + gamma_length(*index) as usize + 1).try_into().unwrap() })]
+    #[bw(try_calc = Gamma::try_from(data_len(data) + leftover.len() + gamma_length(*index) as usize + 1))]
     pub size: Gamma,
     #[br(parse_with = parse_gamma)]
     #[bw(write_with = write_gamma)]
+    #[br(assert(gamma_length(index) < size.value, length_mismatch(size.value as usize - 1, gamma_length(index) as usize)))]
     pub index: u32,
     #[br(if(size.value > 1), parse_with = new_args_iter_with(header, |r,e,props| -> BinResult<(String, TableData)> {
         Ok((props.key.to_string(), TableData::read_options(r, e, (props,))?))
     }))]
+    #[br(assert(
+        data_len(&data) + (gamma_length(index) as usize) < size.value as usize,
+        length_mismatch(size.value as usize - 1, data_len(&data) + gamma_length(index) as usize)
+    ))]
     #[bw(map = |data| -> Vec<TableData> { data.iter().map(|x| x.1.clone()).collect() })]
     #[serde(with = "tuple_vec_map")]
     pub data: Vec<(String, TableData)>,
     // Sometimes there's some leftover data here
-    #[br(count(size.value as usize - 1 - (data.iter().map(|x| x.1.byte_len()).sum::<usize>()) - gamma_length(index) as usize))]
+    #[br(count(size.value as usize - 1 - data_len(&data) - gamma_length(index) as usize))]
     pub leftover: Vec<u8>,
 }
 
 #[binrw]
 #[brw(big)]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct StructHeaderProperty {
     data_type: SleType,
     #[br(temp)]
-    #[bw(calc = Gamma { value: key.len().try_into().unwrap() })]
+    #[bw(try_calc = Gamma::try_from(key.len()))]
     size: Gamma,
     #[br(count = size.value, map = |x: Vec<u8>| String::from_utf8_lossy(&x).to_string())]
     #[bw(map = |x: &String| x.as_bytes())]
@@ -65,13 +83,13 @@ pub struct StructHeaderProperty {
 
 impl StructHeaderProperty {
     fn byte_len(&self) -> usize {
-        1 + (gamma_length(self.key.len().try_into().unwrap()) as usize) + self.key.len()
+        1 + (gamma_length(self.key.len() as u32) as usize) + self.key.len()
     }
 }
 
 #[binrw]
 #[brw(big)]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct StructHeader {
     #[br(parse_with = until_magic(0u8))]
     #[bw(pad_after = 1)]
@@ -88,8 +106,10 @@ impl StructHeader {
     }
 }
 
-impl From<StructHeader> for Vec<TableHeaderProperty> {
-    fn from(header: StructHeader) -> Self {
+impl TryFrom<StructHeader> for Vec<TableHeaderProperty> {
+    type Error = Error;
+
+    fn try_from(header: StructHeader) -> Result<Self, Self::Error> {
         let StructHeader {
             properties,
             sub_headers,
@@ -101,7 +121,7 @@ impl From<StructHeader> for Vec<TableHeaderProperty> {
             .into_iter()
             .map(|x| {
                 let key = x.key;
-                match x.data_type {
+                Ok(match x.data_type {
                     SleType::Int8 => TableHeaderProperty {
                         data_type: TableDataType::Int8,
                         key,
@@ -143,7 +163,14 @@ impl From<StructHeader> for Vec<TableHeaderProperty> {
                         key,
                     },
                     SleType::Struct => TableHeaderProperty {
-                        data_type: TableDataType::Struct(sub_header_iter.next().unwrap().into()),
+                        data_type: TableDataType::Struct(
+                            sub_header_iter
+                                .next()
+                                .ok_or_else(|| Error::Malformed {
+                                    message: format!("Missing header for struct {key}"),
+                                })?
+                                .try_into()?,
+                        ),
                         key,
                     },
                     SleType::Int8List => TableHeaderProperty {
@@ -182,18 +209,18 @@ impl From<StructHeader> for Vec<TableHeaderProperty> {
                         data_type: TableDataType::StringIdList,
                         key,
                     },
-                }
+                })
             })
             .collect()
     }
 }
 
-impl Into<StructHeader> for Vec<TableHeaderProperty> {
-    fn into(self) -> StructHeader {
+impl From<Vec<TableHeaderProperty>> for StructHeader {
+    fn from(header: Vec<TableHeaderProperty>) -> Self {
         let mut sub_headers = vec![];
 
         StructHeader {
-            properties: self
+            properties: header
                 .into_iter()
                 .map(|x| StructHeaderProperty {
                     key: x.key,
@@ -235,6 +262,23 @@ pub struct TableHeaderProperty {
     data_type: TableDataType,
 }
 
+impl TableHeaderProperty {
+    pub fn new(key: impl Into<String>, data_type: TableDataType) -> TableHeaderProperty {
+        TableHeaderProperty {
+            key: key.into(),
+            data_type,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn data_type(&self) -> &TableDataType {
+        &self.data_type
+    }
+}
+
 #[binrw]
 #[br(import(header: &TableHeaderProperty))]
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -266,7 +310,7 @@ pub enum TableData {
     ),
     #[br(pre_assert(matches!(header.data_type, TableDataType::Struct(_))))]
     Struct(
-        #[br(args(match &header.data_type { TableDataType::Struct(x) => &x, _ => unreachable!() }))]
+        #[br(parse_with = |r, e, _: ()| TableStruct::read_options(r, e, (struct_header(header)?,)))]
          TableStruct,
     ),
     #[br(pre_assert(matches!(header.data_type, TableDataType::Int8List)))]
@@ -369,12 +413,122 @@ impl TableData {
     }
 }
 
+impl TableData {
+    /// The value of an integer or string ID, None for any other type
+    pub fn as_i64(&self) -> Option<i64> {
+        Some(match *self {
+            TableData::Int8(x) => x as i64,
+            TableData::UInt8(x) => x as i64,
+            TableData::Int16(x) => x as i64,
+            TableData::UInt16(x) => x as i64,
+            TableData::Int32(x) => x as i64,
+            TableData::UInt32(x) => x as i64,
+            TableData::Int64(x) => x,
+            TableData::UInt64(x) => x as i64,
+            TableData::StringId(x) => x as i64,
+            _ => return None,
+        })
+    }
+
+    /// Replaces an integer or string ID keeping its type, so values that don't fit are truncated.
+    /// Returns false if the value isn't an integer.
+    pub fn set_int(&mut self, value: i64) -> bool {
+        match self {
+            TableData::Int8(x) => *x = value as i8,
+            TableData::UInt8(x) => *x = value as u8,
+            TableData::Int16(x) => *x = value as i16,
+            TableData::UInt16(x) => *x = value as u16,
+            TableData::Int32(x) => *x = value as i32,
+            TableData::UInt32(x) => *x = value as u32,
+            TableData::Int64(x) => *x = value,
+            TableData::UInt64(x) => *x = value as u64,
+            TableData::StringId(x) => *x = value as u16,
+            _ => return false,
+        }
+        true
+    }
+
+    /// The values of an integer or string ID list, None for any other type
+    pub fn as_i64_list(&self) -> Option<Vec<i64>> {
+        fn convert<T: Copy + Into<i64>>(list: &[T]) -> Option<Vec<i64>> {
+            Some(list.iter().map(|&x| x.into()).collect())
+        }
+
+        match self {
+            TableData::Int8List(list) => convert(list),
+            TableData::UInt8List(list) => convert(list),
+            TableData::Int16List(list) => convert(list),
+            TableData::UInt16List(list) => convert(list),
+            TableData::Int32List(list) => convert(list),
+            TableData::UInt32List(list) => convert(list),
+            TableData::Int64List(list) => convert(list),
+            TableData::UInt64List(list) => Some(list.iter().map(|&x| x as i64).collect()),
+            TableData::StringIdList(list) => convert(list),
+            _ => None,
+        }
+    }
+
+    /// Replaces the values of an integer or string ID list keeping its type, like `set_int`.
+    /// Returns false if the value isn't an integer list.
+    pub fn set_int_list(&mut self, values: &[i64]) -> bool {
+        match self {
+            TableData::Int8List(list) => *list = values.iter().map(|&x| x as i8).collect(),
+            TableData::UInt8List(list) => *list = values.iter().map(|&x| x as u8).collect(),
+            TableData::Int16List(list) => *list = values.iter().map(|&x| x as i16).collect(),
+            TableData::UInt16List(list) => *list = values.iter().map(|&x| x as u16).collect(),
+            TableData::Int32List(list) => *list = values.iter().map(|&x| x as i32).collect(),
+            TableData::UInt32List(list) => *list = values.iter().map(|&x| x as u32).collect(),
+            TableData::Int64List(list) => *list = values.to_vec(),
+            TableData::UInt64List(list) => *list = values.iter().map(|&x| x as u64).collect(),
+            TableData::StringIdList(list) => *list = values.iter().map(|&x| x as u16).collect(),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TableData::Str(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+/// The value stored for a key in an element or a row of a struct
+pub fn field<'a>(data: &'a [(String, TableData)], key: &str) -> Option<&'a TableData> {
+    data.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+pub fn field_mut<'a>(data: &'a mut [(String, TableData)], key: &str) -> Option<&'a mut TableData> {
+    data.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+/// Total length of the values in a table element
+fn data_len(data: &[(String, TableData)]) -> usize {
+    data.iter().map(|x| x.1.byte_len()).sum()
+}
+
+fn length_mismatch(expected: usize, actual: usize) -> Error {
+    Error::LengthMismatch { expected, actual }
+}
+
+/// Returns the header of the fields inside a struct property
+fn struct_header(header: &TableHeaderProperty) -> BinResult<&Vec<TableHeaderProperty>> {
+    match &header.data_type {
+        TableDataType::Struct(x) => Ok(x),
+        _ => Err(Error::Malformed {
+            message: format!("{} is not a struct", header.key),
+        }
+        .into_binrw(0)),
+    }
+}
+
 #[binrw]
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[br(import(header: &Vec<TableHeaderProperty>))]
 pub struct TableStruct {
     #[br(temp)]
-    #[bw(calc = Gamma { value: data.len().try_into().unwrap() })]
+    #[bw(try_calc = Gamma::try_from(data.len()))]
     size: Gamma,
     #[br(parse_with = |r,e,a| core::iter::repeat_with(|| new_args_iter_with(header, parse)(r,e,a))
                 .take(size.value as usize)
@@ -395,14 +549,14 @@ fn parse<Reader: Read + Seek>(
 }
 
 #[binrw]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TableString {
     #[br(temp)]
-    #[bw(calc = Gamma { value: (value.len()).try_into().unwrap() })]
+    #[bw(try_calc = Gamma::try_from(value.len()))]
     size: Gamma,
     #[br(count = size.value, map = |x: Vec<u8>| String::from_utf8_lossy(&x).to_string())]
     #[bw(map = |x: &String| x.as_bytes())]
-    value: String,
+    pub value: String,
 }
 
 #[binrw]
@@ -412,7 +566,7 @@ where
     T: for<'a> BinRead<Args<'a> = ()> + for<'a> BinWrite<Args<'a> = ()> + 'static,
 {
     #[br(temp)]
-    #[bw(calc = Gamma { value: data.len().try_into().unwrap() })]
+    #[bw(try_calc = Gamma::try_from(data.len()))]
     size: Gamma,
     #[br(count = size.value)]
     data: Vec<T>,
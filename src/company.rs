@@ -0,0 +1,526 @@
+use serde::{Deserialize, Serialize};
+
+use crate::charray::MAX_HISTORY_QUARTERS;
+use crate::error::{Error, Result};
+use crate::jgr::SLXI;
+use crate::save::{ChArrayElement, Chunk, ChunkValue, LazySave, Save};
+use crate::schema::ChunkSchema;
+use crate::table::{from_table, update_table};
+
+/// Step the loan is taken and repaid in
+pub const LOAN_INTERVAL: i64 = 10_000;
+/// `location_of_hq` of a company without a headquarters
+pub const INVALID_TILE: u32 = 0xFFFF_FFFF;
+
+/// A company from the `PLYR` chunk, read from either the array layout of savegame versions 170
+/// to 292 or the table layout of OpenTTD 13 and later.
+///
+/// Money is in pounds, the base currency of the game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Company {
+    /// Company ID, which is also the owner of its tiles
+    pub index: u32,
+    /// Parameter of the generated name, usually the town it was named after
+    pub name_2: u32,
+    /// String ID of the generated name
+    pub name_1: u16,
+    /// Custom name, empty if the name is generated
+    pub name: String,
+    pub president_name_1: u16,
+    pub president_name_2: u32,
+    /// Custom president name, empty if the name is generated
+    pub president_name: String,
+    pub face: u32,
+    pub money: i64,
+    pub current_loan: i64,
+    /// Fractions of a pound, out of 256
+    pub money_fraction: u8,
+    /// Company colour, also used by the default livery
+    pub colour: u8,
+    pub block_preview: u8,
+    /// Tile of the headquarters, [`INVALID_TILE`] if it hasn't built one
+    pub location_of_hq: u32,
+    pub last_build_coordinate: u32,
+    pub inaugurated_year: i32,
+    /// Company owning each quarter of the shares, 0xFF for shares nobody bought
+    pub share_owners: Vec<u8>,
+    pub months_of_bankruptcy: u8,
+    pub bankrupt_asked: u16,
+    pub bankrupt_timeout: i16,
+    pub bankrupt_value: i64,
+    /// Expenses of this year and the two before it, 13 expense types per year
+    pub yearly_expenses: Vec<i64>,
+    pub is_ai: bool,
+    /// Tiles that can still be terraformed, shifted left by 16
+    pub terraform_limit: u32,
+    /// Tiles that can still be cleared, shifted left by 16
+    pub clear_limit: u32,
+    /// Trees that can still be planted, shifted left by 16, 0 before savegame version 175
+    pub tree_limit: u32,
+    pub settings: CompanySettings,
+    /// Economy of the current quarter
+    pub cur_economy: EconomyEntry,
+    /// Economy of past quarters, newest first
+    pub old_economy: Vec<EconomyEntry>,
+    /// Colours per livery scheme, where the first is the default
+    pub liveries: Vec<Livery>,
+}
+
+/// The company settings stored with the company
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompanySettings {
+    /// Index of the first autoreplace rule
+    pub engine_renew_list: u32,
+    pub engine_renew: bool,
+    pub engine_renew_months: i16,
+    pub engine_renew_money: u32,
+    pub renew_keep_length: bool,
+    pub servint_ispercent: bool,
+    pub servint_trains: u16,
+    pub servint_roadveh: u16,
+    pub servint_aircraft: u16,
+    pub servint_ships: u16,
+}
+
+/// Economy of a company over a quarter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EconomyEntry {
+    pub income: i64,
+    pub expenses: i64,
+    pub company_value: i64,
+    /// Units delivered per cargo type, for 32 types before savegame version 199 and 64 since
+    pub delivered_cargo: Vec<u32>,
+    /// Performance rating, up to 1000
+    pub performance_history: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Livery {
+    /// Bit 0 if `colour1` is used, bit 1 if `colour2` is
+    pub in_use: u8,
+    pub colour1: u8,
+    pub colour2: u8,
+}
+
+/// A company with the keys OpenTTD uses, which the array layout has too
+#[derive(Debug, Deserialize, Serialize)]
+struct CompanyRow {
+    name_2: u32,
+    name_1: u16,
+    name: String,
+    president_name_1: u16,
+    president_name_2: u32,
+    president_name: String,
+    face: u32,
+    money: i64,
+    current_loan: i64,
+    colour: u8,
+    money_fraction: u8,
+    block_preview: u8,
+    #[serde(rename = "location_of_HQ")]
+    location_of_hq: u32,
+    last_build_coordinate: u32,
+    inaugurated_year: i32,
+    share_owners: Vec<u8>,
+    months_of_bankruptcy: u8,
+    bankrupt_asked: u16,
+    bankrupt_timeout: i16,
+    bankrupt_value: i64,
+    yearly_expenses: Vec<i64>,
+    is_ai: bool,
+    terraform_limit: u32,
+    clear_limit: u32,
+    #[serde(default)]
+    tree_limit: u32,
+    settings: SettingsRow,
+    cur_economy: EconomyEntry,
+    old_economy: Vec<EconomyEntry>,
+    liveries: Vec<Livery>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SettingsRow {
+    engine_renew_list: u32,
+    #[serde(rename = "settings.engine_renew")]
+    engine_renew: bool,
+    #[serde(rename = "settings.engine_renew_months")]
+    engine_renew_months: i16,
+    #[serde(rename = "settings.engine_renew_money")]
+    engine_renew_money: u32,
+    #[serde(rename = "settings.renew_keep_length")]
+    renew_keep_length: bool,
+    #[serde(rename = "settings.vehicle.servint_ispercent")]
+    servint_ispercent: bool,
+    #[serde(rename = "settings.vehicle.servint_trains")]
+    servint_trains: u16,
+    #[serde(rename = "settings.vehicle.servint_roadveh")]
+    servint_roadveh: u16,
+    #[serde(rename = "settings.vehicle.servint_aircraft")]
+    servint_aircraft: u16,
+    #[serde(rename = "settings.vehicle.servint_ships")]
+    servint_ships: u16,
+}
+
+impl Company {
+    /// Changes the loan the way taking or repaying it in game does, so the difference is added
+    /// to or taken from the company's money. The loan has to be a multiple of [`LOAN_INTERVAL`].
+    ///
+    /// The maximum loan is a game setting, so it isn't checked here.
+    pub fn set_loan(&mut self, loan: i64) -> Result<()> {
+        if loan < 0 || loan % LOAN_INTERVAL != 0 {
+            return Err(Error::Malformed {
+                message: format!("a loan of {loan} isn't a multiple of {LOAN_INTERVAL}"),
+            });
+        }
+        self.money += loan - self.current_loan;
+        self.current_loan = loan;
+        Ok(())
+    }
+
+    /// Money minus the loan
+    pub fn net_money(&self) -> i64 {
+        self.money - self.current_loan
+    }
+
+    /// Whether the company has a headquarters
+    pub fn has_hq(&self) -> bool {
+        self.location_of_hq != INVALID_TILE
+    }
+
+    pub fn from_save(save: &Save) -> Result<Vec<Company>> {
+        let chunk = save
+            .get(b"PLYR")
+            .ok_or(Error::MissingChunk { tag: *b"PLYR" })?;
+        Company::from_chunk(chunk, save.base_version())
+    }
+
+    pub fn from_lazy_save(save: &LazySave) -> Result<Vec<Company>> {
+        let chunk = save
+            .get(b"PLYR")
+            .ok_or(Error::MissingChunk { tag: *b"PLYR" })?
+            .decode()?;
+        Company::from_chunk(&chunk.value, save.base_version())
+    }
+
+    /// Parses every company in the contents of the `PLYR` chunk.
+    /// `version` is the savegame version, which decides the fields of the array layout.
+    pub fn from_chunk(chunk: &ChunkValue, version: u16) -> Result<Vec<Company>> {
+        let companies = match chunk {
+            ChunkValue::ChArray { elements } => {
+                ChunkSchema::find(*b"PLYR", version).and_then(|schema| {
+                    elements
+                        .iter()
+                        .enumerate()
+                        // Empty elements are companies that don't exist
+                        .filter(|(_, element)| !element.data.is_empty())
+                        .map(|(index, element)| {
+                            let row = schema.read_all(&element.data, version, &SLXI::default())?;
+                            Ok(Company::from_row(index as u32, from_table(&row)?))
+                        })
+                        .collect()
+                })
+            }
+            ChunkValue::ChTable { elements, .. } => elements
+                .iter()
+                .enumerate()
+                .filter(|(_, element)| !element.data.is_empty())
+                .map(|(index, element)| {
+                    Ok(Company::from_row(index as u32, from_table(&element.data)?))
+                })
+                .collect(),
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        companies.map_err(|e| e.in_chunk(*b"PLYR", 0))
+    }
+
+    fn from_row(index: u32, row: CompanyRow) -> Company {
+        let settings = row.settings;
+        Company {
+            index,
+            name_2: row.name_2,
+            name_1: row.name_1,
+            name: row.name,
+            president_name_1: row.president_name_1,
+            president_name_2: row.president_name_2,
+            president_name: row.president_name,
+            face: row.face,
+            money: row.money,
+            current_loan: row.current_loan,
+            money_fraction: row.money_fraction,
+            colour: row.colour,
+            block_preview: row.block_preview,
+            location_of_hq: row.location_of_hq,
+            last_build_coordinate: row.last_build_coordinate,
+            inaugurated_year: row.inaugurated_year,
+            share_owners: row.share_owners,
+            months_of_bankruptcy: row.months_of_bankruptcy,
+            bankrupt_asked: row.bankrupt_asked,
+            bankrupt_timeout: row.bankrupt_timeout,
+            bankrupt_value: row.bankrupt_value,
+            yearly_expenses: row.yearly_expenses,
+            is_ai: row.is_ai,
+            terraform_limit: row.terraform_limit,
+            clear_limit: row.clear_limit,
+            tree_limit: row.tree_limit,
+            settings: CompanySettings {
+                engine_renew_list: settings.engine_renew_list,
+                engine_renew: settings.engine_renew,
+                engine_renew_months: settings.engine_renew_months,
+                engine_renew_money: settings.engine_renew_money,
+                renew_keep_length: settings.renew_keep_length,
+                servint_ispercent: settings.servint_ispercent,
+                servint_trains: settings.servint_trains,
+                servint_roadveh: settings.servint_roadveh,
+                servint_aircraft: settings.servint_aircraft,
+                servint_ships: settings.servint_ships,
+            },
+            cur_economy: row.cur_economy,
+            old_economy: row.old_economy,
+            liveries: row.liveries,
+        }
+    }
+
+    pub fn write_to_save(companies: &[Company], save: &mut Save) -> Result<()> {
+        let mut chunk = save
+            .get(b"PLYR")
+            .ok_or(Error::MissingChunk { tag: *b"PLYR" })?
+            .clone();
+        Company::write_to_chunk(companies, &mut chunk, save.base_version())?;
+        save.set(Chunk {
+            tag: *b"PLYR",
+            value: chunk,
+        });
+        Ok(())
+    }
+
+    pub fn write_to_lazy_save(companies: &[Company], save: &mut LazySave) -> Result<()> {
+        let mut chunk = save
+            .get(b"PLYR")
+            .ok_or(Error::MissingChunk { tag: *b"PLYR" })?
+            .decode()?;
+        Company::write_to_chunk(companies, &mut chunk.value, save.base_version())?;
+        save.set(&chunk)
+    }
+
+    /// Writes the companies back into the contents of the `PLYR` chunk in the layout it already has.
+    ///
+    /// Each company replaces the element at its index. Companies can't be added or removed this
+    /// way since everything they own refers to them. OpenTTD keeps at most
+    /// [`MAX_HISTORY_QUARTERS`] quarters of `old_economy`, and doesn't load saves with more.
+    pub fn write_to_chunk(
+        companies: &[Company],
+        chunk: &mut ChunkValue,
+        version: u16,
+    ) -> Result<()> {
+        if let Some(company) = companies
+            .iter()
+            .find(|company| company.old_economy.len() > MAX_HISTORY_QUARTERS)
+        {
+            return Err(Error::Malformed {
+                message: format!(
+                    "company {} has {} quarters of history, at most {MAX_HISTORY_QUARTERS} are kept",
+                    company.index,
+                    company.old_economy.len()
+                ),
+            }
+            .in_chunk(*b"PLYR", 0));
+        }
+
+        let result = match chunk {
+            ChunkValue::ChArray { elements } => {
+                ChunkSchema::find(*b"PLYR", version).and_then(|schema| {
+                    let slxi = SLXI::default();
+                    companies.iter().try_for_each(|company| {
+                        let element = existing(elements, company.index, |e| e.data.is_empty())?;
+                        let mut row = schema.read_all(&element.data, version, &slxi)?;
+                        schema.update(&mut row, &company.to_row())?;
+                        *element = ChArrayElement {
+                            data: schema.write(&row, version, &slxi)?,
+                        };
+                        Ok(())
+                    })
+                })
+            }
+            ChunkValue::ChTable { header, elements } => companies.iter().try_for_each(|company| {
+                let element = existing(elements, company.index, |e| e.data.is_empty())?;
+                update_table(&mut element.data, header, &company.to_row())
+            }),
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        result.map_err(|e| e.in_chunk(*b"PLYR", 0))
+    }
+
+    fn to_row(&self) -> CompanyRow {
+        let settings = &self.settings;
+        CompanyRow {
+            name_2: self.name_2,
+            name_1: self.name_1,
+            name: self.name.clone(),
+            president_name_1: self.president_name_1,
+            president_name_2: self.president_name_2,
+            president_name: self.president_name.clone(),
+            face: self.face,
+            money: self.money,
+            current_loan: self.current_loan,
+            colour: self.colour,
+            money_fraction: self.money_fraction,
+            block_preview: self.block_preview,
+            location_of_hq: self.location_of_hq,
+            last_build_coordinate: self.last_build_coordinate,
+            inaugurated_year: self.inaugurated_year,
+            share_owners: self.share_owners.clone(),
+            months_of_bankruptcy: self.months_of_bankruptcy,
+            bankrupt_asked: self.bankrupt_asked,
+            bankrupt_timeout: self.bankrupt_timeout,
+            bankrupt_value: self.bankrupt_value,
+            yearly_expenses: self.yearly_expenses.clone(),
+            is_ai: self.is_ai,
+            terraform_limit: self.terraform_limit,
+            clear_limit: self.clear_limit,
+            tree_limit: self.tree_limit,
+            settings: SettingsRow {
+                engine_renew_list: settings.engine_renew_list,
+                engine_renew: settings.engine_renew,
+                engine_renew_months: settings.engine_renew_months,
+                engine_renew_money: settings.engine_renew_money,
+                renew_keep_length: settings.renew_keep_length,
+                servint_ispercent: settings.servint_ispercent,
+                servint_trains: settings.servint_trains,
+                servint_roadveh: settings.servint_roadveh,
+                servint_aircraft: settings.servint_aircraft,
+                servint_ships: settings.servint_ships,
+            },
+            cur_economy: self.cur_economy.clone(),
+            old_economy: self.old_economy.clone(),
+            liveries: self.liveries.clone(),
+        }
+    }
+}
+
+/// The element for a company that already exists
+fn existing<T>(elements: &mut [T], index: u32, is_empty: impl Fn(&T) -> bool) -> Result<&mut T> {
+    elements
+        .get_mut(index as usize)
+        .filter(|element| !is_empty(element))
+        .ok_or_else(|| Error::Malformed {
+            message: format!("there is no company {index} to write to"),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use binrw::io::Cursor;
+    use std::fs::File;
+
+    use crate::company::Company;
+    use crate::error::Result;
+    use crate::jgr::SLXI;
+    use crate::save::{ChArrayElement, ChunkValue, CompressionType, Save, WriteOptions};
+    use crate::schema::ChunkSchema;
+
+    #[test]
+    fn read_companies() -> Result<()> {
+        for path in ["tests/tiny.sav", "tests/TinyVanillaTest.sav"] {
+            let save = Save::from_reader(&mut File::open(path)?)?;
+            let companies = Company::from_save(&save)?;
+            assert_eq!(companies.len(), 1);
+            let company = &companies[0];
+            assert!(!company.is_ai);
+            assert_eq!(company.inaugurated_year, 1950);
+            assert_eq!(company.yearly_expenses.len(), 39);
+            assert_eq!(company.liveries.len(), 23);
+            assert_eq!(company.cur_economy.delivered_cargo.len(), 64);
+            assert_eq!(company.current_loan % 10_000, 0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_companies() -> Result<()> {
+        for path in ["tests/tiny.sav", "tests/TinyVanillaTest.sav"] {
+            let mut save = Save::from_reader(&mut File::open(path)?)?;
+            let options = WriteOptions::new(CompressionType::OTTN);
+            let mut expected = vec![];
+            save.write_with_options(&mut Cursor::new(&mut expected), &options)?;
+
+            // Writing the companies back unchanged keeps the save the same
+            let mut companies = Company::from_save(&save)?;
+            Company::write_to_save(&companies, &mut save)?;
+            let mut d = vec![];
+            save.write_with_options(&mut Cursor::new(&mut d), &options)?;
+            assert_eq!(d, expected);
+
+            let company = &mut companies[0];
+            let net_money = company.net_money();
+            company.set_loan(0)?;
+            assert_eq!(company.net_money(), net_money);
+            assert!(company.set_loan(12_345).is_err());
+            company.money += 1_000_000;
+            company.name = "League Leaders".to_string();
+            company.settings.servint_trains = 90;
+            company.liveries[1].colour1 = 7;
+            Company::write_to_save(&companies, &mut save)?;
+            assert_eq!(Company::from_save(&save)?, companies);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn old_company_layouts() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let Some(ChunkValue::ChArray { elements }) = save.get(b"PLYR") else {
+            panic!("PLYR isn't an array");
+        };
+        let slxi = SLXI::default();
+        let schema = ChunkSchema::find(*b"PLYR", save.version)?;
+        let mut companies = Company::from_save(&save)?;
+
+        // Deliveries were only counted for 32 cargo types before version 199
+        let company = &mut companies[0];
+        for entry in [&mut company.cur_economy]
+            .into_iter()
+            .chain(company.old_economy.iter_mut())
+        {
+            entry.delivered_cargo.truncate(32);
+        }
+        company.cur_economy.delivered_cargo[3] = 120;
+        let mut row = schema.read_all(&elements[0].data, save.version, &slxi)?;
+        schema.update(&mut row, &company.to_row())?;
+        let old = schema.write(&row, 198, &slxi)?;
+        let entries = 1 + company.old_economy.len();
+        assert_eq!(old.len(), elements[0].data.len() - entries * 32 * 4);
+
+        let mut chunk = ChunkValue::ChArray {
+            elements: vec![ChArrayElement { data: old }],
+        };
+        assert_eq!(Company::from_chunk(&chunk, 198)?, companies);
+        companies[0].name = "Old Timers".to_string();
+        Company::write_to_chunk(&companies, &mut chunk, 198)?;
+        assert_eq!(Company::from_chunk(&chunk, 198)?, companies);
+
+        // A company from a newer save has too many cargo types for this layout
+        let new = Company::from_save(&save)?;
+        assert!(Company::write_to_chunk(&new, &mut chunk, 198).is_err());
+
+        // Tree limits were added in version 175
+        companies[0].tree_limit = 0;
+        let mut row = schema.read_all(&elements[0].data, save.version, &slxi)?;
+        schema.update(&mut row, &companies[0].to_row())?;
+        let chunk = ChunkValue::ChArray {
+            elements: vec![ChArrayElement {
+                data: schema.write(&row, 174, &slxi)?,
+            }],
+        };
+        assert_eq!(Company::from_chunk(&chunk, 174)?, companies);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use binrw::io;
+use std::fmt;
+
+/// Errors that can happen while reading or writing a save file
+#[derive(Debug)]
+pub enum Error {
+    /// The underlying reader or writer failed, or the compressed data was invalid
+    Io(io::Error),
+    /// The file doesn't start with a compression magic that OpenTTD has ever used
+    UnsupportedCompression { magic: [u8; 4] },
+    /// The save uses a compression format that this build was compiled without
+    MissingFeature { feature: &'static str },
+    /// The compression level given when writing is out of range for the format
+    InvalidCompressionLevel { level: i32, min: i32, max: i32 },
+    /// A chunk needed to build a model isn't in the save
+    MissingChunk { tag: [u8; 4] },
+    /// A chunk had a type other than RIFF, array, sparse array, table or sparse table
+    BadChunkType { chunk_type: u8 },
+    /// A length was too large to be stored as a gamma
+    GammaOverflow { value: usize },
+    /// A RIFF chunk was too large for its 28 bit size
+    ChunkTooLarge { size: usize },
+    /// The size stored for an element didn't match the size of its contents
+    LengthMismatch { expected: usize, actual: usize },
+    /// A chunk is stored in the layout of a savegame version that can only be read from `since`
+    UnsupportedVersion {
+        tag: [u8; 4],
+        version: u16,
+        since: u16,
+    },
+    /// The data couldn't be parsed for any other reason
+    Malformed { message: String },
+    /// An error that happened while reading or writing a chunk.
+    /// The offset is where the error happened in the decompressed data.
+    Chunk {
+        tag: [u8; 4],
+        offset: u64,
+        source: Box<Error>,
+    },
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+impl Error {
+    /// Adds the tag of the chunk the error happened in
+    pub fn in_chunk(self, tag: [u8; 4], offset: u64) -> Error {
+        Error::Chunk {
+            tag,
+            offset,
+            source: Box::new(self),
+        }
+    }
+
+    /// The tag of the chunk the error happened in, if any
+    pub fn chunk_tag(&self) -> Option<[u8; 4]> {
+        match self {
+            Error::Chunk { tag, .. } => Some(*tag),
+            _ => None,
+        }
+    }
+
+    /// Wraps the error so it can be returned from a binrw parser or writer
+    pub(crate) fn into_binrw(self, pos: u64) -> binrw::Error {
+        binrw::Error::Custom {
+            pos,
+            err: Box::new(self),
+        }
+    }
+
+    /// Converts a binrw error, returning the position it happened at if known
+    pub(crate) fn from_binrw(error: binrw::Error) -> (Option<u64>, Error) {
+        match error {
+            binrw::Error::Io(e) => (None, Error::Io(e)),
+            binrw::Error::Custom { pos, err } => match err.downcast::<Error>() {
+                Ok(e) => (Some(pos), *e),
+                Err(err) => match err.downcast::<io::Error>() {
+                    Ok(e) => (Some(pos), Error::Io(*e)),
+                    Err(err) => (
+                        Some(pos),
+                        Error::Malformed {
+                            message: err.to_string(),
+                        },
+                    ),
+                },
+            },
+            binrw::Error::Backtrace(backtrace) => Error::from_binrw(*backtrace.error),
+            // Enums like ChunkValue pick their variant with pre_assert, so if
+            // only one variant got past it that is the error worth reporting
+            binrw::Error::EnumErrors {
+                pos,
+                variant_errors,
+            } if variant_errors
+                .iter()
+                .filter(|(_, e)| !matches!(e, binrw::Error::AssertFail { .. }))
+                .count()
+                == 1 =>
+            {
+                let (_, error) = variant_errors
+                    .into_iter()
+                    .find(|(_, e)| !matches!(e, binrw::Error::AssertFail { .. }))
+                    .unwrap();
+                match Error::from_binrw(error) {
+                    (None, error) => (Some(pos), error),
+                    result => result,
+                }
+            }
+            e @ (binrw::Error::BadMagic { pos, .. }
+            | binrw::Error::AssertFail { pos, .. }
+            | binrw::Error::NoVariantMatch { pos }
+            | binrw::Error::EnumErrors { pos, .. }) => (
+                Some(pos),
+                Error::Malformed {
+                    message: e.to_string(),
+                },
+            ),
+            e => (
+                None,
+                Error::Malformed {
+                    message: e.to_string(),
+                },
+            ),
+        }
+    }
+}
+
+impl From<binrw::Error> for Error {
+    fn from(error: binrw::Error) -> Self {
+        Error::from_binrw(error).1
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::UnsupportedCompression { magic } => write!(
+                f,
+                "unsupported compression format {:?}",
+                String::from_utf8_lossy(magic)
+            ),
+            Error::MissingFeature { feature } => {
+                write!(f, "not compiled with {feature} support")
+            }
+            Error::InvalidCompressionLevel { level, min, max } => write!(
+                f,
+                "compression level {level} is out of range, expected {min} to {max}"
+            ),
+            Error::MissingChunk { tag } => {
+                write!(f, "missing chunk {}", String::from_utf8_lossy(tag))
+            }
+            Error::BadChunkType { chunk_type } => write!(f, "unknown chunk type {chunk_type}"),
+            Error::GammaOverflow { value } => {
+                write!(f, "{value} is too large to be stored as a gamma")
+            }
+            Error::ChunkTooLarge { size } => {
+                write!(f, "chunk of {size} bytes is too large to be stored")
+            }
+            Error::LengthMismatch { expected, actual } => write!(
+                f,
+                "length mismatch, expected {expected} bytes but found {actual}"
+            ),
+            Error::UnsupportedVersion {
+                tag,
+                version,
+                since,
+            } => write!(
+                f,
+                "{} in savegame version {version} isn't supported, only from version {since}",
+                String::from_utf8_lossy(tag)
+            ),
+            Error::Malformed { message } => write!(f, "{message}"),
+            Error::Chunk {
+                tag,
+                offset,
+                source,
+            } => write!(
+                f,
+                "{source} in chunk {} at 0x{offset:x}",
+                String::from_utf8_lossy(tag)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Chunk { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+// Lets the table (de)serializers in `table` report errors with this type
+impl serde::de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Error::Malformed {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl serde::ser::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Error::Malformed {
+            message: message.to_string(),
+        }
+    }
+}
@@ -4,6 +4,8 @@ use binrw::{
     BinRead, BinResult,
 };
 
+use crate::error::Error;
+
 // Read a gamma value which can be 1-5 bytes of data to represent a u32
 //   0xxxxxxx
 //   10xxxxxx xxxxxxxx
@@ -38,20 +40,20 @@ pub fn write_gamma(i: &u32) -> BinResult<()> {
         if i >= &(1 << 14) {
             if i >= &(1 << 21) {
                 if i >= &(1 << 28) {
-                    writer.write(&[0xF0, (i >> 24) as u8])?;
+                    writer.write_all(&[0xF0, (i >> 24) as u8])?;
                 } else {
-                    writer.write(&[0xE0 | ((i >> 24) as u8)])?;
+                    writer.write_all(&[0xE0 | ((i >> 24) as u8)])?;
                 }
-                writer.write(&[(i >> 16) as u8])?;
+                writer.write_all(&[(i >> 16) as u8])?;
             } else {
-                writer.write(&[(0xC0 | ((i >> 16) as u8))])?;
+                writer.write_all(&[(0xC0 | ((i >> 16) as u8))])?;
             }
-            writer.write(&[((i >> 8) as u8)])?;
+            writer.write_all(&[((i >> 8) as u8)])?;
         } else {
-            writer.write(&[(0x80 | ((i >> 8) as u8))])?;
+            writer.write_all(&[(0x80 | ((i >> 8) as u8))])?;
         }
     }
-    writer.write(&[*i as u8])?;
+    writer.write_all(&[*i as u8])?;
 
     Ok(())
 }
@@ -71,6 +73,16 @@ pub struct Gamma {
     pub value: u32,
 }
 
+impl TryFrom<usize> for Gamma {
+    type Error = Error;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        u32::try_from(value)
+            .map(|value| Gamma { value })
+            .map_err(|_| Error::GammaOverflow { value })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use binrw::{io::Cursor, Endian};
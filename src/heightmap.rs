@@ -0,0 +1,504 @@
+use std::io::{Read, Write};
+
+use crate::error::{Error, Result};
+use crate::map::{RawTile, TileMap, MAX_OLD_TILE_HEIGHT, OWNER_NONE, OWNER_WATER, SLV_HEIGHT_8BIT};
+use crate::save::{LazySave, Save};
+use crate::tile::{ClearGround, ClearTile, Tile, WaterClass, WaterKind, WaterTile};
+
+/// A grayscale image of a map's terrain with one pixel per tile corner, the same
+/// format OpenTTD's heightmap loader reads. Black is sea level and white is `max_height`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heightmap {
+    pub width: u32,
+    pub height: u32,
+    /// 1 byte per pixel, row by row
+    pub pixels: Vec<u8>,
+}
+
+impl Heightmap {
+    /// Scales the tile heights so `max_height` becomes white, tiles above it are clipped
+    pub fn from_map(map: &TileMap, max_height: u8) -> Heightmap {
+        let max_height = max_height.max(1) as u32;
+        let pixels = map
+            .tiles
+            .iter()
+            .map(|tile| (tile.height as u32 * 255 / max_height).min(255) as u8)
+            .collect();
+
+        Heightmap {
+            width: map.dim_x,
+            height: map.dim_y,
+            pixels,
+        }
+    }
+
+    pub fn from_save(save: &Save, max_height: u8) -> Result<Heightmap> {
+        Ok(Heightmap::from_map(&TileMap::from_save(save)?, max_height))
+    }
+
+    pub fn from_lazy_save(save: &LazySave, max_height: u8) -> Result<Heightmap> {
+        Ok(Heightmap::from_map(
+            &TileMap::from_lazy_save(save)?,
+            max_height,
+        ))
+    }
+
+    /// Reads a PNG, converting colour images to grayscale the same way OpenTTD does
+    pub fn read_png<R: Read>(reader: R) -> Result<Heightmap> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(png_decoding_error)?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(png_decoding_error)?;
+        let data = &buffer[..info.buffer_size()];
+
+        let pixels = match info.color_type {
+            png::ColorType::Grayscale => data.to_vec(),
+            png::ColorType::GrayscaleAlpha => data.iter().step_by(2).copied().collect(),
+            png::ColorType::Rgb => data.chunks_exact(3).map(grayscale).collect(),
+            png::ColorType::Rgba => data.chunks_exact(4).map(grayscale).collect(),
+            color_type => {
+                return Err(Error::Malformed {
+                    message: format!("unsupported heightmap colour type {color_type:?}"),
+                })
+            }
+        };
+
+        Ok(Heightmap {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(png_encoding_error)?;
+        writer
+            .write_image_data(&self.pixels)
+            .map_err(png_encoding_error)?;
+        writer.finish().map_err(png_encoding_error)
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        self.write_png(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Rewrites the tile heights from the heightmap, stretching it over the whole map
+    /// with white becoming `max_height`.
+    ///
+    /// Corners of tiles with something built on them, like houses, industries, rails, roads
+    /// and stations, keep their heights so their foundations stay valid. The other corners
+    /// are raised or lowered until neighbouring corners differ by at most 1, as the game
+    /// requires. The void tiles around the edge get heights too since the map edges
+    /// don't have to be at sea level since OpenTTD 13. Sea and coast
+    /// that are no longer at sea level become grass, and flat grass or trees at sea level
+    /// become sea.
+    pub fn apply(&self, map: &mut TileMap, max_height: u8) -> Result<()> {
+        let expected = self.width as usize * self.height as usize;
+        if self.pixels.len() != expected {
+            return Err(Error::LengthMismatch {
+                expected,
+                actual: self.pixels.len(),
+            });
+        }
+        if expected == 0 {
+            return Ok(());
+        }
+
+        let fixed = built_corners(map);
+        for y in 0..map.dim_y {
+            let src_y = (y as u64 * self.height as u64 / map.dim_y as u64) as usize;
+            for x in 0..map.dim_x {
+                let src_x = (x as u64 * self.width as u64 / map.dim_x as u64) as usize;
+                let pixel = self.pixels[src_y * self.width as usize + src_x] as u32;
+                let index = map.index(x, y);
+                if !fixed[index] {
+                    map.tiles[index].height = ((pixel * max_height as u32 + 127) / 255) as u8;
+                }
+            }
+        }
+
+        raise_to_fixed(map, &fixed);
+        fix_slopes(map);
+        fix_water(map);
+        Ok(())
+    }
+
+    /// Applies the heightmap to the map of a save. Saves before version 194 can't have tiles
+    /// higher than 15, which is checked before anything is changed.
+    pub fn apply_to_save(&self, save: &mut Save, max_height: u8) -> Result<()> {
+        check_max_height(save.base_version(), max_height)?;
+        let mut map = TileMap::from_save(save)?;
+        self.apply(&mut map, max_height)?;
+        map.write_to_save(save)
+    }
+
+    pub fn apply_to_lazy_save(&self, save: &mut LazySave, max_height: u8) -> Result<()> {
+        check_max_height(save.base_version(), max_height)?;
+        let mut map = TileMap::from_lazy_save(save)?;
+        self.apply(&mut map, max_height)?;
+        map.write_to_lazy_save(save)
+    }
+}
+
+fn check_max_height(version: u16, max_height: u8) -> Result<()> {
+    if version < SLV_HEIGHT_8BIT && max_height > MAX_OLD_TILE_HEIGHT {
+        return Err(Error::Malformed {
+            message: format!(
+                "tiles in savegame version {version} can't be higher than \
+                 {MAX_OLD_TILE_HEIGHT}, not {max_height}"
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// OpenTTD's `RGBToGrayscale`
+fn grayscale(rgb: &[u8]) -> u8 {
+    ((rgb[0] as u32 * 19595 + rgb[1] as u32 * 38470 + rgb[2] as u32 * 7471) / 65536) as u8
+}
+
+fn png_decoding_error(error: png::DecodingError) -> Error {
+    match error {
+        png::DecodingError::IoError(e) => Error::Io(e),
+        e => Error::Malformed {
+            message: e.to_string(),
+        },
+    }
+}
+
+fn png_encoding_error(error: png::EncodingError) -> Error {
+    match error {
+        png::EncodingError::IoError(e) => Error::Io(e),
+        e => Error::Malformed {
+            message: e.to_string(),
+        },
+    }
+}
+
+/// Whether something is built on the tile whose foundation could be broken by new heights
+fn is_built(tile: &RawTile) -> bool {
+    match tile.decode() {
+        Ok(Tile::Clear(_) | Tile::Trees(_) | Tile::Void) => false,
+        Ok(Tile::Water(water)) => !matches!(water.kind, WaterKind::Clear | WaterKind::Coast),
+        // Tiles we don't understand are left alone too
+        _ => true,
+    }
+}
+
+/// Marks the corners of the built tiles, which are the heights of the tile and the tiles to
+/// its south
+fn built_corners(map: &TileMap) -> Vec<bool> {
+    let mut fixed = vec![false; map.tiles.len()];
+    for y in 0..map.dim_y {
+        for x in 0..map.dim_x {
+            if !is_built(&map.tiles[map.index(x, y)]) {
+                continue;
+            }
+            for (cx, cy) in [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)] {
+                if cx < map.dim_x && cy < map.dim_y {
+                    fixed[map.index(cx, cy)] = true;
+                }
+            }
+        }
+    }
+    fixed
+}
+
+/// Raises corners that are more than 1 below the nearest fixed corners, per tile in between.
+/// Lowering the corners afterwards can't take them below this, so fixed corners keep their
+/// height.
+fn raise_to_fixed(map: &mut TileMap, fixed: &[bool]) {
+    let mut lowest: Vec<u8> = map
+        .tiles
+        .iter()
+        .zip(fixed)
+        .map(|(tile, &fixed)| if fixed { tile.height } else { 0 })
+        .collect();
+    sweep(&mut lowest, map.dim_x as usize, |height, neighbour| {
+        height.max(neighbour.saturating_sub(1))
+    });
+    for (tile, lowest) in map.tiles.iter_mut().zip(lowest) {
+        tile.height = tile.height.max(lowest);
+    }
+}
+
+/// Lowers corners until each is at most 1 above its neighbours
+fn fix_slopes(map: &mut TileMap) {
+    let mut heights: Vec<u8> = map.tiles.iter().map(|tile| tile.height).collect();
+    sweep(&mut heights, map.dim_x as usize, |height, neighbour| {
+        height.min(neighbour.saturating_add(1))
+    });
+    for (tile, height) in map.tiles.iter_mut().zip(heights) {
+        tile.height = height;
+    }
+}
+
+/// Updates each height from its neighbours with `step` until nothing changes
+fn sweep(heights: &mut [u8], dim_x: usize, step: impl Fn(u8, u8) -> u8) {
+    // Sweep from the north and south corners until nothing changes, which is usually
+    // after the first pair of passes
+    let len = heights.len();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for forward in [true, false] {
+            for i in 0..len {
+                let index = if forward { i } else { len - 1 - i };
+                let x = index % dim_x;
+                let mut height = heights[index];
+                if x > 0 {
+                    height = step(height, heights[index - 1]);
+                }
+                if x + 1 < dim_x {
+                    height = step(height, heights[index + 1]);
+                }
+                if index >= dim_x {
+                    height = step(height, heights[index - dim_x]);
+                }
+                if index + dim_x < len {
+                    height = step(height, heights[index + dim_x]);
+                }
+                if height != heights[index] {
+                    heights[index] = height;
+                    changed = true;
+                }
+            }
+        }
+    }
+}
+
+/// Heights of the N, W, S and E corners of a tile, where the north corner is the tile's own height
+fn corners(map: &TileMap, x: u32, y: u32) -> [u8; 4] {
+    let height = |x, y| map.tile(x, y).map_or(0, |tile| tile.height);
+    [
+        height(x, y),
+        height(x + 1, y),
+        height(x + 1, y + 1),
+        height(x, y + 1),
+    ]
+}
+
+/// Whether a slope at sea level can be a coast tile, which is any slope but flat,
+/// steep or with only two opposite corners raised
+fn is_shore(corners: [u8; 4]) -> bool {
+    let raised = corners.iter().filter(|&&h| h > 0).count();
+    let min = *corners.iter().min().unwrap();
+    let max = *corners.iter().max().unwrap();
+    min == 0 && max == 1 && !(raised == 2 && corners[0] == corners[2])
+}
+
+fn fix_water(map: &mut TileMap) {
+    for y in 0..map.dim_y {
+        for x in 0..map.dim_x {
+            let corners = corners(map, x, y);
+            let flat = corners.iter().all(|&h| h == corners[0]);
+            let sea_level = flat && corners[0] == 0;
+            let index = map.index(x, y);
+            let Ok(tile) = map.tiles[index].decode() else {
+                continue;
+            };
+
+            let replacement = match &tile {
+                Tile::Water(water) if matches!(water.kind, WaterKind::Clear | WaterKind::Coast) => {
+                    let kind = match water.water_class {
+                        WaterClass::Sea if sea_level => WaterKind::Clear,
+                        WaterClass::Sea if is_shore(corners) => WaterKind::Coast,
+                        // Rivers and canals only have to stay flat
+                        WaterClass::Canal | WaterClass::River if flat => water.kind.clone(),
+                        _ => {
+                            map.tiles[index].replace(&grass());
+                            continue;
+                        }
+                    };
+                    Tile::Water(WaterTile {
+                        kind,
+                        ..water.clone()
+                    })
+                }
+                Tile::Clear(ClearTile {
+                    ground: ClearGround::Grass | ClearGround::Rough | ClearGround::Rocks,
+                    ..
+                })
+                | Tile::Trees(_)
+                    if sea_level =>
+                {
+                    Tile::Water(WaterTile {
+                        owner: OWNER_WATER,
+                        water_class: WaterClass::Sea,
+                        docking: false,
+                        kind: WaterKind::Clear,
+                    })
+                }
+                _ => continue,
+            };
+
+            if replacement != tile {
+                map.tiles[index].replace(&replacement);
+            }
+        }
+    }
+}
+
+fn grass() -> Tile {
+    Tile::Clear(ClearTile {
+        owner: OWNER_NONE,
+        ground: ClearGround::Grass,
+        density: 3,
+        counter: 0,
+        snow: false,
+        field_type: 0,
+        industry: 0,
+        fences: [0; 4],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::error::Result;
+    use crate::heightmap::{is_built, Heightmap};
+    use crate::map::{TileMap, TileType, SLV_HEIGHT_8BIT};
+    use crate::save::Save;
+
+    #[test]
+    fn heightmap_round_trip() -> Result<()> {
+        let mut save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let map = TileMap::from_save(&save)?;
+        let max_height = map.tiles.iter().map(|tile| tile.height).max().unwrap();
+
+        let heightmap = Heightmap::from_map(&map, max_height);
+        let png = heightmap.to_png()?;
+        assert_eq!(Heightmap::read_png(png.as_slice())?, heightmap);
+
+        heightmap.apply_to_save(&mut save, max_height)?;
+        let imported = TileMap::from_save(&save)?;
+        for (tile, original) in imported.tiles.iter().zip(&map.tiles) {
+            assert_eq!(tile.height, original.height);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn heightmap_fixes_slopes() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let mut map = TileMap::from_save(&save)?;
+
+        // A white square in a black image is far too steep, so it becomes a hill 8 high
+        // in the middle of the 16x16 tiles it covers, away from the town
+        let mut heightmap = Heightmap {
+            width: 32,
+            height: 32,
+            pixels: vec![0; 32 * 32],
+        };
+        for y in 22..30 {
+            for x in 2..10 {
+                heightmap.pixels[y * 32 + x] = 255;
+            }
+        }
+        let water = |map: &TileMap| {
+            map.tiles
+                .iter()
+                .filter(|tile| tile.get_type() == Some(TileType::Water))
+                .count()
+        };
+        let water_before = water(&map);
+        heightmap.apply(&mut map, 20)?;
+
+        // The flat black part is flooded
+        assert!(water(&map) > water_before);
+
+        assert_eq!(map.tiles.iter().map(|tile| tile.height).max(), Some(8));
+        for y in 0..map.dim_y {
+            for x in 0..map.dim_x {
+                let tile = map.tile(x, y).unwrap();
+                for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                    if let Some(neighbour) = map.tile(nx, ny) {
+                        assert!(tile.height.abs_diff(neighbour.height) <= 1);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+    #[test]
+    fn heightmap_keeps_buildings() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let mut map = TileMap::from_save(&save)?;
+        let original = map.clone();
+
+        // Raise the whole map, the town and roads stay where they are and the land slopes
+        // down to them
+        let heightmap = Heightmap {
+            width: 1,
+            height: 1,
+            pixels: vec![255],
+        };
+        heightmap.apply(&mut map, 12)?;
+        let built: Vec<(u32, u32)> = (0..map.dim_y)
+            .flat_map(|y| (0..map.dim_x).map(move |x| (x, y)))
+            .filter(|&(x, y)| is_built(original.tile(x, y).unwrap()))
+            .collect();
+        assert!(!built.is_empty());
+        for &(x, y) in &built {
+            for (cx, cy) in [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)] {
+                if let Some(corner) = map.tile(cx, cy) {
+                    assert_eq!(corner.height, original.tile(cx, cy).unwrap().height);
+                }
+            }
+            assert_eq!(
+                map.tile(x, y).unwrap().tile_type,
+                original.tile(x, y).unwrap().tile_type
+            );
+        }
+        assert!(map.tiles.iter().any(|tile| tile.height == 12));
+        for y in 0..map.dim_y {
+            for x in 0..map.dim_x {
+                let tile = map.tile(x, y).unwrap();
+                for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                    if let Some(neighbour) = map.tile(nx, ny) {
+                        assert!(tile.height.abs_diff(neighbour.height) <= 1);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn heightmap_errors() -> Result<()> {
+        let mut save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let mut map = TileMap::from_save(&save)?;
+
+        // The pixels don't fill the image
+        let heightmap = Heightmap {
+            width: 4,
+            height: 4,
+            pixels: vec![0; 15],
+        };
+        assert!(heightmap.apply(&mut map, 10).is_err());
+        assert_eq!(map, TileMap::from_save(&save)?);
+
+        // Old saves can't be as high, and are left unchanged
+        let heightmap = Heightmap {
+            width: 1,
+            height: 1,
+            pixels: vec![255],
+        };
+        save.version = SLV_HEIGHT_8BIT - 1;
+        let before = format!("{:?}", save.chunks);
+        assert!(heightmap.apply_to_save(&mut save, 16).is_err());
+        assert_eq!(format!("{:?}", save.chunks), before);
+
+        Ok(())
+    }
+}
@@ -59,7 +59,7 @@ where
                     }
                 }
                 Err(err) => {
-                    return Some(Err(err.into()));
+                    return Some(Err(err));
                 }
             }
 
@@ -0,0 +1,807 @@
+use serde::{Deserialize, Serialize};
+
+use crate::chtable::TableData;
+use crate::error::{Error, Result};
+use crate::jgr::SLXI;
+use crate::map::{TileMap, OWNER_NONE, OWNER_WATER};
+use crate::save::{ChArrayElement, Chunk, ChunkValue, LazySave, Save};
+use crate::schema::ChunkSchema;
+use crate::table::{from_table, update_table};
+use crate::tile::{ClearGround, ClearTile, Tile, WaterClass, WaterKind, WaterTile};
+
+/// Production level of an industry that is about to close
+pub const PRODLEVEL_CLOSURE: u8 = 0x00;
+pub const PRODLEVEL_MINIMUM: u8 = 0x04;
+pub const PRODLEVEL_DEFAULT: u8 = 0x10;
+pub const PRODLEVEL_MAXIMUM: u8 = 0x80;
+/// Industry index stored on fields that no longer belong to an industry
+pub const INVALID_INDUSTRY: u16 = 0xFFFF;
+/// Cargo slot that isn't used
+pub const INVALID_CARGO: u8 = 0xFF;
+/// Industry type of stations that don't belong to an industry
+pub const INVALID_INDUSTRY_TYPE: u8 = 0xFF;
+
+/// An industry from the `INDY` chunk, read from either the array layout of older and JGR
+/// saves from version 161 or the table layout of OpenTTD 13 and later.
+///
+/// Per cargo values are indexed by cargo slot, see `produced_cargo` and `accepts_cargo` for
+/// the cargo type in each slot. Saves before version 202 have 2 produced and 3 accepted slots,
+/// and only the date of the first accepted slot in `last_cargo_accepted_at`, later ones have 16.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Industry {
+    /// Index of the industry in the pool, which tiles and other chunks use to refer to it
+    pub index: u32,
+    /// Tile of the north corner of the industry
+    pub tile: u32,
+    pub width: u8,
+    pub height: u8,
+    /// Index of the closest town
+    pub town: u32,
+    /// Index of the station the industry has built itself, like an oil rig's, always None
+    /// before savegame version 210
+    pub neutral_station: Option<u32>,
+    /// Cargo type produced in each slot, 0xFF for unused slots
+    pub produced_cargo: Vec<u8>,
+    pub incoming_cargo_waiting: Vec<u16>,
+    pub produced_cargo_waiting: Vec<u16>,
+    /// Cargo produced per production callback, scaled with `prod_level`
+    pub production_rate: Vec<u8>,
+    /// Cargo type accepted in each slot, 0xFF for unused slots
+    pub accepts_cargo: Vec<u8>,
+    /// Production multiplier, where 16 is the default, see [`Industry::set_production_level`]
+    pub prod_level: u8,
+    pub this_month_production: Vec<u16>,
+    pub this_month_transported: Vec<u16>,
+    /// Percentage of the production transported last month, where 255 is 100%
+    pub last_month_pct_transported: Vec<u8>,
+    pub last_month_production: Vec<u16>,
+    pub last_month_transported: Vec<u16>,
+    pub counter: u16,
+    pub industry_type: u8,
+    /// Company that owns the industry's tiles, which is `OWNER_NONE` for most industries
+    pub owner: u8,
+    pub random_colour: u8,
+    pub last_prod_year: i32,
+    pub was_cargo_delivered: u8,
+    /// Flags set by game scripts, 0 before savegame version 287
+    pub ctlflags: u8,
+    /// Company that funded the industry, `OWNER_NONE` if it was generated
+    pub founder: u8,
+    /// Date the industry was built, in days since year 0
+    pub construction_date: i32,
+    /// How the industry was built: 0 = during the game, 1 = prospected, 2 = map generation
+    pub construction_type: u8,
+    /// Date cargo was last delivered in each accepted slot
+    pub last_cargo_accepted_at: Vec<i32>,
+    pub selected_layout: u8,
+    /// Only company that may deliver cargo, 0xFF for anyone and before savegame version 287
+    pub exclusive_supplier: u8,
+    /// Only company that may pick up cargo, 0xFF for anyone and before savegame version 287
+    pub exclusive_consumer: u8,
+    /// Index of the persistent storage of a NewGRF industry, 0 for none
+    pub psa: u32,
+    pub random: u16,
+    /// Text set by game scripts, empty before savegame version 289
+    pub text: String,
+}
+
+/// An industry with the keys OpenTTD uses, which the array layout has too
+#[derive(Debug, Deserialize, Serialize)]
+struct IndustryRow {
+    #[serde(rename = "location.tile")]
+    tile: u32,
+    #[serde(rename = "location.w")]
+    width: u8,
+    #[serde(rename = "location.h")]
+    height: u8,
+    town: u32,
+    #[serde(default)]
+    neutral_station: u32,
+    produced_cargo: Vec<u8>,
+    incoming_cargo_waiting: Vec<u16>,
+    produced_cargo_waiting: Vec<u16>,
+    production_rate: Vec<u8>,
+    accepts_cargo: Vec<u8>,
+    prod_level: u8,
+    this_month_production: Vec<u16>,
+    this_month_transported: Vec<u16>,
+    last_month_pct_transported: Vec<u8>,
+    last_month_production: Vec<u16>,
+    last_month_transported: Vec<u16>,
+    counter: u16,
+    #[serde(rename = "type")]
+    industry_type: u8,
+    owner: u8,
+    random_colour: u8,
+    last_prod_year: i32,
+    was_cargo_delivered: u8,
+    #[serde(default)]
+    ctlflags: u8,
+    founder: u8,
+    construction_date: i32,
+    construction_type: u8,
+    last_cargo_accepted_at: Vec<i32>,
+    selected_layout: u8,
+    #[serde(default = "anyone")]
+    exclusive_supplier: u8,
+    #[serde(default = "anyone")]
+    exclusive_consumer: u8,
+    psa: u32,
+    random: u16,
+    #[serde(default)]
+    text: String,
+}
+
+/// Any company may deliver to or pick up from the industry
+fn anyone() -> u8 {
+    0xFF
+}
+
+impl Industry {
+    /// The x and y of the north corner of the industry
+    pub fn location(&self, dim_x: u32) -> (u32, u32) {
+        (self.tile % dim_x, self.tile / dim_x)
+    }
+
+    /// Cargo types the industry produces, without the unused slots
+    pub fn produced_cargo_types(&self) -> impl Iterator<Item = u8> + '_ {
+        self.produced_cargo
+            .iter()
+            .copied()
+            .filter(|&cargo| cargo != INVALID_CARGO)
+    }
+
+    /// Cargo types the industry accepts, without the unused slots
+    pub fn accepted_cargo_types(&self) -> impl Iterator<Item = u8> + '_ {
+        self.accepts_cargo
+            .iter()
+            .copied()
+            .filter(|&cargo| cargo != INVALID_CARGO)
+    }
+
+    /// Changes the production multiplier and scales the production rates with it, the same
+    /// way the game does when production changes. `level` has to be between
+    /// [`PRODLEVEL_MINIMUM`] and [`PRODLEVEL_MAXIMUM`], use [`Industry::remove_from_save`]
+    /// to get rid of an industry.
+    pub fn set_production_level(&mut self, level: u8) -> Result<()> {
+        if !(PRODLEVEL_MINIMUM..=PRODLEVEL_MAXIMUM).contains(&level) {
+            return Err(Error::Malformed {
+                message: format!(
+                    "production level {level} is outside {PRODLEVEL_MINIMUM}..={PRODLEVEL_MAXIMUM}"
+                ),
+            });
+        }
+        if self.prod_level == PRODLEVEL_CLOSURE {
+            return Err(Error::Malformed {
+                message: format!("industry {} is closing down", self.index),
+            });
+        }
+
+        let old = self.prod_level as u32;
+        for rate in &mut self.production_rate {
+            *rate = (*rate as u32 * level as u32).div_ceil(old).min(0xFF) as u8;
+        }
+        self.prod_level = level;
+        Ok(())
+    }
+
+    pub fn from_save(save: &Save) -> Result<Vec<Industry>> {
+        let chunk = save
+            .get(b"INDY")
+            .ok_or(Error::MissingChunk { tag: *b"INDY" })?;
+        Industry::from_chunk(chunk, save.base_version())
+    }
+
+    pub fn from_lazy_save(save: &LazySave) -> Result<Vec<Industry>> {
+        let chunk = save
+            .get(b"INDY")
+            .ok_or(Error::MissingChunk { tag: *b"INDY" })?
+            .decode()?;
+        Industry::from_chunk(&chunk.value, save.base_version())
+    }
+
+    /// Parses every industry in the contents of the `INDY` chunk.
+    /// `version` is the savegame version, which decides the fields of the array layout.
+    pub fn from_chunk(chunk: &ChunkValue, version: u16) -> Result<Vec<Industry>> {
+        let industries = match chunk {
+            ChunkValue::ChArray { elements } => {
+                ChunkSchema::find(*b"INDY", version).and_then(|schema| {
+                    elements
+                        .iter()
+                        .enumerate()
+                        // Empty elements are gaps in the pool
+                        .filter(|(_, element)| !element.data.is_empty())
+                        .map(|(index, element)| {
+                            let (row, _) = schema.read(&element.data, version, &SLXI::default())?;
+                            Ok(Industry::from_row(index as u32, from_table(&row)?))
+                        })
+                        .collect()
+                })
+            }
+            ChunkValue::ChTable { elements, .. } => elements
+                .iter()
+                .enumerate()
+                .filter(|(_, element)| !element.data.is_empty())
+                .map(|(index, element)| {
+                    Ok(Industry::from_row(index as u32, from_table(&element.data)?))
+                })
+                .collect(),
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        industries.map_err(|e| e.in_chunk(*b"INDY", 0))
+    }
+
+    fn from_row(index: u32, row: IndustryRow) -> Industry {
+        Industry {
+            index,
+            tile: row.tile,
+            width: row.width,
+            height: row.height,
+            town: from_ref(row.town).unwrap_or(u32::MAX),
+            neutral_station: from_ref(row.neutral_station),
+            produced_cargo: row.produced_cargo,
+            incoming_cargo_waiting: row.incoming_cargo_waiting,
+            produced_cargo_waiting: row.produced_cargo_waiting,
+            production_rate: row.production_rate,
+            accepts_cargo: row.accepts_cargo,
+            prod_level: row.prod_level,
+            this_month_production: row.this_month_production,
+            this_month_transported: row.this_month_transported,
+            last_month_pct_transported: row.last_month_pct_transported,
+            last_month_production: row.last_month_production,
+            last_month_transported: row.last_month_transported,
+            counter: row.counter,
+            industry_type: row.industry_type,
+            owner: row.owner,
+            random_colour: row.random_colour,
+            last_prod_year: row.last_prod_year,
+            was_cargo_delivered: row.was_cargo_delivered,
+            ctlflags: row.ctlflags,
+            founder: row.founder,
+            construction_date: row.construction_date,
+            construction_type: row.construction_type,
+            last_cargo_accepted_at: row.last_cargo_accepted_at,
+            selected_layout: row.selected_layout,
+            exclusive_supplier: row.exclusive_supplier,
+            exclusive_consumer: row.exclusive_consumer,
+            psa: row.psa,
+            random: row.random,
+            text: row.text,
+        }
+    }
+
+    pub fn write_to_save(industries: &[Industry], save: &mut Save) -> Result<()> {
+        let mut chunk = save
+            .get(b"INDY")
+            .ok_or(Error::MissingChunk { tag: *b"INDY" })?
+            .clone();
+        Industry::write_to_chunk(industries, &mut chunk, save.base_version())?;
+        save.set(Chunk {
+            tag: *b"INDY",
+            value: chunk,
+        });
+        Ok(())
+    }
+
+    pub fn write_to_lazy_save(industries: &[Industry], save: &mut LazySave) -> Result<()> {
+        let mut chunk = save
+            .get(b"INDY")
+            .ok_or(Error::MissingChunk { tag: *b"INDY" })?
+            .decode()?;
+        Industry::write_to_chunk(industries, &mut chunk.value, save.base_version())?;
+        save.set(&chunk)
+    }
+
+    /// Writes the industries back into the contents of the `INDY` chunk in the layout it already has.
+    ///
+    /// Each industry replaces the element at its index, and fields the model doesn't know about
+    /// are kept. The per cargo lists need as many slots as the array layout of the version has.
+    /// Industries can't be added this way, see [`Industry::remove_from_save`] for removing them.
+    pub fn write_to_chunk(
+        industries: &[Industry],
+        chunk: &mut ChunkValue,
+        version: u16,
+    ) -> Result<()> {
+        let result = match chunk {
+            ChunkValue::ChArray { elements } => {
+                ChunkSchema::find(*b"INDY", version).and_then(|schema| {
+                    let slxi = SLXI::default();
+                    industries.iter().try_for_each(|industry| {
+                        let element = existing(elements, industry.index, |e| e.data.is_empty())?;
+                        let (mut row, len) = schema.read(&element.data, version, &slxi)?;
+                        // Anything after the fields we know about is kept as is
+                        let rest = element.data[len..].to_vec();
+                        schema.update(&mut row, &industry.to_row())?;
+
+                        let mut data = schema.write(&row, version, &slxi)?;
+                        data.extend(rest);
+                        *element = ChArrayElement { data };
+                        Ok(())
+                    })
+                })
+            }
+            ChunkValue::ChTable { header, elements } => {
+                industries.iter().try_for_each(|industry| {
+                    let element = existing(elements, industry.index, |e| e.data.is_empty())?;
+                    update_table(&mut element.data, header, &industry.to_row())
+                })
+            }
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        result.map_err(|e| e.in_chunk(*b"INDY", 0))
+    }
+
+    fn to_row(&self) -> IndustryRow {
+        IndustryRow {
+            tile: self.tile,
+            width: self.width,
+            height: self.height,
+            town: to_ref(Some(self.town)),
+            neutral_station: to_ref(self.neutral_station),
+            produced_cargo: self.produced_cargo.clone(),
+            incoming_cargo_waiting: self.incoming_cargo_waiting.clone(),
+            produced_cargo_waiting: self.produced_cargo_waiting.clone(),
+            production_rate: self.production_rate.clone(),
+            accepts_cargo: self.accepts_cargo.clone(),
+            prod_level: self.prod_level,
+            this_month_production: self.this_month_production.clone(),
+            this_month_transported: self.this_month_transported.clone(),
+            last_month_pct_transported: self.last_month_pct_transported.clone(),
+            last_month_production: self.last_month_production.clone(),
+            last_month_transported: self.last_month_transported.clone(),
+            counter: self.counter,
+            industry_type: self.industry_type,
+            owner: self.owner,
+            random_colour: self.random_colour,
+            last_prod_year: self.last_prod_year,
+            was_cargo_delivered: self.was_cargo_delivered,
+            ctlflags: self.ctlflags,
+            founder: self.founder,
+            construction_date: self.construction_date,
+            construction_type: self.construction_type,
+            last_cargo_accepted_at: self.last_cargo_accepted_at.clone(),
+            selected_layout: self.selected_layout,
+            exclusive_supplier: self.exclusive_supplier,
+            exclusive_consumer: self.exclusive_consumer,
+            psa: self.psa,
+            random: self.random,
+            text: self.text.clone(),
+        }
+    }
+}
+
+impl Industry {
+    /// Removes an industry the way the game does when one closes: its tiles become bare land or
+    /// the water they were built on, its fields no longer belong to it, and subsidies and
+    /// animations for it are dropped.
+    ///
+    /// Industries with their own station, like oil rigs, can't be removed since the station
+    /// would have to go too. Cargo already on its way keeps the industry as its source, which the
+    /// game only uses for subsidies.
+    pub fn remove_from_save(save: &mut Save, index: u32) -> Result<()> {
+        let slxi = SLXI::from_save(save)?;
+        let mut map = TileMap::from_save(save)?;
+        let mut chunks = RemovalChunks {
+            industries: save
+                .get(b"INDY")
+                .ok_or(Error::MissingChunk { tag: *b"INDY" })?
+                .clone(),
+            subsidies: save.get(b"SUBS").cloned(),
+            animated_tiles: save.get(b"ANIT").cloned(),
+        };
+        remove(index, save.base_version(), &slxi, &mut map, &mut chunks)?;
+
+        map.write_to_save(save)?;
+        for chunk in chunks.into_chunks() {
+            save.set(chunk);
+        }
+        Ok(())
+    }
+
+    pub fn remove_from_lazy_save(save: &mut LazySave, index: u32) -> Result<()> {
+        let slxi = SLXI::from_lazy_save(save)?;
+        let mut map = TileMap::from_lazy_save(save)?;
+        let decode = |tag: &[u8; 4]| -> Result<Option<ChunkValue>> {
+            save.get(tag)
+                .map(|chunk| Ok(chunk.decode()?.value))
+                .transpose()
+        };
+        let mut chunks = RemovalChunks {
+            industries: decode(b"INDY")?.ok_or(Error::MissingChunk { tag: *b"INDY" })?,
+            subsidies: decode(b"SUBS")?,
+            animated_tiles: decode(b"ANIT")?,
+        };
+        remove(index, save.base_version(), &slxi, &mut map, &mut chunks)?;
+
+        map.write_to_lazy_save(save)?;
+        for chunk in chunks.into_chunks() {
+            save.set(&chunk)?;
+        }
+        Ok(())
+    }
+}
+
+/// The chunks that refer to an industry, other than the map
+struct RemovalChunks {
+    industries: ChunkValue,
+    subsidies: Option<ChunkValue>,
+    animated_tiles: Option<ChunkValue>,
+}
+
+impl RemovalChunks {
+    fn into_chunks(self) -> impl Iterator<Item = Chunk> {
+        [
+            (*b"INDY", Some(self.industries)),
+            (*b"SUBS", self.subsidies),
+            (*b"ANIT", self.animated_tiles),
+        ]
+        .into_iter()
+        .filter_map(|(tag, value)| value.map(|value| Chunk { tag, value }))
+    }
+}
+
+fn remove(
+    index: u32,
+    version: u16,
+    slxi: &SLXI,
+    map: &mut TileMap,
+    chunks: &mut RemovalChunks,
+) -> Result<()> {
+    let industry = Industry::from_chunk(&chunks.industries, version)?
+        .into_iter()
+        .find(|industry| industry.index == index)
+        .ok_or_else(|| no_industry(index))?;
+    if industry.neutral_station.is_some() {
+        return Err(Error::Malformed {
+            message: format!("industry {index} has its own station and can't be removed"),
+        });
+    }
+
+    match &mut chunks.industries {
+        ChunkValue::ChArray { elements } => elements[index as usize].data.clear(),
+        ChunkValue::ChTable { elements, .. } => {
+            let element = &mut elements[index as usize];
+            element.data.clear();
+            element.leftover.clear();
+        }
+        _ => unreachable!("from_chunk only accepts arrays and tables"),
+    }
+
+    let mut removed = Vec::new();
+    for (tile_index, raw) in map.tiles.iter_mut().enumerate() {
+        match raw.decode() {
+            Ok(Tile::Industry(tile)) if tile.industry as u32 == index => {
+                raw.replace(&cleared(tile.water_class));
+                removed.push(tile_index as u32);
+            }
+            Ok(Tile::Clear(mut clear))
+                if clear.ground == ClearGround::Fields && clear.industry as u32 == index =>
+            {
+                clear.industry = INVALID_INDUSTRY;
+                Tile::Clear(clear).encode_into(raw);
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(subsidies) = &mut chunks.subsidies {
+        remove_subsidies(subsidies, index).map_err(|e| e.in_chunk(*b"SUBS", 0))?;
+    }
+    if let Some(animated_tiles) = &mut chunks.animated_tiles {
+        remove_animated_tiles(animated_tiles, &removed, slxi)
+            .map_err(|e| e.in_chunk(*b"ANIT", 0))?;
+    }
+    Ok(())
+}
+
+/// What is left of an industry tile, which is the water it was built on or bare land
+fn cleared(water_class: WaterClass) -> Tile {
+    match water_class {
+        WaterClass::Invalid => Tile::Clear(ClearTile {
+            owner: OWNER_NONE,
+            ground: ClearGround::Grass,
+            density: 0,
+            counter: 0,
+            snow: false,
+            field_type: 0,
+            industry: 0,
+            fences: [0; 4],
+        }),
+        water_class => Tile::Water(WaterTile {
+            owner: if water_class == WaterClass::Sea {
+                OWNER_WATER
+            } else {
+                OWNER_NONE
+            },
+            water_class,
+            docking: false,
+            kind: WaterKind::Clear,
+        }),
+    }
+}
+
+/// The part of a subsidy that says what it's for
+#[derive(Deserialize)]
+struct SubsidyRoute {
+    src_type: u8,
+    dst_type: u8,
+    src: u16,
+    dst: u16,
+}
+
+/// Source type of subsidies between industries
+const SOURCE_INDUSTRY: u8 = 0;
+
+impl SubsidyRoute {
+    fn uses(&self, index: u32) -> bool {
+        (self.src_type == SOURCE_INDUSTRY && self.src as u32 == index)
+            || (self.dst_type == SOURCE_INDUSTRY && self.dst as u32 == index)
+    }
+}
+
+fn remove_subsidies(chunk: &mut ChunkValue, index: u32) -> Result<()> {
+    match chunk {
+        ChunkValue::ChArray { elements } => {
+            for element in elements {
+                let data = &element.data;
+                // `remaining` became 2 bytes in savegame version 292
+                let offset = match data.len() {
+                    0 => continue,
+                    9 => 3,
+                    10 => 4,
+                    len => {
+                        return Err(Error::Malformed {
+                            message: format!("unknown subsidy layout of {len} bytes"),
+                        })
+                    }
+                };
+                let route = SubsidyRoute {
+                    src_type: data[offset],
+                    dst_type: data[offset + 1],
+                    src: u16::from_be_bytes([data[offset + 2], data[offset + 3]]),
+                    dst: u16::from_be_bytes([data[offset + 4], data[offset + 5]]),
+                };
+                if route.uses(index) {
+                    element.data.clear();
+                }
+            }
+        }
+        ChunkValue::ChTable { elements, .. } => {
+            for element in elements {
+                if element.data.is_empty() {
+                    continue;
+                }
+                if from_table::<SubsidyRoute>(&element.data)?.uses(index) {
+                    element.data.clear();
+                    element.leftover.clear();
+                }
+            }
+        }
+        _ => {
+            return Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            })
+        }
+    }
+    Ok(())
+}
+
+fn remove_animated_tiles(chunk: &mut ChunkValue, removed: &[u32], slxi: &SLXI) -> Result<()> {
+    match chunk {
+        ChunkValue::ChRiff { data } => {
+            // JGR saves store an animation speed after each tile
+            let size = if slxi.has_feature("animated_tile_extra") {
+                5
+            } else {
+                4
+            };
+            if data.len() % size != 0 {
+                return Err(Error::Malformed {
+                    message: format!("animated tiles aren't a multiple of {size} bytes"),
+                });
+            }
+            *data = data
+                .chunks_exact(size)
+                .filter(|entry| {
+                    let tile = u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]);
+                    !removed.contains(&tile)
+                })
+                .flatten()
+                .copied()
+                .collect();
+        }
+        ChunkValue::ChTable { elements, .. } => {
+            for element in elements {
+                let Some(TableData::UInt32List(tiles)) = element.get_mut("tiles") else {
+                    continue;
+                };
+                tiles.retain(|tile| !removed.contains(tile));
+            }
+        }
+        _ => {
+            return Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            })
+        }
+    }
+    Ok(())
+}
+
+/// Pool references are stored as the index plus one, with 0 for none
+fn from_ref(value: u32) -> Option<u32> {
+    value.checked_sub(1)
+}
+
+fn to_ref(index: Option<u32>) -> u32 {
+    index.map_or(0, |index| index.wrapping_add(1))
+}
+
+fn no_industry(index: u32) -> Error {
+    Error::Malformed {
+        message: format!("there is no industry {index}"),
+    }
+}
+
+/// The element for an industry that's already in the pool
+fn existing<T>(elements: &mut [T], index: u32, is_empty: impl Fn(&T) -> bool) -> Result<&mut T> {
+    elements
+        .get_mut(index as usize)
+        .filter(|element| !is_empty(element))
+        .ok_or_else(|| no_industry(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use binrw::io::Cursor;
+    use std::fs::File;
+
+    use crate::error::Result;
+    use crate::industry::{Industry, PRODLEVEL_DEFAULT};
+    use crate::jgr::SLXI;
+    use crate::map::TileMap;
+    use crate::save::{ChArrayElement, ChunkValue, CompressionType, LazySave, Save, WriteOptions};
+    use crate::schema::ChunkSchema;
+    use crate::tile::Tile;
+
+    #[test]
+    fn read_industries() -> Result<()> {
+        for path in ["tests/tiny.sav", "tests/TinyVanillaTest.sav"] {
+            let save = Save::from_reader(&mut File::open(path)?)?;
+            let industries = Industry::from_save(&save)?;
+            assert_eq!(industries.len(), 10);
+            for industry in &industries {
+                assert!(industry.tile < 64 * 64);
+                assert_eq!(industry.produced_cargo.len(), 16);
+                assert_eq!(industry.prod_level, PRODLEVEL_DEFAULT);
+                assert_eq!(industry.neutral_station, None);
+            }
+            // A printing works accepts paper and produces goods
+            let printing_works = industries.iter().find(|x| x.industry_type == 9).unwrap();
+            assert_eq!(printing_works.produced_cargo_types().count(), 2);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_industries() -> Result<()> {
+        for path in ["tests/tiny.sav", "tests/TinyVanillaTest.sav"] {
+            let mut save = Save::from_reader(&mut File::open(path)?)?;
+            let options = WriteOptions::new(CompressionType::OTTN);
+            let mut expected = vec![];
+            save.write_with_options(&mut Cursor::new(&mut expected), &options)?;
+
+            // Writing the industries back unchanged keeps the save the same
+            let mut industries = Industry::from_save(&save)?;
+            Industry::write_to_save(&industries, &mut save)?;
+            let mut d = vec![];
+            save.write_with_options(&mut Cursor::new(&mut d), &options)?;
+            assert_eq!(d, expected);
+
+            let rate = industries[3].production_rate[0];
+            industries[3].set_production_level(PRODLEVEL_DEFAULT * 2)?;
+            assert_eq!(industries[3].production_rate[0], rate * 2);
+            assert!(industries[3].set_production_level(0).is_err());
+            industries[0].exclusive_supplier = 1;
+            Industry::write_to_save(&industries, &mut save)?;
+            assert_eq!(Industry::from_save(&save)?, industries);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_industry() -> Result<()> {
+        for path in ["tests/tiny.sav", "tests/TinyVanillaTest.sav"] {
+            let mut save = Save::from_reader(&mut File::open(path)?)?;
+            let map = TileMap::from_save(&save)?;
+            let industry_tiles = |map: &TileMap, index: u16| {
+                map.tiles
+                    .iter()
+                    .filter(|tile| matches!(tile.decode(), Ok(Tile::Industry(x)) if x.industry == index))
+                    .count()
+            };
+            assert!(industry_tiles(&map, 3) > 0);
+
+            Industry::remove_from_save(&mut save, 3)?;
+            let industries = Industry::from_save(&save)?;
+            assert_eq!(industries.len(), 9);
+            assert!(industries.iter().all(|industry| industry.index != 3));
+            let map = TileMap::from_save(&save)?;
+            assert_eq!(industry_tiles(&map, 3), 0);
+            assert!(industry_tiles(&map, 4) > 0);
+            assert!(Industry::remove_from_save(&mut save, 3).is_err());
+
+            // Removing from a lazy save does the same
+            let mut lazy = LazySave::from_reader(&mut File::open(path)?)?;
+            Industry::remove_from_lazy_save(&mut lazy, 3)?;
+            let mut d = vec![];
+            lazy.to_writer(&mut d)?;
+            let reread = Save::from_reader(&mut Cursor::new(d))?;
+            assert_eq!(Industry::from_save(&reread)?, industries);
+            assert_eq!(TileMap::from_save(&reread)?, map);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn old_industry_layouts() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let Some(ChunkValue::ChArray { elements }) = save.get(b"INDY") else {
+            panic!("INDY isn't an array");
+        };
+        let slxi = SLXI::default();
+        let schema = ChunkSchema::find(*b"INDY", save.version)?;
+        let new = Industry::from_save(&save)?;
+
+        // Before version 202 industries had 2 produced and 3 accepted cargo slots
+        let mut industries = new.clone();
+        let mut old_elements = vec![];
+        for (element, industry) in elements
+            .iter()
+            .filter(|element| !element.data.is_empty())
+            .zip(&mut industries)
+        {
+            industry.produced_cargo.truncate(2);
+            industry.incoming_cargo_waiting.truncate(3);
+            industry.produced_cargo_waiting.truncate(2);
+            industry.production_rate.truncate(2);
+            industry.accepts_cargo.truncate(3);
+            industry.this_month_production.truncate(2);
+            industry.this_month_transported.truncate(2);
+            industry.last_month_pct_transported.truncate(2);
+            industry.last_month_production.truncate(2);
+            industry.last_month_transported.truncate(2);
+            industry.last_cargo_accepted_at.truncate(1);
+            industry.ctlflags = 0;
+            industry.exclusive_supplier = 0xFF;
+            industry.exclusive_consumer = 0xFF;
+            industry.text.clear();
+
+            let mut row = schema.read_all(&element.data, save.version, &slxi)?;
+            schema.update(&mut row, &industry.to_row())?;
+            old_elements.push(ChArrayElement {
+                data: schema.write(&row, 201, &slxi)?,
+            });
+        }
+        let mut chunk = ChunkValue::ChArray {
+            elements: old_elements,
+        };
+        assert_eq!(Industry::from_chunk(&chunk, 201)?, industries);
+
+        industries[1].production_rate[0] = 9;
+        Industry::write_to_chunk(&industries, &mut chunk, 201)?;
+        assert_eq!(Industry::from_chunk(&chunk, 201)?, industries);
+        assert!(Industry::write_to_chunk(&new, &mut chunk, 201).is_err());
+        assert!(Industry::from_chunk(&chunk, 160).is_err());
+
+        Ok(())
+    }
+}
@@ -1,39 +1,188 @@
-use binrw::binrw;
+use binrw::{binrw, io::Cursor, BinReaderExt, BinWrite};
 use modular_bitfield::{
     bitfield,
     specifiers::{B24, B4},
 };
 
-use crate::gamma::Gamma;
+use crate::{
+    error::{Error, Result},
+    gamma::Gamma,
+    save::{Chunk, ChunkValue, LazySave, RawChunk, Save},
+};
 
+/// The extended features of a JGR's Patch Pack save, from its `SLXI` chunk. Each feature has
+/// a version, and other chunks add or change fields depending on which features the save has.
 #[binrw]
 #[brw(big)]
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct SLXI {
     chunk_version: u32,
     flags: u32,
     #[br(temp)]
-    #[bw(calc = chunks.len() as u32)]
+    #[bw(calc = features.len() as u32)]
     item_count: u32,
     #[br(count = item_count)]
-    chunks: Vec<ExtendedChunk>,
+    features: Vec<ExtendedChunk>,
 }
 
 impl SLXI {
+    /// Parses the contents of the `SLXI` chunk
+    pub fn from_chunk(chunk: &ChunkValue) -> Result<SLXI> {
+        match chunk {
+            ChunkValue::ChRiff { data } => Ok(Cursor::new(data).read_be()?),
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }
+            .in_chunk(*b"SLXI", 0)),
+        }
+    }
+
+    /// Parses `SLXI` if the save has it, vanilla saves don't and get an empty one
+    pub fn from_save(save: &Save) -> Result<SLXI> {
+        save.get(b"SLXI")
+            .map(SLXI::from_chunk)
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    pub fn from_lazy_save(save: &LazySave) -> Result<SLXI> {
+        match save.get(b"SLXI") {
+            Some(chunk) => SLXI::from_chunk(&chunk.decode()?.value),
+            None => Ok(SLXI::default()),
+        }
+    }
+
+    /// The features as the contents of an `SLXI` chunk
+    pub fn to_chunk(&self) -> Result<ChunkValue> {
+        let mut data = Vec::new();
+        self.write(&mut Cursor::new(&mut data))?;
+        Ok(ChunkValue::ChRiff { data })
+    }
+
+    /// Replaces the save's `SLXI` chunk. A save that didn't have one gets it as its first
+    /// chunk, since the other chunks can only be read once the features are known.
+    pub fn write_to_save(&self, save: &mut Save) -> Result<()> {
+        let chunk = Chunk {
+            tag: *b"SLXI",
+            value: self.to_chunk()?,
+        };
+        match save.get(b"SLXI") {
+            Some(_) => save.set(chunk),
+            None => save.chunks.insert(0, chunk),
+        }
+        Ok(())
+    }
+
+    pub fn write_to_lazy_save(&self, save: &mut LazySave) -> Result<()> {
+        let chunk = Chunk {
+            tag: *b"SLXI",
+            value: self.to_chunk()?,
+        };
+        match save.get(b"SLXI") {
+            Some(_) => save.set(&chunk),
+            None => {
+                save.chunks.insert(0, RawChunk::encode(&chunk)?);
+                Ok(())
+            }
+        }
+    }
+
+    /// Version of the `SLXI` chunk layout itself, 0 for every known save
+    pub fn chunk_version(&self) -> u32 {
+        self.chunk_version
+    }
+
+    /// Flags for the whole chunk, none of which are used yet
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    pub fn features(&self) -> &[ExtendedChunk] {
+        &self.features
+    }
+
+    pub fn feature(&self, name: &str) -> Option<&ExtendedChunk> {
+        self.features.iter().find(|feature| feature.name == name)
+    }
+
+    pub fn feature_mut(&mut self, name: &str) -> Option<&mut ExtendedChunk> {
+        self.features
+            .iter_mut()
+            .find(|feature| feature.name == name)
+    }
+
     pub fn has_feature(&self, name: &str) -> bool {
-        self.chunks.iter().any(|sub_chunk| sub_chunk.name == name)
+        self.feature(name).is_some()
+    }
+
+    /// The version of a feature, None if the save doesn't have it
+    pub fn feature_version(&self, name: &str) -> Option<u16> {
+        self.feature(name).map(|feature| feature.version)
+    }
+
+    /// Whether the save has at least a version of a feature, like JGR's `SlXvIsFeaturePresent`
+    pub fn has_feature_version(&self, name: &str, min_version: u16) -> bool {
+        self.feature_version(name)
+            .is_some_and(|version| version >= min_version)
+    }
+
+    /// Adds a feature, replacing any with the same name
+    pub fn set_feature(&mut self, feature: ExtendedChunk) {
+        match self.feature_mut(&feature.name) {
+            Some(existing) => *existing = feature,
+            None => self.features.push(feature),
+        }
+    }
+
+    pub fn remove_feature(&mut self, name: &str) -> Option<ExtendedChunk> {
+        let index = self
+            .features
+            .iter()
+            .position(|feature| feature.name == name)?;
+        Some(self.features.remove(index))
+    }
+
+    /// The features this crate knows whose version is newer than it reads, so models of the
+    /// chunks they change would get the layout wrong. Features flagged with an ignorable version
+    /// are left out.
+    pub fn unsupported(&self, known: &[KnownFeature]) -> Vec<&ExtendedChunk> {
+        self.features
+            .iter()
+            .filter(|feature| {
+                known.iter().any(|known| {
+                    known.name == feature.name
+                        && feature.version > known.version
+                        && !feature.flags.ignorable_version()
+                })
+            })
+            .collect()
+    }
+
+    /// The features not in `known` that a loader can't ignore. Their chunks can still be read
+    /// and written raw, but models of the chunks they change may fail or read the wrong fields.
+    pub fn unknown(&self, known: &[KnownFeature]) -> Vec<&ExtendedChunk> {
+        self.features
+            .iter()
+            .filter(|feature| {
+                !feature.flags.ignorable_unknown()
+                    && !known.iter().any(|known| known.name == feature.name)
+            })
+            .collect()
     }
 }
 
+/// How a loader should treat a feature it doesn't know, and what follows the feature's name
 #[bitfield(bits = 32)]
 #[binrw]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 #[br(map = Self::from_bytes)]
 #[bw(map = |&x| Self::into_bytes(x))]
 pub struct SlxiSubChunkFlags {
     #[skip]
     __: B24,
+    /// A loader that doesn't know the feature can load the save anyway
     pub ignorable_unknown: bool,
+    /// A loader that knows an older version of the feature can load the save anyway
     pub ignorable_version: bool,
     pub extra_data_present: bool,
     pub chunk_id_list_present: bool,
@@ -41,30 +190,158 @@ pub struct SlxiSubChunkFlags {
     __: B4,
 }
 
+/// A feature in `SLXI`. The present flags are kept in sync with `extra_data` and `chunk_list`
+/// when writing.
 #[binrw]
 #[brw(big)]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ExtendedChunk {
-    flags: SlxiSubChunkFlags,
-    version: u16,
+    #[bw(map = |x: &SlxiSubChunkFlags| x
+        .with_extra_data_present(extra_data.is_some())
+        .with_chunk_id_list_present(chunk_list.is_some()))]
+    pub flags: SlxiSubChunkFlags,
+    pub version: u16,
     #[br(temp)]
-    #[bw(calc = Gamma { value: name.len().try_into().unwrap() })]
+    #[bw(try_calc = Gamma::try_from(name.len()))]
     name_size: Gamma,
     #[br(count = name_size.value, map = |x: Vec<u8>| String::from_utf8_lossy(&x).to_string())]
     #[bw(map = |x: &String| x.as_bytes())]
-    name: String,
-    #[brw(if(flags.extra_data_present()))]
+    pub name: String,
+    #[br(if(flags.extra_data_present()))]
+    #[bw(if(extra_data.is_some()))]
     #[br(temp)]
     #[bw(calc = extra_data.as_ref().map_or(0, |x| x.len()) as u32)]
     extra_data_length: u32,
-    #[brw(if(flags.extra_data_present()))]
+    /// Data the feature needs that isn't in any chunk
+    #[br(if(flags.extra_data_present()))]
     #[br(count = extra_data_length)]
-    extra_data: Option<Vec<u8>>,
-    #[brw(if(flags.chunk_id_list_present()))]
+    pub extra_data: Option<Vec<u8>>,
+    #[br(if(flags.chunk_id_list_present()))]
+    #[bw(if(chunk_list.is_some()))]
     #[br(temp)]
     #[bw(calc = chunk_list.as_ref().map_or(0, |x| x.len()) as u32)]
     chunk_count: u32,
-    #[brw(if(flags.chunk_id_list_present()))]
+    /// Tags of the chunks that only exist for the feature, as big endian numbers.
+    /// A loader ignoring the feature skips them.
+    #[br(if(flags.chunk_id_list_present()))]
     #[br(count = chunk_count)]
-    chunk_list: Option<Vec<u32>>,
+    pub chunk_list: Option<Vec<u32>>,
+}
+
+impl ExtendedChunk {
+    pub fn new(name: &str, version: u16) -> ExtendedChunk {
+        ExtendedChunk {
+            flags: SlxiSubChunkFlags::new(),
+            version,
+            name: name.to_string(),
+            extra_data: None,
+            chunk_list: None,
+        }
+    }
+
+    /// Tags of the chunks that only exist for the feature
+    pub fn chunk_tags(&self) -> Vec<[u8; 4]> {
+        self.chunk_list
+            .iter()
+            .flatten()
+            .map(|id| id.to_be_bytes())
+            .collect()
+    }
+}
+
+/// A feature this crate knows, and the newest version whose layout it reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownFeature {
+    pub name: &'static str,
+    pub version: u16,
+}
+
+/// The features whose fields the models read. Saves with anything else can still be read and
+/// written as raw chunks, but models of the chunks the features change may fail.
+///
+/// This is deliberately not a list of every feature JGR has. A feature only belongs here once
+/// the schema registry or the map reads the fields it adds, so the others are reported by
+/// [`SLXI::unknown`] rather than being called safe. [`SLXI::unsupported`] only reports these
+/// features at versions newer than listed.
+pub const KNOWN_FEATURES: &[KnownFeature] = &[
+    KnownFeature {
+        name: "town_multi_building",
+        version: 1,
+    },
+    KnownFeature {
+        name: "town_setting_override",
+        version: 1,
+    },
+    KnownFeature {
+        name: "animated_tile_extra",
+        version: 1,
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::error::Result;
+    use crate::jgr::{ExtendedChunk, KnownFeature, SLXI};
+    use crate::save::{LazySave, Save};
+
+    #[test]
+    fn edit_features() -> Result<()> {
+        let mut save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let mut slxi = SLXI::from_save(&save)?;
+        assert!(slxi.features().is_empty());
+
+        let mut feature = ExtendedChunk::new("town_multi_building", 1);
+        feature.flags.set_ignorable_version(true);
+        slxi.set_feature(feature);
+        let mut feature = ExtendedChunk::new("some_patch", 3);
+        feature.extra_data = Some(vec![1, 2, 3]);
+        feature.chunk_list = Some(vec![u32::from_be_bytes(*b"XPAT")]);
+        slxi.set_feature(feature);
+        slxi.write_to_save(&mut save)?;
+        assert_eq!(save.chunks[0].tag, *b"SLXI");
+        let mut lazy = LazySave::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        slxi.write_to_lazy_save(&mut lazy)?;
+        assert_eq!(lazy.chunks[0].tag(), *b"SLXI");
+
+        // The present flags are only set once written
+        let read = SLXI::from_save(&save)?;
+        assert_eq!(read.features().len(), 2);
+        assert_eq!(read, SLXI::from_chunk(&slxi.to_chunk()?)?);
+        assert_eq!(read, SLXI::from_lazy_save(&lazy)?);
+        assert_eq!(read.feature_version("some_patch"), Some(3));
+        assert!(read.has_feature_version("town_multi_building", 1));
+        assert!(!read.has_feature_version("town_multi_building", 2));
+        let feature = read.feature("some_patch").unwrap();
+        assert!(feature.flags.extra_data_present() && feature.flags.chunk_id_list_present());
+        assert_eq!(feature.chunk_tags(), [*b"XPAT"]);
+
+        // Unknown features are reported apart from known ones that are too new, which can be
+        // ignored when flagged
+        let known = [KnownFeature {
+            name: "town_multi_building",
+            version: 0,
+        }];
+        let names = |features: Vec<&ExtendedChunk>| -> Vec<String> {
+            features
+                .iter()
+                .map(|feature| feature.name.clone())
+                .collect()
+        };
+        assert_eq!(names(read.unknown(&known)), ["some_patch"]);
+        assert!(read.unsupported(&known).is_empty());
+        slxi.feature_mut("town_multi_building")
+            .unwrap()
+            .flags
+            .set_ignorable_version(false);
+        assert_eq!(names(slxi.unsupported(&known)), ["town_multi_building"]);
+
+        slxi.remove_feature("some_patch");
+        assert!(slxi.unknown(&known).is_empty());
+        slxi.feature_mut("town_multi_building").unwrap().version = 0;
+        assert!(slxi.unsupported(&known).is_empty());
+
+        Ok(())
+    }
 }
@@ -1,62 +1,120 @@
 #[cfg(target_arch = "wasm32")]
-use binrw::{io::Cursor, BinReaderExt, BinWrite};
+use binrw::io::Cursor;
 #[cfg(target_arch = "wasm32")]
 use gloo_utils::format::JsValueSerdeExt;
+#[cfg(all(target_arch = "wasm32", feature = "minimap"))]
+use minimap::{Minimap, MinimapMode};
 #[cfg(target_arch = "wasm32")]
-use save::{OuterSave, Save};
+use save::{CompressionType, OuterSave, Save, WriteOptions};
 #[cfg(target_arch = "wasm32")]
 use serde::Serialize;
 #[cfg(target_arch = "wasm32")]
+use strings::NamedSave;
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 #[cfg(target_arch = "wasm32")]
 extern crate console_error_panic_hook;
 
+pub mod cargo;
 pub mod charray;
 pub mod chtable;
+pub mod company;
+pub mod error;
 pub mod gamma;
+#[cfg(feature = "heightmap")]
+pub mod heightmap;
 pub mod helpers;
+pub mod industry;
 pub mod jgr;
+pub mod lzo;
+pub mod map;
+#[cfg(feature = "minimap")]
+pub mod minimap;
+pub mod newgrf;
+pub mod order;
 pub mod save;
+pub mod schema;
+pub mod settings;
+pub mod station;
+pub mod stream;
+pub mod strings;
+pub mod table;
+pub mod tile;
+pub mod town;
+pub mod townname;
+pub mod vanilla;
+pub mod vehicle;
 
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
-pub fn parse_outer_file(data: &[u8]) -> JsValue {
-    let mut c = Cursor::new(data);
-    let output: OuterSave = c.read_ne().unwrap();
+pub fn parse_outer_file(data: &[u8]) -> Result<JsValue, JsError> {
+    console_error_panic_hook::set_once();
+    let output = OuterSave::from_reader(&mut Cursor::new(data))?;
 
-    serde_wasm_bindgen::to_value(&output).unwrap()
+    Ok(serde_wasm_bindgen::to_value(&output)?)
 }
 
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
-pub fn parse_file(data: &[u8]) -> JsValue {
+pub fn parse_file(data: &[u8]) -> Result<JsValue, JsError> {
     console_error_panic_hook::set_once();
-    let mut c = Cursor::new(data);
-    let output: Save = c.read_ne().unwrap();
+    let output = Save::from_reader(&mut Cursor::new(data))?;
 
     let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
-    output.serialize(&serializer).unwrap()
+    Ok(output.serialize(&serializer)?)
 }
 
+/// Like `parse_file` but through JSON, with a `names` object that shows the names of the towns,
+/// stations, companies and vehicles in the save
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
-pub fn parse_file_json(data: &[u8]) -> JsValue {
+pub fn parse_file_json(data: &[u8]) -> Result<JsValue, JsError> {
     console_error_panic_hook::set_once();
-    let mut c = Cursor::new(data);
-    let output: Save = c.read_ne().unwrap();
-    JsValue::from_serde(&output).unwrap()
+    let output = Save::from_reader(&mut Cursor::new(data))?;
+    Ok(JsValue::from_serde(&NamedSave::new(&output)?)?)
 }
 
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
-pub fn write_file(data: JsValue) -> Box<[u8]> {
+pub fn write_file(data: JsValue) -> Result<Box<[u8]>, JsError> {
     console_error_panic_hook::set_once();
-    let save: Save = serde_wasm_bindgen::from_value(data).unwrap();
+    let save: Save = serde_wasm_bindgen::from_value(data)?;
     let mut buffer = Vec::new();
-    {
-        let mut writer = Cursor::new(&mut buffer);
-        Save::write_be(&save, &mut writer).unwrap();
-    }
+    save.to_writer(&mut Cursor::new(&mut buffer))?;
+
+    Ok(buffer.into_boxed_slice())
+}
+
+/// Like `write_file` but compresses with the given format
+/// ("OTTD", "OTTN", "OTTZ", "OTTX" or "OTTS") instead of the one the save was read with.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn write_file_with_compression(
+    data: JsValue,
+    compression_type: &str,
+) -> Result<Box<[u8]>, JsError> {
+    console_error_panic_hook::set_once();
+    let save: Save = serde_wasm_bindgen::from_value(data)?;
+    let options = WriteOptions::new(CompressionType::from_magic(
+        compression_type
+            .as_bytes()
+            .try_into()
+            .map_err(|_| JsError::new("Compression type must be 4 chars"))?,
+    )?);
+
+    let mut buffer = Vec::new();
+    save.write_with_options(&mut Cursor::new(&mut buffer), &options)?;
+
+    Ok(buffer.into_boxed_slice())
+}
+
+/// Renders a PNG minimap of a save, see `MinimapMode` for the modes.
+#[cfg(all(target_arch = "wasm32", feature = "minimap"))]
+#[wasm_bindgen]
+pub fn render_minimap(data: &[u8], mode: &str) -> Result<Box<[u8]>, JsError> {
+    console_error_panic_hook::set_once();
+    let save = Save::from_reader(&mut Cursor::new(data))?;
+    let minimap = Minimap::from_save(&save, mode.parse::<MinimapMode>()?)?;
 
-    buffer.into_boxed_slice()
+    Ok(minimap.to_png()?.into_boxed_slice())
 }
@@ -0,0 +1,621 @@
+use binrw::io::{Error, ErrorKind, Read, Result, Write};
+
+/// Size of the uncompressed data in each block of an LZO savegame
+pub const LZO_BUFFER_SIZE: usize = 8192;
+
+/// Maximum size of a compressed block, as OpenTTD sizes the buffer it reads blocks into
+const LZO_MAX_BLOCK_SIZE: usize = LZO_BUFFER_SIZE + LZO_BUFFER_SIZE / 16 + 64 + 3 + 8;
+
+const M2_MAX_OFFSET: usize = 0x0800;
+const M3_MAX_OFFSET: usize = 0x4000;
+const M4_MAX_OFFSET: usize = 0xbfff;
+
+const HASH_BITS: u32 = 14;
+
+/// Adler-32 checksum as calculated by `lzo_adler32`.
+/// OpenTTD seeds it with 0 rather than the usual 1.
+pub fn adler32(adler: u32, data: &[u8]) -> u32 {
+    const BASE: u32 = 65521;
+    // Largest n such that 255n(n+1)/2 + (n+1)(BASE-1) <= 2^32-1
+    const NMAX: usize = 5552;
+
+    let mut s1 = adler & 0xffff;
+    let mut s2 = adler >> 16;
+
+    for chunk in data.chunks(NMAX) {
+        for &byte in chunk {
+            s1 += byte as u32;
+            s2 += s1;
+        }
+        s1 %= BASE;
+        s2 %= BASE;
+    }
+
+    (s2 << 16) | s1
+}
+
+/// Decompresses the blocks of an LZO savegame until the end of the reader.
+///
+/// Every block is laid out as:
+///   u32 (BE) adler32 checksum of the next 4 bytes + compressed data
+///   u32 (BE) size of the compressed data
+///   LZO1X compressed data of up to 8192 uncompressed bytes
+pub fn decompress_blocks<R: Read>(reader: &mut R) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    LzoReader::new(reader).read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Compresses data into the blocks of an LZO savegame, see [`decompress_blocks`].
+pub fn compress_blocks<W: Write>(blob: &[u8], writer: &mut W) -> Result<()> {
+    let mut encoder = LzoWriter::new(writer);
+    encoder.write_all(blob)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Decompresses the blocks of an LZO savegame one block at a time
+pub struct LzoReader<R: Read> {
+    reader: R,
+    block: Vec<u8>,
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> LzoReader<R> {
+    pub fn new(reader: R) -> LzoReader<R> {
+        LzoReader {
+            reader,
+            block: Vec::with_capacity(LZO_MAX_BLOCK_SIZE + 4),
+            buffer: Vec::with_capacity(LZO_BUFFER_SIZE),
+            pos: 0,
+        }
+    }
+
+    /// Decompresses the next block into the buffer, returning false at the end of the reader
+    fn next_block(&mut self) -> Result<bool> {
+        let mut header = [0u8; 8];
+        let read = read_up_to(&mut self.reader, &mut header)?;
+        if read == 0 {
+            return Ok(false);
+        }
+        if read != header.len() {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "Truncated LZO block header",
+            ));
+        }
+
+        let checksum = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let size = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+
+        if size > LZO_MAX_BLOCK_SIZE {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Inconsistent LZO block size",
+            ));
+        }
+
+        self.block.clear();
+        self.block.extend_from_slice(&header[4..8]);
+        self.block.resize(size + 4, 0);
+        self.reader.read_exact(&mut self.block[4..])?;
+
+        if adler32(0, &self.block) != checksum {
+            return Err(Error::new(ErrorKind::InvalidData, "Bad LZO block checksum"));
+        }
+
+        self.buffer.clear();
+        self.pos = 0;
+        decompress(&self.block[4..], &mut self.buffer, LZO_BUFFER_SIZE)?;
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for LzoReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        // Loop as a block could decompress to nothing
+        while self.pos == self.buffer.len() {
+            if buf.is_empty() || !self.next_block()? {
+                return Ok(0);
+            }
+        }
+
+        let len = buf.len().min(self.buffer.len() - self.pos);
+        buf[..len].copy_from_slice(&self.buffer[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// Compresses data into the blocks of an LZO savegame as it's written.
+/// [`LzoWriter::finish`] must be called to write the last block.
+pub struct LzoWriter<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> LzoWriter<W> {
+    pub fn new(writer: W) -> LzoWriter<W> {
+        LzoWriter {
+            writer,
+            buffer: Vec::with_capacity(LZO_BUFFER_SIZE),
+        }
+    }
+
+    fn write_block(&mut self) -> Result<()> {
+        let mut block = vec![0u8; 4];
+        compress(&self.buffer, &mut block);
+
+        let size = (block.len() - 4) as u32;
+        block[0..4].copy_from_slice(&size.to_be_bytes());
+
+        self.writer.write_all(&adler32(0, &block).to_be_bytes())?;
+        self.writer.write_all(&block)?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Writes the remaining data as the last block and returns the inner writer
+    pub fn finish(mut self) -> Result<W> {
+        if !self.buffer.is_empty() {
+            self.write_block()?;
+        }
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for LzoWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let len = buf.len().min(LZO_BUFFER_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() == LZO_BUFFER_SIZE {
+            self.write_block()?;
+        }
+        Ok(len)
+    }
+
+    /// Only flushes the inner writer, a partial block isn't written until [`LzoWriter::finish`]
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Fills as much of `buf` as possible, only stopping early at the end of the reader
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+fn corrupt() -> Error {
+    Error::new(ErrorKind::InvalidData, "Corrupt LZO data")
+}
+
+/// Reader over the compressed input that errors instead of panicking on overruns
+struct Input<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Input<'a> {
+    fn byte(&mut self) -> Result<usize> {
+        let byte = *self.data.get(self.pos).ok_or_else(corrupt)?;
+        self.pos += 1;
+        Ok(byte as usize)
+    }
+
+    fn le16(&mut self) -> Result<usize> {
+        Ok(self.byte()? | (self.byte()? << 8))
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(corrupt)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    // A length of 0 in an instruction is followed by any number of zero bytes
+    // (each adding 255) and a final non zero byte.
+    fn extended_length(&mut self, base: usize) -> Result<usize> {
+        let mut length = base;
+        loop {
+            match self.byte()? {
+                0 => length += 255,
+                x => return Ok(length + x),
+            }
+        }
+    }
+}
+
+/// Decompresses an LZO1X stream, appending at most `max_len` bytes to `output`.
+///
+/// See <https://docs.kernel.org/staging/lzo.html> for a description of the format.
+pub fn decompress(src: &[u8], output: &mut Vec<u8>, max_len: usize) -> Result<()> {
+    let start = output.len();
+    let limit = start + max_len;
+    let mut input = Input { data: src, pos: 0 };
+
+    // Number of literals copied by the last instruction, 4 meaning 4 or more
+    let mut state = 0;
+
+    let literals = |input: &mut Input, output: &mut Vec<u8>, len: usize| -> Result<()> {
+        if output.len() + len > limit {
+            return Err(corrupt());
+        }
+        output.extend_from_slice(input.bytes(len)?);
+        Ok(())
+    };
+
+    if src.first().is_some_and(|&x| x > 17) {
+        let len = input.byte()? - 17;
+        literals(&mut input, output, len)?;
+        state = len.min(4);
+    }
+
+    loop {
+        let instruction = input.byte()?;
+
+        let (len, distance, next_state) = match instruction {
+            0..=15 if state == 0 => {
+                let len = match instruction {
+                    0 => input.extended_length(15)?,
+                    x => x,
+                } + 3;
+                literals(&mut input, output, len)?;
+                state = 4;
+                continue;
+            }
+            0..=15 if state < 4 => {
+                let distance = (input.byte()? << 2) + ((instruction >> 2) & 3) + 1;
+                (2, distance, instruction & 3)
+            }
+            0..=15 => {
+                let distance = (input.byte()? << 2) + ((instruction >> 2) & 3) + M2_MAX_OFFSET + 1;
+                (3, distance, instruction & 3)
+            }
+            16..=31 => {
+                let len = match instruction & 7 {
+                    0 => input.extended_length(7)?,
+                    x => x,
+                } + 2;
+                let value = input.le16()?;
+                let distance = M3_MAX_OFFSET + ((instruction & 8) << 11) + (value >> 2);
+                if distance == M3_MAX_OFFSET {
+                    break;
+                }
+                (len, distance, value & 3)
+            }
+            32..=63 => {
+                let len = match instruction & 31 {
+                    0 => input.extended_length(31)?,
+                    x => x,
+                } + 2;
+                let value = input.le16()?;
+                (len, (value >> 2) + 1, value & 3)
+            }
+            64..=127 => {
+                let distance = (input.byte()? << 3) + ((instruction >> 2) & 7) + 1;
+                (3 + ((instruction >> 5) & 1), distance, instruction & 3)
+            }
+            _ => {
+                let distance = (input.byte()? << 3) + ((instruction >> 2) & 7) + 1;
+                (5 + ((instruction >> 5) & 3), distance, instruction & 3)
+            }
+        };
+
+        if distance > output.len() - start || output.len() + len > limit {
+            return Err(corrupt());
+        }
+
+        // Matches can overlap with the bytes they produce so copy one at a time
+        let from = output.len() - distance;
+        for i in 0..len {
+            output.push(output[from + i]);
+        }
+
+        literals(&mut input, output, next_state)?;
+        state = next_state;
+    }
+
+    if input.pos != src.len() {
+        return Err(corrupt());
+    }
+
+    Ok(())
+}
+
+/// Appends an extended length (see [`Input::extended_length`])
+fn write_extended_length(output: &mut Vec<u8>, mut length: usize) {
+    while length > 255 {
+        output.push(0);
+        length -= 255;
+    }
+    output.push(length as u8);
+}
+
+/// Writes a run of literals. Up to 3 literals following a match are stored
+/// in the low 2 bits of `state_byte` (the byte of the match that holds them).
+fn write_literals(output: &mut Vec<u8>, literals: &[u8], state_byte: Option<usize>) {
+    let len = literals.len();
+    if len == 0 {
+        return;
+    }
+
+    match state_byte {
+        Some(pos) if len <= 3 => output[pos] |= len as u8,
+        None if len <= 238 => output.push(17 + len as u8),
+        _ if len <= 18 => output.push(len as u8 - 3),
+        _ => {
+            output.push(0);
+            write_extended_length(output, len - 18);
+        }
+    }
+
+    output.extend_from_slice(literals);
+}
+
+/// Writes a match, returning the position of the byte that holds the state
+fn write_match(output: &mut Vec<u8>, len: usize, distance: usize) -> usize {
+    if len <= 8 && distance <= M2_MAX_OFFSET {
+        let distance = distance - 1;
+        let pos = output.len();
+        output.push((((len - 1) << 5) | ((distance & 7) << 2)) as u8);
+        output.push((distance >> 3) as u8);
+        pos
+    } else if distance <= M3_MAX_OFFSET {
+        let distance = distance - 1;
+        if len <= 33 {
+            output.push(32 | (len - 2) as u8);
+        } else {
+            output.push(32);
+            write_extended_length(output, len - 33);
+        }
+        let pos = output.len();
+        output.push((distance << 2) as u8);
+        output.push((distance >> 6) as u8);
+        pos
+    } else {
+        let distance = distance - M3_MAX_OFFSET;
+        let high = ((distance >> 11) & 8) as u8;
+        if len <= 9 {
+            output.push(16 | high | (len - 2) as u8);
+        } else {
+            output.push(16 | high);
+            write_extended_length(output, len - 9);
+        }
+        let pos = output.len();
+        output.push((distance << 2) as u8);
+        output.push((distance >> 6) as u8);
+        pos
+    }
+}
+
+fn hash(data: &[u8]) -> usize {
+    let value = u32::from_le_bytes([data[0], data[1], data[2], 0]);
+    (value.wrapping_mul(0x9E3779B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Compresses data into an LZO1X stream appended to `output`.
+///
+/// This is a simple greedy compressor so the output won't be identical to
+/// liblzo's, but it can be read by any LZO1X decompressor. The output is never
+/// larger than storing `src` as literals.
+pub fn compress(src: &[u8], output: &mut Vec<u8>) {
+    let start = output.len();
+    compress_greedy(src, output);
+
+    // Splitting long runs of literals around short matches costs more than it saves on data
+    // that hardly compresses
+    let mut literals = Vec::with_capacity(src.len() + src.len() / 255 + 5);
+    write_literals(&mut literals, src, None);
+    literals.extend_from_slice(&[17, 0, 0]);
+    if output.len() - start > literals.len() {
+        output.truncate(start);
+        output.extend_from_slice(&literals);
+    }
+}
+
+fn compress_greedy(src: &[u8], output: &mut Vec<u8>) {
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let mut literal_start = 0;
+    let mut state_byte = None;
+    let mut pos = 0;
+
+    while pos + 3 <= src.len() {
+        let h = hash(&src[pos..]);
+        let candidate = table[h];
+        table[h] = pos;
+
+        if candidate == usize::MAX
+            || pos - candidate > M4_MAX_OFFSET
+            || src[candidate..candidate + 3] != src[pos..pos + 3]
+        {
+            pos += 1;
+            continue;
+        }
+
+        let len = 3 + src[pos + 3..]
+            .iter()
+            .zip(&src[candidate + 3..])
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        // Only M2 stores 3 bytes in less than 3. Taking longer ones could make the data grow,
+        // since literals after a match can need their own instruction.
+        if len == 3 && pos - candidate > M2_MAX_OFFSET {
+            pos += 1;
+            continue;
+        }
+
+        write_literals(output, &src[literal_start..pos], state_byte);
+        state_byte = Some(write_match(output, len, pos - candidate));
+
+        pos += len;
+        literal_start = pos;
+    }
+
+    write_literals(output, &src[literal_start..], state_byte);
+
+    // End of stream marker
+    output.extend_from_slice(&[17, 0, 0]);
+}
+
+#[cfg(test)]
+mod tests {
+    use binrw::io::{Cursor, Read};
+
+    use super::{
+        adler32, compress, compress_blocks, decompress, decompress_blocks, LzoReader,
+        LZO_BUFFER_SIZE, LZO_MAX_BLOCK_SIZE,
+    };
+
+    /// Bytes from a fixed xorshift sequence, limited to `symbols` values
+    fn noise(len: usize, symbols: u32) -> Vec<u8> {
+        let mut state = 0x2545_f491u32;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state % symbols) as u8
+            })
+            .collect()
+    }
+
+    /// Sizes of the compressed data of each block
+    fn block_sizes(mut compressed: &[u8]) -> Vec<usize> {
+        let mut sizes = vec![];
+        while !compressed.is_empty() {
+            let size = u32::from_be_bytes(compressed[4..8].try_into().unwrap()) as usize;
+            sizes.push(size);
+            compressed = &compressed[8 + size..];
+        }
+        sizes
+    }
+
+    fn round_trip(data: &[u8]) {
+        let mut compressed = vec![];
+        compress(data, &mut compressed);
+
+        let mut decompressed = vec![];
+        decompress(&compressed, &mut decompressed, data.len()).unwrap();
+        assert_eq!(data, &decompressed[..]);
+    }
+
+    #[test]
+    fn adler32_test() {
+        assert_eq!(adler32(1, b"Wikipedia"), 0x11E60398);
+        assert_eq!(adler32(0, b""), 0);
+    }
+
+    #[test]
+    fn decompress_test() {
+        // "abcabcabcabcX" compressed with LZO1X-999
+        let compressed = [
+            0x14, b'a', b'b', b'c', 0x27, 0x09, 0x00, b'X', 0x11, 0x00, 0x00,
+        ];
+        let mut decompressed = vec![];
+        decompress(&compressed, &mut decompressed, 64).unwrap();
+        assert_eq!(decompressed, b"abcabcabcabcX");
+
+        // Output limit is respected
+        assert!(decompress(&compressed, &mut vec![], 4).is_err());
+        // Truncated input is an error rather than a panic
+        assert!(decompress(&compressed[..8], &mut vec![], 64).is_err());
+    }
+
+    #[test]
+    fn compress_test() {
+        round_trip(b"");
+        round_trip(b"a");
+        round_trip(b"abcabcabcabcX");
+        round_trip(&[0xaa; 10000]);
+        round_trip(
+            &(0..20000u32)
+                .map(|x| (x * x / 7) as u8)
+                .collect::<Vec<u8>>(),
+        );
+        round_trip(&(0..300u32).map(|x| (x * 17) as u8).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn blocks_test() {
+        let data: Vec<u8> = (0..50000u32)
+            .map(|x| (x % 251) as u8 ^ (x / 1000) as u8)
+            .collect();
+
+        let mut compressed = vec![];
+        compress_blocks(&data, &mut compressed).unwrap();
+        let decompressed = decompress_blocks(&mut Cursor::new(&compressed)).unwrap();
+        assert_eq!(data, decompressed);
+
+        let mut streamed = vec![];
+        LzoReader::new(Cursor::new(&compressed))
+            .read_to_end(&mut streamed)
+            .unwrap();
+        assert_eq!(data, streamed);
+
+        compressed[10] ^= 1;
+        assert!(decompress_blocks(&mut Cursor::new(&compressed)).is_err());
+    }
+
+    #[test]
+    fn incompressible_blocks_test() {
+        for symbols in [32, 256] {
+            let data = noise(LZO_BUFFER_SIZE * 3, symbols);
+            round_trip(&data);
+
+            let mut compressed = vec![];
+            compress_blocks(&data, &mut compressed).unwrap();
+            let sizes = block_sizes(&compressed);
+            assert_eq!(sizes.len(), 3);
+            // Never more than the block stored as literals
+            assert!(sizes.iter().all(|&size| size <= LZO_BUFFER_SIZE + 37));
+            assert_eq!(
+                decompress_blocks(&mut Cursor::new(&compressed)).unwrap(),
+                data
+            );
+        }
+    }
+
+    /// Blocks as OpenTTD writes them, with liblzo's LZO1X-1
+    #[test]
+    fn liblzo_blocks_test() {
+        let data = [
+            noise(LZO_BUFFER_SIZE, 32),
+            (0..LZO_BUFFER_SIZE as u32)
+                .map(|x| (x / 96) as u8)
+                .collect(),
+            noise(LZO_BUFFER_SIZE, 256),
+            noise(1000, 4),
+        ]
+        .concat();
+
+        let mut compressed = vec![];
+        for block in data.chunks(LZO_BUFFER_SIZE) {
+            let lzo = lzo1x::compress(block, lzo1x::CompressLevel::new(3));
+            assert!(lzo.len() <= LZO_MAX_BLOCK_SIZE);
+            let mut sized = (lzo.len() as u32).to_be_bytes().to_vec();
+            sized.extend(lzo);
+            compressed.extend(adler32(0, &sized).to_be_bytes());
+            compressed.extend(sized);
+        }
+        assert!(block_sizes(&compressed)[0] > LZO_BUFFER_SIZE);
+
+        assert_eq!(
+            decompress_blocks(&mut Cursor::new(&compressed)).unwrap(),
+            data
+        );
+    }
+}
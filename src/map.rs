@@ -0,0 +1,439 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::charray::Maps;
+use crate::error::{Error, Result};
+use crate::save::{Chunk, ChunkValue, LazySave, Save};
+
+/// Tags of the chunks holding the tile arrays, in the order they're stored in a save
+const MAP_CHUNKS: [&[u8; 4]; 10] = [
+    b"MAPT", b"MAPH", b"MAPO", b"MAP2", b"M3LO", b"M3HI", b"MAP5", b"MAPE", b"MAP7", b"MAP8",
+];
+
+/// Version 194 moved tile heights from the low bits of `MAPT` to their own `MAPH` chunk
+pub const SLV_HEIGHT_8BIT: u16 = 194;
+/// Highest a tile can be in saves from before [`SLV_HEIGHT_8BIT`]
+pub const MAX_OLD_TILE_HEIGHT: u8 = 15;
+
+/// Owner of a tile that doesn't belong to any company
+pub const OWNER_NONE: u8 = 0x10;
+/// Owner of tiles belonging to a town
+pub const OWNER_TOWN: u8 = 0x0F;
+/// Owner of water tiles
+pub const OWNER_WATER: u8 = 0x11;
+
+/// The type stored in the upper 4 bits of a tile's type byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TileType {
+    Clear = 0,
+    Railway = 1,
+    Road = 2,
+    House = 3,
+    Trees = 4,
+    Station = 5,
+    Water = 6,
+    Void = 7,
+    Industry = 8,
+    TunnelBridge = 9,
+    Object = 10,
+}
+
+impl TileType {
+    pub fn from_raw(value: u8) -> Option<TileType> {
+        match value {
+            0 => Some(TileType::Clear),
+            1 => Some(TileType::Railway),
+            2 => Some(TileType::Road),
+            3 => Some(TileType::House),
+            4 => Some(TileType::Trees),
+            5 => Some(TileType::Station),
+            6 => Some(TileType::Water),
+            7 => Some(TileType::Void),
+            8 => Some(TileType::Industry),
+            9 => Some(TileType::TunnelBridge),
+            10 => Some(TileType::Object),
+            _ => None,
+        }
+    }
+}
+
+/// The bytes stored for a single tile, see OpenTTD's `docs/landscape.html` for their meaning per tile type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawTile {
+    /// Tile type (bits 4..7), bridge above (bits 2..3) and tropic zone (bits 0..1)
+    pub tile_type: u8,
+    pub height: u8,
+    pub m1: u8,
+    pub m2: u16,
+    pub m3: u8,
+    pub m4: u8,
+    pub m5: u8,
+    pub m6: u8,
+    pub m7: u8,
+    pub m8: u16,
+}
+
+impl RawTile {
+    /// The tile's type, None if the type byte holds a type OpenTTD doesn't have
+    pub fn get_type(&self) -> Option<TileType> {
+        TileType::from_raw(self.tile_type >> 4)
+    }
+
+    pub fn set_type(&mut self, tile_type: TileType) {
+        self.tile_type = (self.tile_type & 0x0F) | ((tile_type as u8) << 4);
+    }
+
+    /// 0 = normal, 1 = desert, 2 = rainforest
+    pub fn tropic_zone(&self) -> u8 {
+        self.tile_type & 0x03
+    }
+
+    pub fn set_tropic_zone(&mut self, zone: u8) {
+        self.tile_type = (self.tile_type & !0x03) | (zone & 0x03);
+    }
+
+    /// 0 = no bridge, 1 = bridge along the x axis, 2 = bridge along the y axis
+    pub fn bridge_above(&self) -> u8 {
+        (self.tile_type >> 2) & 0x03
+    }
+
+    /// Owner of the tile. Houses and industries don't store an owner so this is None for them.
+    pub fn owner(&self) -> Option<u8> {
+        match self.get_type() {
+            Some(TileType::House | TileType::Industry) => None,
+            Some(TileType::Void) => Some(OWNER_NONE),
+            _ => Some(self.m1 & 0x1F),
+        }
+    }
+
+    pub fn set_owner(&mut self, owner: u8) {
+        self.m1 = (self.m1 & !0x1F) | (owner & 0x1F);
+    }
+
+    /// Moves the height out of the type byte and the tropic zone and bridge bits into it, like
+    /// OpenTTD's `AfterLoadGame` does for saves before [`SLV_HEIGHT_8BIT`]
+    fn into_current_layout(self) -> RawTile {
+        RawTile {
+            tile_type: (self.tile_type & 0xF0) | (self.m6 & 0x03) | ((self.m6 >> 6) << 2),
+            height: self.tile_type & 0x0F,
+            m6: self.m6 & 0x3C,
+            ..self
+        }
+    }
+
+    fn into_old_layout(self) -> RawTile {
+        RawTile {
+            tile_type: (self.tile_type & 0xF0) | self.height,
+            height: 0,
+            m6: (self.m6 & 0x3C) | self.tropic_zone() | (self.bridge_above() << 6),
+            ..self
+        }
+    }
+}
+
+/// All tiles of a map, built from the map array chunks and the dimensions in `MAPS`.
+/// Tiles are stored row by row, so the tile at (x, y) is at index `y * dim_x + x`.
+///
+/// Tiles are always in the layout of current saves. Saves from before [`SLV_HEIGHT_8BIT`] keep
+/// the height in the low bits of `MAPT` and the tropic zone and bridge bits in `m6`, which are
+/// moved when reading and writing like OpenTTD's loader does.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TileMap {
+    pub dim_x: u32,
+    pub dim_y: u32,
+    pub tiles: Vec<RawTile>,
+}
+
+impl TileMap {
+    pub fn from_save(save: &Save) -> Result<TileMap> {
+        TileMap::from_chunks(save.base_version(), |tag| {
+            Ok(save.get(tag).map(Cow::Borrowed))
+        })
+    }
+
+    pub fn from_lazy_save(save: &LazySave) -> Result<TileMap> {
+        TileMap::from_chunks(save.base_version(), |tag| {
+            save.get(tag)
+                .map(|chunk| Ok(Cow::Owned(chunk.decode()?.value)))
+                .transpose()
+        })
+    }
+
+    fn from_chunks<'a>(
+        version: u16,
+        get: impl Fn(&[u8; 4]) -> Result<Option<Cow<'a, ChunkValue>>>,
+    ) -> Result<TileMap> {
+        let maps = get(b"MAPS")?.ok_or(Error::MissingChunk { tag: *b"MAPS" })?;
+        let Maps { dim_x, dim_y } = Maps::from_chunk(&maps)?;
+
+        let size = (dim_x as usize)
+            .checked_mul(dim_y as usize)
+            .ok_or_else(|| Error::Malformed {
+                message: format!("map size {dim_x}x{dim_y} is too large"),
+            })?;
+
+        // Older saves don't have all of the arrays so only the tile types are required
+        let mut arrays: Vec<Option<Cow<'a, ChunkValue>>> = Vec::new();
+        for tag in MAP_CHUNKS {
+            let chunk = get(tag)?;
+            let expected = size * map_value_size(tag);
+            match chunk.as_deref() {
+                Some(ChunkValue::ChRiff { data }) if data.len() == expected => {}
+                Some(ChunkValue::ChRiff { data }) => {
+                    return Err(Error::LengthMismatch {
+                        expected,
+                        actual: data.len(),
+                    }
+                    .in_chunk(*tag, 0))
+                }
+                Some(chunk) => {
+                    return Err(Error::BadChunkType {
+                        chunk_type: chunk.chunk_type(),
+                    }
+                    .in_chunk(*tag, 0))
+                }
+                None if tag == b"MAPT" => return Err(Error::MissingChunk { tag: *tag }),
+                None => {}
+            }
+            arrays.push(chunk);
+        }
+
+        let data = |i: usize| match arrays[i].as_deref() {
+            Some(ChunkValue::ChRiff { data }) => Some(data.as_slice()),
+            _ => None,
+        };
+        let byte = |i: usize, index: usize| data(i).map_or(0, |data| data[index]);
+        let word = |i: usize, index: usize| {
+            data(i).map_or(0, |data| {
+                u16::from_be_bytes([data[index * 2], data[index * 2 + 1]])
+            })
+        };
+
+        let tiles = (0..size)
+            .map(|index| {
+                let tile = RawTile {
+                    tile_type: byte(0, index),
+                    height: byte(1, index),
+                    m1: byte(2, index),
+                    m2: word(3, index),
+                    m3: byte(4, index),
+                    m4: byte(5, index),
+                    m5: byte(6, index),
+                    m6: byte(7, index),
+                    m7: byte(8, index),
+                    m8: word(9, index),
+                };
+                match version {
+                    version if version < SLV_HEIGHT_8BIT => tile.into_current_layout(),
+                    _ => tile,
+                }
+            })
+            .collect();
+
+        Ok(TileMap {
+            dim_x,
+            dim_y,
+            tiles,
+        })
+    }
+
+    /// The map array chunks for all of the tiles in the layout of a savegame version. Saves
+    /// before [`SLV_HEIGHT_8BIT`] can't have tiles higher than [`MAX_OLD_TILE_HEIGHT`].
+    pub fn chunks(&self, version: u16) -> Result<Vec<Chunk>> {
+        let old_layout = version < SLV_HEIGHT_8BIT;
+        if old_layout {
+            if let Some(tile) = self.tiles.iter().find(|t| t.height > MAX_OLD_TILE_HEIGHT) {
+                return Err(Error::Malformed {
+                    message: format!(
+                        "tiles in savegame version {version} can't be higher than \
+                         {MAX_OLD_TILE_HEIGHT}, not {}",
+                        tile.height
+                    ),
+                });
+            }
+        }
+
+        let chunks = MAP_CHUNKS
+            .iter()
+            .map(|tag| {
+                let mut data = Vec::with_capacity(self.tiles.len() * map_value_size(tag));
+                for tile in &self.tiles {
+                    let tile = match old_layout {
+                        true => tile.into_old_layout(),
+                        false => *tile,
+                    };
+                    match *tag {
+                        b"MAPT" => data.push(tile.tile_type),
+                        b"MAPH" => data.push(tile.height),
+                        b"MAPO" => data.push(tile.m1),
+                        b"MAP2" => data.extend_from_slice(&tile.m2.to_be_bytes()),
+                        b"M3LO" => data.push(tile.m3),
+                        b"M3HI" => data.push(tile.m4),
+                        b"MAP5" => data.push(tile.m5),
+                        b"MAPE" => data.push(tile.m6),
+                        b"MAP7" => data.push(tile.m7),
+                        _ => data.extend_from_slice(&tile.m8.to_be_bytes()),
+                    }
+                }
+                Chunk {
+                    tag: **tag,
+                    value: ChunkValue::ChRiff { data },
+                }
+            })
+            .collect();
+        Ok(chunks)
+    }
+
+    /// Replaces the map array chunks in the save with the tiles.
+    /// Arrays the save didn't have aren't added, and `MAPS` isn't changed.
+    pub fn write_to_save(&self, save: &mut Save) -> Result<()> {
+        for chunk in self.chunks(save.base_version())? {
+            if save.get(&chunk.tag).is_some() {
+                save.set(chunk);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the map array chunks in the save with the tiles.
+    /// Arrays the save didn't have aren't added, and `MAPS` isn't changed.
+    pub fn write_to_lazy_save(&self, save: &mut LazySave) -> Result<()> {
+        for chunk in self.chunks(save.base_version())? {
+            if save.get(&chunk.tag).is_some() {
+                save.set(&chunk)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.dim_x as usize + x as usize
+    }
+
+    pub fn tile(&self, x: u32, y: u32) -> Option<&RawTile> {
+        if x >= self.dim_x {
+            return None;
+        }
+        self.tiles.get(self.index(x, y))
+    }
+
+    pub fn tile_mut(&mut self, x: u32, y: u32) -> Option<&mut RawTile> {
+        if x >= self.dim_x {
+            return None;
+        }
+        let index = self.index(x, y);
+        self.tiles.get_mut(index)
+    }
+}
+
+/// Number of bytes stored per tile in a map array chunk
+fn map_value_size(tag: &[u8; 4]) -> usize {
+    match tag {
+        b"MAP2" | b"MAP8" => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use binrw::io::Cursor;
+    use std::fs::File;
+
+    use crate::error::Result;
+    use crate::map::{TileMap, TileType, SLV_HEIGHT_8BIT};
+    use crate::save::{ChunkValue, CompressionType, LazySave, Save, WriteOptions};
+
+    #[test]
+    fn read_tile_map() -> Result<()> {
+        for path in ["tests/tiny.sav", "tests/TinyVanillaTest.sav"] {
+            let save = Save::from_reader(&mut File::open(path)?)?;
+            let map = TileMap::from_save(&save)?;
+            assert_eq!((map.dim_x, map.dim_y), (64, 64));
+            assert_eq!(map.tiles.len(), 64 * 64);
+
+            // The south edges of the map are always void
+            let edge = map.tile(63, 10).unwrap();
+            assert_eq!(edge.get_type(), Some(TileType::Void));
+            assert!(map.tile(64, 0).is_none());
+            assert!(map.tiles.iter().all(|tile| tile.get_type().is_some()));
+
+            let lazy = LazySave::from_reader(&mut File::open(path)?)?;
+            assert_eq!(TileMap::from_lazy_save(&lazy)?, map);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_tile_map() -> Result<()> {
+        let mut save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let options = WriteOptions::new(CompressionType::OTTN);
+        let mut expected = vec![];
+        save.write_with_options(&mut Cursor::new(&mut expected), &options)?;
+
+        // Writing back an unchanged map keeps the save the same
+        let mut map = TileMap::from_save(&save)?;
+        map.write_to_save(&mut save)?;
+        let mut d = vec![];
+        save.write_with_options(&mut Cursor::new(&mut d), &options)?;
+        assert_eq!(d, expected);
+
+        let tile = map.tile_mut(10, 20).unwrap();
+        tile.m2 = 0x1234;
+        tile.set_owner(3);
+        map.write_to_save(&mut save)?;
+
+        let changed = TileMap::from_save(&save)?;
+        assert_eq!(changed.tile(10, 20).unwrap().m2, 0x1234);
+        assert_eq!(changed, map);
+
+        Ok(())
+    }
+
+    #[test]
+    fn old_tile_map_layout() -> Result<()> {
+        let mut save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let mut map = TileMap::from_save(&save)?;
+        // Animated houses use bits of m6 that old saves kept the tropic zone and bridges in
+        for tile in map.tiles.iter_mut() {
+            tile.m6 &= 0x3C;
+        }
+        let tile = map.tile_mut(10, 20).unwrap();
+        tile.height = 3;
+        tile.set_tropic_zone(2);
+        tile.m6 |= 0x04;
+        let index = map.index(10, 20);
+
+        // Make a save from before heights had their own chunk
+        save.version = SLV_HEIGHT_8BIT - 1;
+        for chunk in map.chunks(save.version)? {
+            match &chunk.tag {
+                b"MAPH" => save.chunks.retain(|chunk| chunk.tag != *b"MAPH"),
+                _ => save.set(chunk),
+            }
+        }
+        let Some(ChunkValue::ChRiff { data }) = save.get(b"MAPT") else {
+            panic!("MAPT isn't a RIFF chunk");
+        };
+        assert_eq!(data[index] & 0x0F, 3);
+        let Some(ChunkValue::ChRiff { data }) = save.get(b"MAPE") else {
+            panic!("MAPE isn't a RIFF chunk");
+        };
+        assert_eq!(data[index], 0x06);
+
+        let old = TileMap::from_save(&save)?;
+        assert_eq!(old, map);
+        assert_eq!(old.tiles[index].tropic_zone(), 2);
+        assert_eq!(old.tiles[index].m6, 0x04);
+
+        // Writing keeps the old layout, where tiles can't be as high
+        old.write_to_save(&mut save)?;
+        assert!(save.get(b"MAPH").is_none());
+        assert_eq!(TileMap::from_save(&save)?, map);
+        map.tiles[index].height = 16;
+        assert!(map.write_to_save(&mut save).is_err());
+
+        Ok(())
+    }
+}
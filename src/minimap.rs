@@ -0,0 +1,473 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::industry::Industry;
+use crate::map::{RawTile, TileMap, OWNER_TOWN, OWNER_WATER};
+use crate::save::{LazySave, Save};
+use crate::tile::{
+    ClearGround, RailKind, RoadKind, StationType, Tile, TransportType, TreeGround, WaterClass,
+};
+
+type Colour = [u8; 3];
+
+const BLACK: Colour = [0x00, 0x00, 0x00];
+const WATER: Colour = [0x34, 0x5c, 0xc4];
+const TOWN: Colour = [0xd4, 0xd4, 0xd4];
+const INDUSTRY: Colour = [0xc0, 0x50, 0x48];
+const BUILDING: Colour = [0x8c, 0x8c, 0x8c];
+const LAND: Colour = [0x48, 0x6c, 0x34];
+const RAIL: Colour = [0x7c, 0x54, 0x30];
+const ROAD: Colour = [0xa8, 0xa8, 0xa8];
+
+/// Colours used for companies 0 to 14, in the order of OpenTTD's company colours
+const COMPANY_COLOURS: [Colour; 15] = [
+    [0x1c, 0x3c, 0xa0],
+    [0x7c, 0xb0, 0xe8],
+    [0xe0, 0x68, 0xa0],
+    [0xe8, 0xc8, 0x20],
+    [0xd0, 0x24, 0x24],
+    [0x58, 0xb8, 0xc0],
+    [0x44, 0xb4, 0x30],
+    [0x1c, 0x6c, 0x1c],
+    [0x20, 0x58, 0x84],
+    [0xc4, 0xa8, 0x78],
+    [0x94, 0x5c, 0xc0],
+    [0x8c, 0x2c, 0x78],
+    [0xf0, 0x90, 0x30],
+    [0x7c, 0x48, 0x24],
+    [0x80, 0x80, 0x80],
+];
+
+/// Height colours from sea level to the highest tile, blended linearly in between
+const HEIGHT_STOPS: [(u32, Colour); 4] = [
+    (0, [0x28, 0x60, 0x20]),
+    (128, [0x9c, 0x98, 0x44]),
+    (200, [0x88, 0x64, 0x3c]),
+    (255, [0xf4, 0xf4, 0xf4]),
+];
+
+/// What to show on a minimap, matching the modes of the in-game smallmap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MinimapMode {
+    /// Land coloured by height
+    Contours,
+    /// Grass, rough land, fields, trees and other ground
+    Vegetation,
+    /// Tiles coloured by the company owning them
+    Owners,
+    /// Rail, roads and stations by type
+    Routes,
+    /// Industries, each type in its own colour
+    Industries,
+}
+
+impl FromStr for MinimapMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<MinimapMode> {
+        match s.to_ascii_lowercase().as_str() {
+            "contours" | "height" => Ok(MinimapMode::Contours),
+            "vegetation" => Ok(MinimapMode::Vegetation),
+            "owners" => Ok(MinimapMode::Owners),
+            "routes" => Ok(MinimapMode::Routes),
+            "industries" => Ok(MinimapMode::Industries),
+            _ => Err(Error::Malformed {
+                message: format!("unknown minimap mode {s:?}"),
+            }),
+        }
+    }
+}
+
+/// An RGB image of a map with one pixel per tile.
+///
+/// The map is drawn top down rather than rotated like in game,
+/// so the north corner is at the top left and x grows to the right.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Minimap {
+    pub width: u32,
+    pub height: u32,
+    /// 3 bytes per pixel, row by row
+    pub pixels: Vec<u8>,
+}
+
+impl Minimap {
+    /// Renders the tiles alone, which don't say the type of their industry, so industries are
+    /// all drawn in the same colour
+    pub fn render(map: &TileMap, mode: MinimapMode) -> Minimap {
+        Minimap::render_with_industries(map, mode, &BTreeMap::new())
+    }
+
+    /// Like `render`, with the type of each industry by its index in the pool
+    pub fn render_with_industries(
+        map: &TileMap,
+        mode: MinimapMode,
+        industry_types: &BTreeMap<u16, u8>,
+    ) -> Minimap {
+        let max_height = map.tiles.iter().map(|tile| tile.height).max().unwrap_or(0);
+        let mut pixels = Vec::with_capacity(map.tiles.len() * 3);
+        for raw in &map.tiles {
+            pixels.extend_from_slice(&tile_colour(raw, mode, max_height, industry_types));
+        }
+
+        Minimap {
+            width: map.dim_x,
+            height: map.dim_y,
+            pixels,
+        }
+    }
+
+    /// Renders the map of a save, reading the industries from `INDY` in the industries mode.
+    /// When they can't be read, industries are drawn like `render` does.
+    pub fn from_save(save: &Save, mode: MinimapMode) -> Result<Minimap> {
+        let industries = match mode {
+            MinimapMode::Industries => Industry::from_save(save).unwrap_or_default(),
+            _ => vec![],
+        };
+        Ok(Minimap::render_with_industries(
+            &TileMap::from_save(save)?,
+            mode,
+            &industry_types(&industries),
+        ))
+    }
+
+    pub fn from_lazy_save(save: &LazySave, mode: MinimapMode) -> Result<Minimap> {
+        let industries = match mode {
+            MinimapMode::Industries => Industry::from_lazy_save(save).unwrap_or_default(),
+            _ => vec![],
+        };
+        Ok(Minimap::render_with_industries(
+            &TileMap::from_lazy_save(save)?,
+            mode,
+            &industry_types(&industries),
+        ))
+    }
+
+    /// Scales the image to the given size, picking the nearest tile for each pixel. An empty
+    /// image has nothing to scale and stays empty.
+    pub fn resize(&self, width: u32, height: u32) -> Minimap {
+        if self.pixels.is_empty() {
+            return self.clone();
+        }
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 3);
+        for y in 0..height as u64 {
+            let src_y = (y * self.height as u64 / height as u64) as usize;
+            for x in 0..width as u64 {
+                let src_x = (x * self.width as u64 / width as u64) as usize;
+                let index = (src_y * self.width as usize + src_x) * 3;
+                pixels.extend_from_slice(&self.pixels[index..index + 3]);
+            }
+        }
+
+        Minimap {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        writer.write_image_data(&self.pixels).map_err(png_error)?;
+        writer.finish().map_err(png_error)
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        self.write_png(&mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+fn industry_types(industries: &[Industry]) -> BTreeMap<u16, u8> {
+    industries
+        .iter()
+        .map(|industry| (industry.index as u16, industry.industry_type))
+        .collect()
+}
+
+fn png_error(error: png::EncodingError) -> Error {
+    match error {
+        png::EncodingError::IoError(e) => Error::Io(e),
+        e => Error::Malformed {
+            message: e.to_string(),
+        },
+    }
+}
+
+fn tile_colour(
+    raw: &RawTile,
+    mode: MinimapMode,
+    max_height: u8,
+    industry_types: &BTreeMap<u16, u8>,
+) -> Colour {
+    // Tiles with bits we don't understand are drawn as plain land
+    let tile = match raw.decode() {
+        Ok(Tile::Void) => return BLACK,
+        Ok(tile) => tile,
+        Err(_) => return LAND,
+    };
+
+    match mode {
+        MinimapMode::Contours => contour_colour(&tile, raw.height, max_height),
+        MinimapMode::Vegetation => vegetation_colour(&tile),
+        MinimapMode::Owners => owner_colour(&tile, raw),
+        MinimapMode::Routes => route_colour(&tile).unwrap_or_else(|| background(&tile, raw)),
+        MinimapMode::Industries => match tile {
+            Tile::Industry(industry) => industry_types
+                .get(&industry.industry)
+                .map_or(INDUSTRY, |&industry_type| industry_colour(industry_type)),
+            _ => background(&tile, raw),
+        },
+    }
+}
+
+fn is_water(tile: &Tile) -> bool {
+    match tile {
+        Tile::Water(_) => true,
+        Tile::Station(station) => matches!(station.station_type, StationType::Buoy),
+        _ => false,
+    }
+}
+
+fn contour_colour(tile: &Tile, height: u8, max_height: u8) -> Colour {
+    if is_water(tile) {
+        return WATER;
+    }
+
+    // Stretch the heights so flat maps still show some contours
+    let height = height as u32 * 255 / max_height.max(1) as u32;
+    let upper = HEIGHT_STOPS
+        .iter()
+        .position(|&(stop, _)| stop >= height)
+        .unwrap_or(HEIGHT_STOPS.len() - 1)
+        .max(1);
+    let (low, from) = HEIGHT_STOPS[upper - 1];
+    let (high, to) = HEIGHT_STOPS[upper];
+    blend(from, to, height.saturating_sub(low), high - low)
+}
+
+fn vegetation_colour(tile: &Tile) -> Colour {
+    match tile {
+        Tile::Clear(clear) if clear.snow => [0xf0, 0xf0, 0xf8],
+        Tile::Clear(clear) => match clear.ground {
+            // Grass gets greener as it grows back
+            ClearGround::Grass => blend(
+                [0x94, 0x8c, 0x50],
+                [0x48, 0x9c, 0x34],
+                clear.density as u32,
+                3,
+            ),
+            ClearGround::Rough => [0x6c, 0x74, 0x3c],
+            ClearGround::Rocks => [0x8c, 0x88, 0x80],
+            ClearGround::Fields => [0xc8, 0xb0, 0x48],
+            ClearGround::Snow => [0xf0, 0xf0, 0xf8],
+            ClearGround::Desert => [0xe0, 0xc8, 0x8c],
+        },
+        Tile::Trees(trees) => match trees.ground {
+            TreeGround::SnowDesert | TreeGround::RoughSnow => [0x8c, 0xb0, 0x8c],
+            _ => blend(
+                [0x3c, 0x80, 0x28],
+                [0x18, 0x4c, 0x14],
+                trees.count as u32,
+                3,
+            ),
+        },
+        Tile::House(_) => TOWN,
+        Tile::Industry(_) => INDUSTRY,
+        tile if is_water(tile) => WATER,
+        _ => BUILDING,
+    }
+}
+
+fn owner_colour(tile: &Tile, raw: &RawTile) -> Colour {
+    if is_water(tile) {
+        return WATER;
+    }
+
+    match tile {
+        Tile::House(_) => TOWN,
+        Tile::Industry(_) => INDUSTRY,
+        _ => match raw.owner() {
+            Some(owner) if (owner as usize) < COMPANY_COLOURS.len() => {
+                COMPANY_COLOURS[owner as usize]
+            }
+            Some(OWNER_TOWN) => TOWN,
+            Some(OWNER_WATER) => WATER,
+            _ => LAND,
+        },
+    }
+}
+
+fn route_colour(tile: &Tile) -> Option<Colour> {
+    match tile {
+        Tile::Rail(rail) => Some(match rail.kind {
+            RailKind::Track { .. } => RAIL,
+            RailKind::Depot { .. } => [0x5c, 0x38, 0x18],
+        }),
+        Tile::Road(road) => Some(match road.kind {
+            RoadKind::Crossing { .. } => RAIL,
+            _ => ROAD,
+        }),
+        Tile::TunnelBridge(tunnel_bridge) => match tunnel_bridge.transport_type {
+            TransportType::Rail => Some(RAIL),
+            TransportType::Road => Some(ROAD),
+            TransportType::Water => None,
+        },
+        Tile::Station(station) => Some(match station.station_type {
+            StationType::Rail | StationType::Waypoint => [0xe0, 0x38, 0x30],
+            StationType::Airport => [0x70, 0xc4, 0xf8],
+            StationType::Truck => [0xf0, 0x90, 0x28],
+            StationType::Bus => [0xf0, 0xd8, 0x30],
+            StationType::Dock | StationType::Buoy | StationType::Oilrig => [0x24, 0x44, 0xe8],
+        }),
+        _ => None,
+    }
+}
+
+/// Dimmed land, used behind the routes and industries
+fn background(tile: &Tile, raw: &RawTile) -> Colour {
+    match tile {
+        _ if is_water(tile) => [0x24, 0x34, 0x70],
+        Tile::Station(station) if station.water_class != WaterClass::Invalid => [0x24, 0x34, 0x70],
+        Tile::House(_) => [0x70, 0x70, 0x70],
+        _ => blend(
+            [0x2c, 0x40, 0x24],
+            [0x5c, 0x64, 0x50],
+            raw.height as u32,
+            255,
+        ),
+    }
+}
+
+/// Picks a bright colour for an industry type that stays the same between renders
+fn industry_colour(industry_type: u8) -> Colour {
+    let hash = (industry_type as u32).wrapping_mul(0x9E37_79B9);
+    let [r, g, b, _] = hash.to_be_bytes();
+    [r | 0x60, g | 0x40, b | 0x20]
+}
+
+fn blend(from: Colour, to: Colour, amount: u32, max: u32) -> Colour {
+    let amount = amount.min(max);
+    let mut colour = [0; 3];
+    for i in 0..3 {
+        colour[i] = ((from[i] as u32 * (max - amount) + to[i] as u32 * amount) / max.max(1)) as u8;
+    }
+    colour
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::error::Result;
+    use crate::industry::Industry;
+    use crate::map::TileMap;
+    use crate::minimap::{industry_colour, Minimap, MinimapMode, INDUSTRY};
+    use crate::save::Save;
+    use crate::tile::Tile;
+
+    #[test]
+    fn render_minimap() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let map = TileMap::from_save(&save)?;
+
+        for mode in [
+            MinimapMode::Contours,
+            MinimapMode::Vegetation,
+            MinimapMode::Owners,
+            MinimapMode::Routes,
+            MinimapMode::Industries,
+        ] {
+            let minimap = Minimap::render(&map, mode);
+            assert_eq!((minimap.width, minimap.height), (64, 64));
+            assert_eq!(minimap.pixels.len(), 64 * 64 * 3);
+
+            // Void tiles on the edge are black
+            let index = map.index(63, 10) * 3;
+            assert_eq!(&minimap.pixels[index..index + 3], &[0, 0, 0]);
+
+            let png = minimap.resize(128, 32).to_png()?;
+            let decoder = png::Decoder::new(png.as_slice());
+            let reader = decoder.read_info().unwrap();
+            assert_eq!((reader.info().width, reader.info().height), (128, 32));
+        }
+
+        assert_eq!("Routes".parse::<MinimapMode>()?, MinimapMode::Routes);
+        assert!("satellite".parse::<MinimapMode>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn colour_industries_by_type() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let map = TileMap::from_save(&save)?;
+        let industries = Industry::from_save(&save)?;
+        let minimap = Minimap::from_save(&save, MinimapMode::Industries)?;
+
+        let mut types = vec![];
+        for (index, raw) in map.tiles.iter().enumerate() {
+            let Ok(Tile::Industry(tile)) = raw.decode() else {
+                continue;
+            };
+            let industry = industries
+                .iter()
+                .find(|industry| industry.index == tile.industry as u32)
+                .unwrap();
+            let colour = industry_colour(industry.industry_type);
+            assert_eq!(&minimap.pixels[index * 3..index * 3 + 3], &colour);
+            types.push(industry.industry_type);
+        }
+        types.sort();
+        types.dedup();
+        assert!(types.len() > 1);
+
+        // Different industries of the same type share a colour
+        let same_type = industries
+            .iter()
+            .map(|industry| (industry.index as u16, types[0]))
+            .collect();
+        let minimap = Minimap::render_with_industries(&map, MinimapMode::Industries, &same_type);
+        for (index, raw) in map.tiles.iter().enumerate() {
+            if let Ok(Tile::Industry(_)) = raw.decode() {
+                let colour = industry_colour(types[0]);
+                assert_eq!(&minimap.pixels[index * 3..index * 3 + 3], &colour);
+            }
+        }
+
+        // Without the industries they're all drawn the same
+        let minimap = Minimap::render(&map, MinimapMode::Industries);
+        let index = map
+            .tiles
+            .iter()
+            .position(|raw| matches!(raw.decode(), Ok(Tile::Industry(_))))
+            .unwrap();
+        assert_eq!(&minimap.pixels[index * 3..index * 3 + 3], &INDUSTRY);
+
+        // And when the industries can't be read
+        let mut save = save;
+        save.chunks.retain(|chunk| chunk.tag != *b"INDY");
+        assert_eq!(Minimap::from_save(&save, MinimapMode::Industries)?, minimap);
+
+        Ok(())
+    }
+
+    #[test]
+    fn resize_empty_minimap() {
+        let map = TileMap {
+            dim_x: 0,
+            dim_y: 0,
+            tiles: vec![],
+        };
+        let minimap = Minimap::render(&map, MinimapMode::Contours).resize(64, 64);
+        assert_eq!((minimap.width, minimap.height), (0, 0));
+        assert!(minimap.pixels.is_empty());
+    }
+}
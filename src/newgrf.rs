@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+
+use crate::chtable::ChTableElement;
+use crate::error::{Error, Result};
+use crate::jgr::SLXI;
+use crate::save::{ChArrayElement, Chunk, ChunkValue, LazySave, Save};
+use crate::schema::ChunkSchema;
+use crate::table::{from_table, to_row, to_table};
+
+/// Number of parameters saved for each NewGRF, of which `num_params` are used
+pub const GRF_MAX_PARAMS: usize = 0x80;
+
+/// The NewGRF was made for the Windows palette rather than the DOS one
+pub const GRFP_USE_WINDOWS: u8 = 0x01;
+/// The palette the NewGRF asks for in its action 14, if any
+pub const GRFP_GRF_MASK: u8 = 0x0C;
+pub const GRFP_GRF_DOS: u8 = 0x04;
+pub const GRFP_GRF_WINDOWS: u8 = 0x08;
+/// The NewGRF needs a 32bpp blitter
+pub const GRFP_BLT_32BPP: u8 = 0x10;
+
+/// A NewGRF the game was using, from the `NGRF` chunk. OpenTTD looks the file up by
+/// `grfid` and `md5sum` when loading, falling back to `filename` only to report it missing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrfConfig {
+    /// Path relative to the NewGRF search paths, like `opengfx/ogfx1_base.grf`
+    pub filename: String,
+    #[serde(rename = "ident.grfid")]
+    pub grfid: u32,
+    #[serde(rename = "ident.md5sum")]
+    pub md5sum: [u8; 16],
+    /// Version from the NewGRF's action 14, 0 in saves before version 151
+    #[serde(default)]
+    pub version: u32,
+    pub param: Vec<u32>,
+    pub num_params: u8,
+    /// `GRFP_*` flags for the palette and blitter the NewGRF needs, 0 in saves before
+    /// version 101
+    #[serde(default)]
+    pub palette: u8,
+}
+
+impl GrfConfig {
+    pub fn from_save(save: &Save) -> Result<Vec<GrfConfig>> {
+        let chunk = save
+            .get(b"NGRF")
+            .ok_or(Error::MissingChunk { tag: *b"NGRF" })?;
+        GrfConfig::from_chunk(chunk, save.base_version())
+    }
+
+    pub fn from_lazy_save(save: &LazySave) -> Result<Vec<GrfConfig>> {
+        let chunk = save
+            .get(b"NGRF")
+            .ok_or(Error::MissingChunk { tag: *b"NGRF" })?
+            .decode()?;
+        GrfConfig::from_chunk(&chunk.value, save.base_version())
+    }
+
+    /// Parses the NewGRFs in the contents of the `NGRF` chunk, in the order they're loaded
+    pub fn from_chunk(chunk: &ChunkValue, version: u16) -> Result<Vec<GrfConfig>> {
+        let grfs = match chunk {
+            ChunkValue::ChArray { elements } => {
+                let schema = ChunkSchema::find(*b"NGRF", version)?;
+                elements
+                    .iter()
+                    .map(|element| {
+                        from_table(&schema.read_all(&element.data, version, &SLXI::default())?)
+                    })
+                    .collect()
+            }
+            ChunkValue::ChTable { elements, .. } => elements
+                .iter()
+                .map(|element| from_table(&element.data))
+                .collect(),
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        grfs.map_err(|e| e.in_chunk(*b"NGRF", 0))
+    }
+
+    pub fn write_to_save(grfs: &[GrfConfig], save: &mut Save) -> Result<()> {
+        let mut chunk = save
+            .get(b"NGRF")
+            .ok_or(Error::MissingChunk { tag: *b"NGRF" })?
+            .clone();
+        GrfConfig::write_to_chunk(grfs, &mut chunk, save.base_version())?;
+        save.set(Chunk {
+            tag: *b"NGRF",
+            value: chunk,
+        });
+        Ok(())
+    }
+
+    pub fn write_to_lazy_save(grfs: &[GrfConfig], save: &mut LazySave) -> Result<()> {
+        let mut chunk = save
+            .get(b"NGRF")
+            .ok_or(Error::MissingChunk { tag: *b"NGRF" })?
+            .decode()?;
+        GrfConfig::write_to_chunk(grfs, &mut chunk.value, save.base_version())?;
+        save.set(&chunk)
+    }
+
+    /// Replaces the contents of the `NGRF` chunk with the NewGRFs, keeping its layout.
+    /// Unlike pools the list can grow or shrink, nothing else refers to its entries.
+    pub fn write_to_chunk(grfs: &[GrfConfig], chunk: &mut ChunkValue, version: u16) -> Result<()> {
+        let result = match chunk {
+            ChunkValue::ChArray { elements } => {
+                ChunkSchema::find(*b"NGRF", version).and_then(|schema| {
+                    grfs.iter()
+                        .map(|grf| {
+                            let data = schema.write(&to_row(grf)?, version, &SLXI::default())?;
+                            Ok(ChArrayElement { data })
+                        })
+                        .collect::<Result<Vec<_>>>()
+                        .map(|new| *elements = new)
+                })
+            }
+            ChunkValue::ChTable { header, elements } => grfs
+                .iter()
+                .map(|grf| {
+                    Ok(ChTableElement {
+                        data: to_table(grf, header)?,
+                        leftover: vec![],
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+                .map(|new| *elements = new),
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        result.map_err(|e| e.in_chunk(*b"NGRF", 0))
+    }
+
+    /// The parameters the NewGRF uses
+    pub fn params(&self) -> &[u32] {
+        &self.param[..(self.num_params as usize).min(self.param.len())]
+    }
+
+    /// Replaces the parameters, which can't be more than `GRF_MAX_PARAMS`
+    pub fn set_params(&mut self, params: &[u32]) -> Result<()> {
+        if params.len() > GRF_MAX_PARAMS {
+            return Err(Error::Malformed {
+                message: format!("a NewGRF can't have {} parameters", params.len()),
+            });
+        }
+        self.param = params.to_vec();
+        self.param.resize(GRF_MAX_PARAMS, 0);
+        self.num_params = params.len() as u8;
+        Ok(())
+    }
+
+    /// The MD5 checksum as lowercase hex
+    pub fn md5sum_hex(&self) -> String {
+        self.md5sum.iter().map(|x| format!("{x:02x}")).collect()
+    }
+
+    /// Parses a checksum in hex, like `md5sum` prints it
+    pub fn parse_md5sum(hex: &str) -> Result<[u8; 16]> {
+        let invalid = || Error::Malformed {
+            message: format!("{hex} isn't an MD5 checksum"),
+        };
+        if hex.len() != 32 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let mut md5sum = [0; 16];
+        for (i, byte) in md5sum.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(md5sum)
+    }
+
+    /// Points the entry at another version of the NewGRF, for moving a save to a newer release
+    /// without opening it in the game
+    pub fn replace_file(&mut self, filename: &str, md5sum: [u8; 16]) {
+        self.filename = filename.to_string();
+        self.md5sum = md5sum;
+    }
+
+    /// The first NewGRF with a GRF ID
+    pub fn find(grfs: &[GrfConfig], grfid: u32) -> Option<&GrfConfig> {
+        grfs.iter().find(|grf| grf.grfid == grfid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use binrw::io::Cursor;
+
+    use crate::error::Result;
+    use crate::newgrf::{GrfConfig, GRFP_GRF_WINDOWS, GRF_MAX_PARAMS};
+    use crate::save::Save;
+
+    fn opengfx() -> GrfConfig {
+        let mut grf = GrfConfig {
+            filename: "opengfx/ogfx1_base.grf".to_string(),
+            grfid: 0x0744_4f47,
+            md5sum: GrfConfig::parse_md5sum("a1b2c3d4e5f60718293a4b5c6d7e8f90").unwrap(),
+            version: 7,
+            param: vec![],
+            num_params: 0,
+            palette: GRFP_GRF_WINDOWS,
+        };
+        grf.set_params(&[1, 2, 3]).unwrap();
+        grf
+    }
+
+    fn round_trip(path: &str) -> Result<()> {
+        let mut save = Save::from_reader(&mut File::open(path)?)?;
+        assert!(GrfConfig::from_save(&save)?.is_empty());
+
+        let mut grfs = vec![opengfx(), opengfx()];
+        grfs[1].grfid = 0x0102_0304;
+        GrfConfig::write_to_save(&grfs, &mut save)?;
+
+        let mut buffer = Vec::new();
+        save.to_writer(&mut Cursor::new(&mut buffer))?;
+        let save = Save::from_reader(&mut Cursor::new(buffer))?;
+        let read = GrfConfig::from_save(&save)?;
+        assert_eq!(read, grfs);
+        assert_eq!(read[0].params(), [1, 2, 3]);
+        assert_eq!(read[0].param.len(), GRF_MAX_PARAMS);
+        assert_eq!(
+            GrfConfig::find(&read, 0x0102_0304).map(|grf| grf.md5sum_hex()),
+            Some("a1b2c3d4e5f60718293a4b5c6d7e8f90".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn grf_array_round_trip() -> Result<()> {
+        round_trip("tests/tiny.sav")
+    }
+
+    #[test]
+    fn grf_table_round_trip() -> Result<()> {
+        round_trip("tests/TinyVanillaTest.sav")
+    }
+
+    #[test]
+    fn edit_grf() {
+        let mut grf = opengfx();
+        grf.replace_file(
+            "opengfx/ogfx1_base_new.grf",
+            GrfConfig::parse_md5sum("00112233445566778899AABBCCDDEEFF").unwrap(),
+        );
+        assert_eq!(grf.md5sum_hex(), "00112233445566778899aabbccddeeff");
+        assert!(GrfConfig::parse_md5sum("0011").is_err());
+        assert!(GrfConfig::parse_md5sum("zz112233445566778899aabbccddeeff").is_err());
+        assert!(grf.set_params(&[0; GRF_MAX_PARAMS + 1]).is_err());
+        grf.set_params(&[]).unwrap();
+        assert!(grf.params().is_empty());
+    }
+}
@@ -0,0 +1,804 @@
+use std::collections::BTreeMap;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::jgr::SLXI;
+use crate::save::{ChArrayElement, Chunk, ChunkValue, LazySave, Save};
+use crate::schema::ChunkSchema;
+use crate::table::{from_table, update_table};
+use crate::tile::{gb, has_bit, invalid, raw_enum, sb16, sb8};
+use crate::vehicle::Vehicle;
+
+// Bit layouts follow the accessors of OpenTTD's order_base.h as of OpenTTD 13
+
+/// Speed limit of an order that doesn't have one
+pub const NO_MAX_SPEED: u16 = 0xFFFF;
+/// `refit_cargo` of an order that doesn't refit
+pub const CT_NO_REFIT: u8 = 0xFE;
+
+raw_enum!(
+    /// Whether a vehicle loads at a station, from the bits of `OrderLoadFlags`
+    LoadType {
+        IfPossible = 0,
+        FullLoad = 2,
+        FullLoadAny = 3,
+        NoLoad = 4,
+    }
+);
+
+raw_enum!(
+    /// Whether a vehicle unloads at a station, from the bits of `OrderUnloadFlags`
+    UnloadType {
+        IfPossible = 0,
+        Unload = 1,
+        Transfer = 2,
+        NoUnload = 4,
+    }
+);
+
+/// An order as the `ORDR` chunk stores it, in both the array and the table layout.
+/// Fields older saves don't have get the values OpenTTD gives them when loading.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawOrder {
+    /// Order type in the low 4 bits, the rest depends on it
+    #[serde(rename = "type")]
+    pub order_type: u8,
+    pub flags: u8,
+    pub dest: u16,
+    /// Next order of the list, as a reference
+    pub next: u32,
+    #[serde(default = "no_refit")]
+    pub refit_cargo: u8,
+    #[serde(default)]
+    pub wait_time: u16,
+    #[serde(default)]
+    pub travel_time: u16,
+    #[serde(default = "no_max_speed")]
+    pub max_speed: u16,
+}
+
+fn no_refit() -> u8 {
+    CT_NO_REFIT
+}
+
+fn no_max_speed() -> u16 {
+    NO_MAX_SPEED
+}
+
+/// An order decoded into what it does in game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Order {
+    pub kind: OrderKind,
+    /// Next order of the list, as a reference
+    pub next: u32,
+    /// Cargo type to refit to, 0xFE for none
+    pub refit_cargo: u8,
+    /// Timetabled time to wait at the destination, in ticks
+    pub wait_time: u16,
+    /// Timetabled time to travel to the destination, in ticks
+    pub travel_time: u16,
+    /// Whether `wait_time` was set by the player. Conditional orders have no flag for it, so
+    /// like in OpenTTD it's whether `wait_time` isn't 0.
+    pub wait_timetabled: bool,
+    /// Whether `travel_time` was set by the player, for conditional orders whether it isn't 0
+    pub travel_timetabled: bool,
+    /// Speed limit on the way to the destination, [`NO_MAX_SPEED`] for none
+    pub max_speed: u16,
+}
+
+/// The type of an order and the fields only that type has.
+///
+/// `non_stop` holds OpenTTD's `OrderNonStopFlags`: bit 0 skips intermediate stations and bit 1
+/// skips the destination itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum OrderKind {
+    Nothing,
+    GotoStation {
+        station: u16,
+        non_stop: u8,
+        /// Where in the platform trains stop: 0 = near end, 1 = middle, 2 = far end
+        stop_location: u8,
+        load: LoadType,
+        unload: UnloadType,
+    },
+    GotoDepot {
+        /// Depot index, or the nearest depot when `nearest` is set
+        depot: u16,
+        non_stop: u8,
+        /// Only go there when the vehicle needs servicing
+        service: bool,
+        /// Stop in the depot once there
+        halt: bool,
+        nearest: bool,
+    },
+    Loading {
+        station: u16,
+    },
+    LeaveStation,
+    Dummy,
+    GotoWaypoint {
+        waypoint: u16,
+        non_stop: u8,
+    },
+    /// Jump to `skip_to` when the condition holds
+    Conditional {
+        /// OpenTTD's `OrderConditionVariable`, like 0 = load percentage or 5 = always
+        variable: u8,
+        /// OpenTTD's `OrderConditionComparator`, like 0 = equal or 2 = less than
+        comparator: u8,
+        value: u16,
+        skip_to: u8,
+    },
+    /// Added by the game for stations the vehicle stops at without an order
+    Implicit {
+        station: u16,
+    },
+}
+
+impl Order {
+    /// Decodes the meaning of an order's fields
+    pub fn decode(raw: &RawOrder) -> Result<Order> {
+        let non_stop = gb(raw.order_type, 6, 2) as u8;
+        let kind = match gb(raw.order_type, 0, 4) {
+            0 => OrderKind::Nothing,
+            1 => OrderKind::GotoStation {
+                station: raw.dest,
+                non_stop,
+                stop_location: gb(raw.order_type, 4, 2) as u8,
+                load: LoadType::from_raw(gb(raw.flags, 4, 3))?,
+                unload: UnloadType::from_raw(gb(raw.flags, 0, 3))?,
+            },
+            2 => OrderKind::GotoDepot {
+                depot: raw.dest,
+                non_stop,
+                service: has_bit(raw.flags, 0),
+                halt: has_bit(raw.flags, 4),
+                nearest: has_bit(raw.flags, 5),
+            },
+            3 => OrderKind::Loading { station: raw.dest },
+            4 => OrderKind::LeaveStation,
+            5 => OrderKind::Dummy,
+            6 => OrderKind::GotoWaypoint {
+                waypoint: raw.dest,
+                non_stop,
+            },
+            7 => OrderKind::Conditional {
+                variable: gb(raw.dest, 11, 5) as u8,
+                comparator: gb(raw.order_type, 5, 3) as u8,
+                value: gb(raw.dest, 0, 11) as u16,
+                skip_to: raw.flags,
+            },
+            8 => OrderKind::Implicit { station: raw.dest },
+            other => return Err(invalid("order type", other)),
+        };
+        let conditional = matches!(kind, OrderKind::Conditional { .. });
+
+        Ok(Order {
+            kind,
+            next: raw.next,
+            refit_cargo: raw.refit_cargo,
+            wait_time: raw.wait_time,
+            travel_time: raw.travel_time,
+            wait_timetabled: match conditional {
+                true => raw.wait_time > 0,
+                false => has_bit(raw.flags, 3),
+            },
+            travel_timetabled: match conditional {
+                true => raw.travel_time > 0,
+                false => has_bit(raw.flags, 7),
+            },
+            max_speed: raw.max_speed,
+        })
+    }
+
+    /// Encodes the order into a new set of fields
+    pub fn to_raw(&self) -> RawOrder {
+        let mut raw = RawOrder::default();
+        self.encode_into(&mut raw);
+        raw
+    }
+
+    /// Writes the order into existing fields, keeping any bits its type doesn't use
+    pub fn encode_into(&self, raw: &mut RawOrder) {
+        let (order_type, non_stop) = match &self.kind {
+            OrderKind::Nothing => (0, None),
+            OrderKind::GotoStation { non_stop, .. } => (1, Some(*non_stop)),
+            OrderKind::GotoDepot { non_stop, .. } => (2, Some(*non_stop)),
+            OrderKind::Loading { .. } => (3, None),
+            OrderKind::LeaveStation => (4, None),
+            OrderKind::Dummy => (5, None),
+            OrderKind::GotoWaypoint { non_stop, .. } => (6, Some(*non_stop)),
+            OrderKind::Conditional { .. } => (7, None),
+            OrderKind::Implicit { .. } => (8, None),
+        };
+        sb8(&mut raw.order_type, 0, 4, order_type as u8);
+        if let Some(non_stop) = non_stop {
+            sb8(&mut raw.order_type, 6, 2, non_stop);
+        }
+
+        match &self.kind {
+            OrderKind::GotoStation {
+                station,
+                stop_location,
+                load,
+                unload,
+                ..
+            } => {
+                raw.dest = *station;
+                sb8(&mut raw.order_type, 4, 2, *stop_location);
+                sb8(&mut raw.flags, 4, 3, *load as u8);
+                sb8(&mut raw.flags, 0, 3, *unload as u8);
+            }
+            OrderKind::GotoDepot {
+                depot,
+                service,
+                halt,
+                nearest,
+                ..
+            } => {
+                raw.dest = *depot;
+                sb8(&mut raw.flags, 0, 1, *service);
+                sb8(&mut raw.flags, 4, 1, *halt);
+                sb8(&mut raw.flags, 5, 1, *nearest);
+            }
+            OrderKind::Loading { station } | OrderKind::Implicit { station } => {
+                raw.dest = *station;
+            }
+            OrderKind::GotoWaypoint { waypoint, .. } => raw.dest = *waypoint,
+            OrderKind::Conditional {
+                variable,
+                comparator,
+                value,
+                skip_to,
+            } => {
+                sb16(&mut raw.dest, 11, 5, *variable);
+                sb16(&mut raw.dest, 0, 11, *value);
+                sb8(&mut raw.order_type, 5, 3, *comparator);
+                raw.flags = *skip_to;
+            }
+            OrderKind::Nothing | OrderKind::LeaveStation | OrderKind::Dummy => {}
+        }
+        // The flags of conditional orders hold the order to skip to instead
+        if !matches!(self.kind, OrderKind::Conditional { .. }) {
+            sb8(&mut raw.flags, 3, 1, self.wait_timetabled);
+            sb8(&mut raw.flags, 7, 1, self.travel_timetabled);
+        }
+
+        raw.next = self.next;
+        raw.refit_cargo = self.refit_cargo;
+        raw.wait_time = self.wait_time;
+        raw.travel_time = self.travel_time;
+        raw.max_speed = self.max_speed;
+    }
+
+    pub fn from_save(save: &Save) -> Result<BTreeMap<u32, Order>> {
+        let chunk = save
+            .get(b"ORDR")
+            .ok_or(Error::MissingChunk { tag: *b"ORDR" })?;
+        Order::from_chunk(chunk, save.base_version())
+    }
+
+    pub fn from_lazy_save(save: &LazySave) -> Result<BTreeMap<u32, Order>> {
+        let chunk = save
+            .get(b"ORDR")
+            .ok_or(Error::MissingChunk { tag: *b"ORDR" })?
+            .decode()?;
+        Order::from_chunk(&chunk.value, save.base_version())
+    }
+
+    /// Parses every order in the contents of the `ORDR` chunk, by their index in the pool.
+    /// `version` is the savegame version, which decides the fields of the array layout.
+    pub fn from_chunk(chunk: &ChunkValue, version: u16) -> Result<BTreeMap<u32, Order>> {
+        read_pool::<RawOrder>(chunk, *b"ORDR", version)?
+            .into_iter()
+            .map(|(index, raw)| Ok((index, Order::decode(&raw)?)))
+            .collect::<Result<_>>()
+            .map_err(|e| e.in_chunk(*b"ORDR", 0))
+    }
+
+    pub fn write_to_save(orders: &BTreeMap<u32, Order>, save: &mut Save) -> Result<()> {
+        let mut chunk = save
+            .get(b"ORDR")
+            .ok_or(Error::MissingChunk { tag: *b"ORDR" })?
+            .clone();
+        Order::write_to_chunk(orders, &mut chunk, save.base_version())?;
+        save.set(Chunk {
+            tag: *b"ORDR",
+            value: chunk,
+        });
+        Ok(())
+    }
+
+    pub fn write_to_lazy_save(orders: &BTreeMap<u32, Order>, save: &mut LazySave) -> Result<()> {
+        let mut chunk = save
+            .get(b"ORDR")
+            .ok_or(Error::MissingChunk { tag: *b"ORDR" })?
+            .decode()?;
+        Order::write_to_chunk(orders, &mut chunk.value, save.base_version())?;
+        save.set(&chunk)
+    }
+
+    /// Writes the orders back into the contents of the `ORDR` chunk in the layout it already
+    /// has. Each order replaces the element with its index, orders that aren't in `orders` are
+    /// kept. Orders can't be added this way since the save has to have an element for them.
+    pub fn write_to_chunk(
+        orders: &BTreeMap<u32, Order>,
+        chunk: &mut ChunkValue,
+        version: u16,
+    ) -> Result<()> {
+        let existing = read_pool::<RawOrder>(chunk, *b"ORDR", version)?;
+        if let Some(index) = orders.keys().find(|index| !existing.contains_key(index)) {
+            return Err(Error::Malformed {
+                message: format!("there is no order {index} to write to"),
+            }
+            .in_chunk(*b"ORDR", 0));
+        }
+
+        write_pool::<RawOrder>(chunk, *b"ORDR", version, |index, raw| {
+            orders.get(&index).map(|order| order.encode_into(raw))
+        })
+    }
+}
+
+/// The start of a list of orders from the `ORDL` chunk, which all vehicles sharing the orders
+/// point to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderList {
+    /// First order in the `ORDR` chunk, as a reference
+    pub first: u32,
+}
+
+impl OrderList {
+    pub fn from_save(save: &Save) -> Result<BTreeMap<u32, OrderList>> {
+        let chunk = save
+            .get(b"ORDL")
+            .ok_or(Error::MissingChunk { tag: *b"ORDL" })?;
+        OrderList::from_chunk(chunk, save.base_version())
+    }
+
+    pub fn from_lazy_save(save: &LazySave) -> Result<BTreeMap<u32, OrderList>> {
+        let chunk = save
+            .get(b"ORDL")
+            .ok_or(Error::MissingChunk { tag: *b"ORDL" })?
+            .decode()?;
+        OrderList::from_chunk(&chunk.value, save.base_version())
+    }
+
+    /// Parses every order list in the contents of the `ORDL` chunk, by their index in the pool
+    pub fn from_chunk(chunk: &ChunkValue, version: u16) -> Result<BTreeMap<u32, OrderList>> {
+        read_pool(chunk, *b"ORDL", version)
+    }
+
+    /// Indexes of the orders in the list, in order
+    pub fn orders(&self, orders: &BTreeMap<u32, Order>) -> Vec<u32> {
+        let mut list = Vec::new();
+        let mut next = from_ref(self.first);
+        // A broken chain could loop, so stop once every order was visited
+        while let Some(index) = next.filter(|_| list.len() < orders.len()) {
+            let Some(order) = orders.get(&index) else {
+                break;
+            };
+            list.push(index);
+            next = from_ref(order.next);
+        }
+        list
+    }
+
+    /// Indexes of the vehicles using the order list with index `list`, which are the front
+    /// parts of vehicles sharing their orders
+    pub fn vehicles(list: u32, vehicles: &BTreeMap<u32, Vehicle>) -> Vec<u32> {
+        vehicles
+            .iter()
+            .filter(|(_, vehicle)| {
+                vehicle
+                    .common()
+                    .is_some_and(|common| from_ref(common.orders) == Some(list))
+            })
+            .map(|(&index, _)| index)
+            .collect()
+    }
+}
+
+/// Orders kept from a vehicle that was sold in a depot, from the `BKOR` chunk. Building a
+/// vehicle in the same depot gives it these orders back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderBackup {
+    /// Client that sold the vehicle
+    pub user: u32,
+    /// Depot the vehicle was sold in
+    pub tile: u32,
+    pub group: u16,
+    pub service_interval: u16,
+    pub name: String,
+    /// Vehicle the orders were shared with, as a reference. Saves before version 192 didn't
+    /// keep it.
+    #[serde(default)]
+    pub clone: u32,
+    pub cur_real_order_index: u8,
+    pub cur_implicit_order_index: u8,
+    pub current_order_time: u32,
+    pub lateness_counter: i32,
+    pub timetable_start: i32,
+    pub vehicle_flags: u16,
+    /// First of the kept orders in the `ORDR` chunk, as a reference
+    pub orders: u32,
+}
+
+impl OrderBackup {
+    pub fn from_save(save: &Save) -> Result<BTreeMap<u32, OrderBackup>> {
+        let chunk = save
+            .get(b"BKOR")
+            .ok_or(Error::MissingChunk { tag: *b"BKOR" })?;
+        OrderBackup::from_chunk(chunk, save.base_version())
+    }
+
+    pub fn from_lazy_save(save: &LazySave) -> Result<BTreeMap<u32, OrderBackup>> {
+        let chunk = save
+            .get(b"BKOR")
+            .ok_or(Error::MissingChunk { tag: *b"BKOR" })?
+            .decode()?;
+        OrderBackup::from_chunk(&chunk.value, save.base_version())
+    }
+
+    /// Parses every backup in the contents of the `BKOR` chunk, by their index in the pool
+    pub fn from_chunk(chunk: &ChunkValue, version: u16) -> Result<BTreeMap<u32, OrderBackup>> {
+        read_pool(chunk, *b"BKOR", version)
+    }
+
+    /// Indexes of the kept orders, in order
+    pub fn order_indexes(&self, orders: &BTreeMap<u32, Order>) -> Vec<u32> {
+        OrderList { first: self.orders }.orders(orders)
+    }
+}
+
+/// Reads every element of an array or table pool chunk, skipping the gaps. Array elements are
+/// read with the chunk's schema, which checks they have no fields from a patch pack.
+fn read_pool<T: DeserializeOwned>(
+    chunk: &ChunkValue,
+    tag: [u8; 4],
+    version: u16,
+) -> Result<BTreeMap<u32, T>> {
+    let elements = match chunk {
+        ChunkValue::ChArray { elements } => ChunkSchema::find(tag, version).and_then(|schema| {
+            elements
+                .iter()
+                .enumerate()
+                .filter(|(_, element)| !element.data.is_empty())
+                .map(|(index, element)| {
+                    let row = schema.read_all(&element.data, version, &SLXI::default())?;
+                    Ok((index as u32, from_table(&row)?))
+                })
+                .collect()
+        }),
+        ChunkValue::ChTable { elements, .. } => elements
+            .iter()
+            .enumerate()
+            .filter(|(_, element)| !element.data.is_empty())
+            .map(|(index, element)| Ok((index as u32, from_table(&element.data)?)))
+            .collect(),
+        _ => Err(Error::BadChunkType {
+            chunk_type: chunk.chunk_type(),
+        }),
+    };
+
+    elements.map_err(|e| e.in_chunk(tag, 0))
+}
+
+/// Updates the existing elements of an array or table pool chunk in place. `update` returns
+/// None for elements it doesn't change.
+fn write_pool<T: DeserializeOwned + Serialize>(
+    chunk: &mut ChunkValue,
+    tag: [u8; 4],
+    version: u16,
+    mut update: impl FnMut(u32, &mut T) -> Option<()>,
+) -> Result<()> {
+    let result = match chunk {
+        ChunkValue::ChArray { elements } => ChunkSchema::find(tag, version).and_then(|schema| {
+            let slxi = SLXI::default();
+            elements
+                .iter_mut()
+                .enumerate()
+                .filter(|(_, element)| !element.data.is_empty())
+                .try_for_each(|(index, element)| {
+                    let mut row = schema.read_all(&element.data, version, &slxi)?;
+                    let mut value: T = from_table(&row)?;
+                    if update(index as u32, &mut value).is_some() {
+                        schema.update(&mut row, &value)?;
+                        *element = ChArrayElement {
+                            data: schema.write(&row, version, &slxi)?,
+                        };
+                    }
+                    Ok(())
+                })
+        }),
+        ChunkValue::ChTable { header, elements } => elements
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, element)| !element.data.is_empty())
+            .try_for_each(|(index, element)| {
+                let mut value: T = from_table(&element.data)?;
+                if update(index as u32, &mut value).is_some() {
+                    update_table(&mut element.data, header, &value)?;
+                }
+                Ok(())
+            }),
+        _ => Err(Error::BadChunkType {
+            chunk_type: chunk.chunk_type(),
+        }),
+    };
+
+    result.map_err(|e| e.in_chunk(tag, 0))
+}
+
+/// Pool references are stored as the index plus one, with 0 for none
+fn from_ref(value: u32) -> Option<u32> {
+    value.checked_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use binrw::io::Cursor;
+    use std::fs::File;
+
+    use crate::error::Result;
+    use crate::jgr::SLXI;
+    use crate::order::{
+        LoadType, Order, OrderBackup, OrderKind, OrderList, RawOrder, UnloadType, NO_MAX_SPEED,
+    };
+    use crate::save::{ChArrayElement, ChunkValue, CompressionType, Save, WriteOptions};
+    use crate::schema::ChunkSchema;
+    use crate::table::{from_table, to_row, to_table};
+    use crate::vehicle::{Vehicle, VehicleType};
+
+    #[test]
+    fn read_orders() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        assert!(Order::from_save(&save)?.is_empty());
+        assert!(OrderList::from_save(&save)?.is_empty());
+        assert!(OrderBackup::from_save(&save)?.is_empty());
+
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let orders = Order::from_save(&save)?;
+        let lists = OrderList::from_save(&save)?;
+        let vehicles = Vehicle::from_save(&save)?;
+        assert_eq!(orders.len(), 10);
+        assert_eq!(lists.len(), 5);
+        assert!(OrderBackup::from_save(&save)?.is_empty());
+
+        // The train goes between two stations with full load any at the first
+        assert_eq!(lists[&0].orders(&orders), [4, 5]);
+        let users = OrderList::vehicles(0, &vehicles);
+        assert_eq!(users, [1]);
+        assert_eq!(vehicles[&1].vehicle_type(), VehicleType::Train);
+        assert_eq!(
+            orders[&4].kind,
+            OrderKind::GotoStation {
+                station: 2,
+                non_stop: 1,
+                stop_location: 0,
+                load: LoadType::FullLoadAny,
+                unload: UnloadType::IfPossible,
+            }
+        );
+
+        // Every list is used by a single vehicle and visits two stations
+        for (&index, list) in &lists {
+            assert_eq!(list.orders(&orders).len(), 2);
+            assert_eq!(OrderList::vehicles(index, &vehicles).len(), 1);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_orders() -> Result<()> {
+        let mut save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let options = WriteOptions::new(CompressionType::OTTN);
+        let mut expected = vec![];
+        save.write_with_options(&mut Cursor::new(&mut expected), &options)?;
+
+        // Writing the orders back unchanged keeps the save the same
+        let mut orders = Order::from_save(&save)?;
+        Order::write_to_save(&orders, &mut save)?;
+        let mut d = vec![];
+        save.write_with_options(&mut Cursor::new(&mut d), &options)?;
+        assert_eq!(d, expected);
+
+        let order = orders.get_mut(&4).unwrap();
+        order.kind = OrderKind::GotoDepot {
+            depot: 0,
+            non_stop: 1,
+            service: true,
+            halt: false,
+            nearest: true,
+        };
+        order.wait_timetabled = true;
+        order.max_speed = 80;
+        let order = orders.get_mut(&5).unwrap();
+        order.kind = OrderKind::Conditional {
+            variable: 3,
+            comparator: 2,
+            value: 1000,
+            skip_to: 0,
+        };
+        order.wait_time = 0;
+        order.wait_timetabled = false;
+        order.travel_time = 50;
+        order.travel_timetabled = true;
+        Order::write_to_save(&orders, &mut save)?;
+        assert_eq!(Order::from_save(&save)?, orders);
+
+        // Orders can only replace existing elements
+        let mut extra = orders.clone();
+        extra.insert(99, orders[&4].clone());
+        assert!(Order::write_to_save(&extra, &mut save).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn old_order_layouts() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let orders = Order::from_save(&save)?;
+        let Some(ChunkValue::ChArray { elements }) = save.get(b"ORDR") else {
+            panic!("ORDR isn't an array");
+        };
+        let slxi = SLXI::default();
+        let schema = ChunkSchema::find(*b"ORDR", save.version)?;
+        let in_version = |version: u16| -> Result<ChunkValue> {
+            let elements = elements
+                .iter()
+                .map(|element| {
+                    let row = schema.read_all(&element.data, save.version, &slxi)?;
+                    let data = schema.write(&row, version, &slxi)?;
+                    Ok(ChArrayElement { data })
+                })
+                .collect::<Result<_>>()?;
+            Ok(ChunkValue::ChArray { elements })
+        };
+
+        // Before version 182 orders still had the byte of the refit subtype
+        let mut chunk = in_version(181)?;
+        let ChunkValue::ChArray { elements: old } = &chunk else {
+            unreachable!()
+        };
+        assert!(old.iter().all(|element| element.data.len() == 16));
+        assert_eq!(Order::from_chunk(&chunk, 181)?, orders);
+        Order::write_to_chunk(&orders, &mut chunk, 181)?;
+        assert_eq!(format!("{chunk:?}"), format!("{:?}", in_version(181)?));
+
+        // Before version 67 there were no timetables, and before 172 no speed limits
+        for order in Order::from_chunk(&in_version(66)?, 66)?.values() {
+            assert_eq!((order.wait_time, order.travel_time), (0, 0));
+            assert_eq!(order.max_speed, NO_MAX_SPEED);
+        }
+        assert!(Order::from_chunk(&in_version(4)?, 4).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn old_backup_layouts() -> Result<()> {
+        let backup = OrderBackup {
+            user: 1,
+            tile: 0x1234,
+            group: 0xFFFF,
+            service_interval: 150,
+            name: "Mail".to_string(),
+            clone: 5,
+            cur_real_order_index: 1,
+            cur_implicit_order_index: 2,
+            current_order_time: 300,
+            lateness_counter: -20,
+            timetable_start: 0,
+            vehicle_flags: 0x100,
+            orders: 7,
+        };
+        let slxi = SLXI::default();
+        let schema = ChunkSchema::find(*b"BKOR", 200)?;
+        let row = to_row(&backup)?;
+        let read = |version: u16| -> Result<OrderBackup> {
+            let data = schema.write(&row, version, &slxi)?;
+            let chunk = ChunkValue::ChArray {
+                elements: vec![ChArrayElement { data }],
+            };
+            Ok(OrderBackup::from_chunk(&chunk, version)?
+                .remove(&0)
+                .unwrap())
+        };
+
+        assert_eq!(read(200)?, backup);
+        // The service interval was 32 bit and the clone wasn't kept before version 192
+        assert_ne!(
+            schema.write(&row, 191, &slxi)?,
+            schema.write(&row, 192, &slxi)?
+        );
+        assert_eq!(
+            read(191)?,
+            OrderBackup {
+                clone: 0,
+                ..backup.clone()
+            }
+        );
+        assert!(schema.write(&row, 179, &slxi).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn order_table_round_trip() -> Result<()> {
+        // The table save has no orders, so move the orders of the array save into its layout
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let Some(ChunkValue::ChTable { header, .. }) = save.get(b"ORDR") else {
+            panic!("ORDR isn't a table");
+        };
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        for order in Order::from_save(&save)?.into_values() {
+            let data = to_table(&order.to_raw(), header)?;
+            assert_eq!(Order::decode(&from_table::<RawOrder>(&data)?)?, order);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_orders() -> Result<()> {
+        let kinds = [
+            OrderKind::Nothing,
+            OrderKind::GotoStation {
+                station: 12,
+                non_stop: 3,
+                stop_location: 1,
+                load: LoadType::NoLoad,
+                unload: UnloadType::Transfer,
+            },
+            OrderKind::GotoDepot {
+                depot: 7,
+                non_stop: 0,
+                service: false,
+                halt: true,
+                nearest: false,
+            },
+            OrderKind::GotoWaypoint {
+                waypoint: 3,
+                non_stop: 2,
+            },
+            OrderKind::Conditional {
+                variable: 5,
+                comparator: 6,
+                value: 0x7FF,
+                skip_to: 9,
+            },
+            OrderKind::Implicit { station: 4 },
+        ];
+        for kind in kinds {
+            let order = Order {
+                kind,
+                next: 3,
+                refit_cargo: 0xFE,
+                wait_time: 100,
+                travel_time: 0,
+                wait_timetabled: false,
+                travel_timetabled: true,
+                max_speed: 0xFFFF,
+            };
+            let decoded = Order::decode(&order.to_raw())?;
+            match order.kind {
+                // Conditional orders are timetabled when they have a time
+                OrderKind::Conditional { .. } => assert_eq!(
+                    decoded,
+                    Order {
+                        wait_timetabled: true,
+                        travel_timetabled: false,
+                        ..order
+                    }
+                ),
+                _ => assert_eq!(decoded, order),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,27 +1,38 @@
 use crate::chtable::{ChSparseTableElement, ChTableElement, StructHeader, TableHeaderProperty};
+use crate::error::{Error, Result};
 use crate::gamma::{gamma_length, parse_gamma, write_gamma, Gamma};
 use crate::helpers::{until_magic, until_magic_with};
+use crate::lzo::{LzoReader, LzoWriter};
+use crate::stream::{ChunkReader, ChunkWriter};
 #[cfg(feature = "lzma-rs")]
 use binrw::io::BufReader;
-#[cfg(feature = "xz2")]
 use binrw::io::{Read, Write};
 use binrw::{
     binrw,
     helpers::until_eof,
-    io::{Cursor, Error, ErrorKind, Result},
-    BinRead, BinResult, BinWrite,
+    io::{Cursor, Seek},
+    BinRead, BinReaderExt, BinResult, BinWrite,
 };
+#[cfg(feature = "flate2")]
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
 #[cfg(feature = "lzma-rs")]
 use lzma_rs::{xz_compress, xz_decompress};
 use modular_bitfield::{bitfield, specifiers::B4};
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "xz2")]
-use xz2::{read::XzDecoder, write::XzEncoder};
+use xz2::{
+    read::XzDecoder,
+    stream::{Check, MtStreamBuilder},
+    write::XzEncoder,
+};
+
+/// JGR's Patch Pack sets this bit in the savegame version of its saves
+pub const SAVEGAME_VERSION_EXT: u16 = 0x8000;
 
 #[binrw]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 pub enum CompressionType {
-    /// Compressed with LZO (deprecated, only really old savegames would use this).
+    /// Compressed with LZO in blocks of 8192 bytes (deprecated, only really old savegames would use this).
     #[brw(magic = b"OTTD")]
     OTTD,
     /// No compression.
@@ -38,180 +49,355 @@ pub enum CompressionType {
     OTTS,
 }
 
-/// Performs save file compression on a byte slice of the compressed data
-fn compress_save(compression_type: &CompressionType, blob: &Vec<u8>) -> Result<Vec<u8>> {
-    match compression_type {
-        CompressionType::OTTD => Err(Error::new(ErrorKind::Other, "Old save file not supported")),
-        CompressionType::OTTN => Ok(blob.clone()),
-        CompressionType::OTTZ => Err(Error::new(ErrorKind::Other, "zlib not supported (yet)")),
-        CompressionType::OTTX => {
-            cfg_if::cfg_if! {
-                if #[cfg(feature = "xz2")] {
-                    let mut buffer: Vec<u8> = Vec::new();
-                    {
-                        let mut encoder = XzEncoder::new(&mut buffer, 2);
-                        encoder.write(&blob).unwrap();
+impl CompressionType {
+    pub fn from_magic(magic: [u8; 4]) -> Result<CompressionType> {
+        match &magic {
+            b"OTTD" => Ok(CompressionType::OTTD),
+            b"OTTN" => Ok(CompressionType::OTTN),
+            b"OTTZ" => Ok(CompressionType::OTTZ),
+            b"OTTX" => Ok(CompressionType::OTTX),
+            b"OTTS" => Ok(CompressionType::OTTS),
+            _ => Err(Error::UnsupportedCompression { magic }),
+        }
+    }
+
+    pub fn magic(&self) -> [u8; 4] {
+        match self {
+            CompressionType::OTTD => *b"OTTD",
+            CompressionType::OTTN => *b"OTTN",
+            CompressionType::OTTZ => *b"OTTZ",
+            CompressionType::OTTX => *b"OTTX",
+            CompressionType::OTTS => *b"OTTS",
+        }
+    }
+}
+
+/// Options for how a save file is compressed when it's written
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Compression format to write with, uses the one the save was read with if None
+    pub compression_type: Option<CompressionType>,
+    /// Compression level, uses the same default as OpenTTD if None.
+    /// zlib and xz2 accept 0-9, zstd accepts the range given by `zstd::compression_level_range`.
+    /// Ignored for LZO, no compression and the lzma-rs backend which don't have levels.
+    pub level: Option<i32>,
+    /// Number of worker threads, 0 or 1 compresses on the calling thread.
+    /// Only used by the xz2 and zstd backends.
+    pub threads: u32,
+}
+
+impl WriteOptions {
+    pub fn new(compression_type: CompressionType) -> WriteOptions {
+        WriteOptions {
+            compression_type: Some(compression_type),
+            ..Default::default()
+        }
+    }
+
+    /// The compression type to write with, given the one the save was read with
+    pub fn compression_type_or(&self, compression_type: &CompressionType) -> CompressionType {
+        self.compression_type.unwrap_or(*compression_type)
+    }
+}
+
+/// Returns the compression level to use, checking it is within range
+#[cfg(any(feature = "flate2", feature = "xz2", feature = "zstd"))]
+fn compression_level(level: Option<i32>, default: i32, min: i32, max: i32) -> Result<i32> {
+    match level {
+        None => Ok(default),
+        Some(level) if (min..=max).contains(&level) => Ok(level),
+        Some(level) => Err(Error::InvalidCompressionLevel { level, min, max }),
+    }
+}
+
+/// zlib level used by OpenTTD's default "zlib" savegame format
+#[cfg(feature = "flate2")]
+const ZLIB_LEVEL: i32 = 6;
+
+/// xz preset used by OpenTTD's default "lzma" savegame format
+#[cfg(feature = "xz2")]
+const LZMA_LEVEL: i32 = 2;
+
+/// Compresses data as it's written with any of the save file compression formats.
+/// [`Encoder::finish`] must be called to write the end of the compressed stream.
+pub(crate) enum Encoder<W: Write> {
+    Lzo(LzoWriter<W>),
+    None(W),
+    #[cfg(feature = "flate2")]
+    Zlib(ZlibEncoder<W>),
+    #[cfg(feature = "xz2")]
+    Xz(XzEncoder<W>),
+    /// lzma-rs can only compress a whole buffer at once
+    #[cfg(all(feature = "lzma-rs", not(feature = "xz2")))]
+    LzmaRs {
+        buffer: Vec<u8>,
+        writer: W,
+    },
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    pub(crate) fn new(
+        compression_type: CompressionType,
+        options: &WriteOptions,
+        writer: W,
+    ) -> Result<Encoder<W>> {
+        #[cfg(not(any(feature = "xz2", feature = "zstd")))]
+        let _ = options.threads;
+
+        match compression_type {
+            CompressionType::OTTD => Ok(Encoder::Lzo(LzoWriter::new(writer))),
+            CompressionType::OTTN => Ok(Encoder::None(writer)),
+            CompressionType::OTTZ => {
+                cfg_if::cfg_if! {
+                    if #[cfg(feature = "flate2")] {
+                        let level = compression_level(options.level, ZLIB_LEVEL, 0, 9)?;
+                        Ok(Encoder::Zlib(ZlibEncoder::new(writer, Compression::new(level as u32))))
+                    } else {
+                        Err(Error::MissingFeature { feature: "zlib" })
+                    }
+                }
+            }
+            CompressionType::OTTX => {
+                cfg_if::cfg_if! {
+                    if #[cfg(feature = "xz2")] {
+                        let level = compression_level(options.level, LZMA_LEVEL, 0, 9)? as u32;
+                        if options.threads > 1 {
+                            let stream = MtStreamBuilder::new()
+                                .threads(options.threads)
+                                .preset(level)
+                                .check(Check::Crc64)
+                                .encoder()
+                                .map_err(binrw::io::Error::from)?;
+                            Ok(Encoder::Xz(XzEncoder::new_stream(writer, stream)))
+                        } else {
+                            Ok(Encoder::Xz(XzEncoder::new(writer, level)))
+                        }
+                    } else if #[cfg(feature = "lzma-rs")] {
+                        Ok(Encoder::LzmaRs { buffer: Vec::new(), writer })
+                    } else {
+                        Err(Error::MissingFeature { feature: "LZMA" })
                     }
-                    Ok(buffer)
-                } else if #[cfg(feature = "lzma-rs")] {
-                    let mut buffer: Vec<u8> = Vec::new();
-                    {
-                        xz_compress(&mut Cursor::new(blob), &mut buffer)?;
+                }
+            }
+            CompressionType::OTTS => {
+                cfg_if::cfg_if! {
+                    if #[cfg(feature = "zstd")] {
+                        let range = zstd::compression_level_range();
+                        let level = compression_level(options.level, 0, *range.start(), *range.end())?;
+                        let mut encoder = zstd::stream::write::Encoder::new(writer, level)?;
+                        if options.threads > 1 {
+                            encoder.multithread(options.threads)?;
+                        }
+                        Ok(Encoder::Zstd(encoder))
+                    } else {
+                        Err(Error::MissingFeature { feature: "zstd" })
                     }
-                    Ok(buffer)
-                } else {
-                    Err(Error::new(ErrorKind::Other, "Not compiled with LZMA support"))
                 }
             }
         }
-        CompressionType::OTTS => {
-            #[cfg(not(feature = "zstd"))]
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Not compiled with zstd support",
-            ));
+    }
+
+    /// Writes the end of the compressed stream and returns the inner writer
+    pub(crate) fn finish(self) -> Result<W> {
+        match self {
+            Encoder::Lzo(encoder) => Ok(encoder.finish()?),
+            Encoder::None(writer) => Ok(writer),
+            #[cfg(feature = "flate2")]
+            Encoder::Zlib(encoder) => Ok(encoder.finish()?),
+            #[cfg(feature = "xz2")]
+            Encoder::Xz(encoder) => Ok(encoder.finish()?),
+            #[cfg(all(feature = "lzma-rs", not(feature = "xz2")))]
+            Encoder::LzmaRs { buffer, mut writer } => {
+                xz_compress(&mut Cursor::new(buffer), &mut writer)?;
+                Ok(writer)
+            }
             #[cfg(feature = "zstd")]
-            zstd::stream::encode_all(&mut Cursor::new(blob), 0)
+            Encoder::Zstd(encoder) => Ok(encoder.finish()?),
         }
     }
 }
 
-/// Performs save file decompression on a byte slice of the decompressed data
-fn decompress_save(compression_type: &CompressionType, blob: Vec<u8>) -> Result<Vec<u8>> {
-    match compression_type {
-        CompressionType::OTTD => Err(Error::new(ErrorKind::Other, "Old save file not supported")),
-        CompressionType::OTTN => Ok(blob),
-        CompressionType::OTTZ => Err(Error::new(ErrorKind::Other, "zlib not supported (yet)")),
-        CompressionType::OTTX => {
-            cfg_if::cfg_if! {
-                if #[cfg(feature = "xz2")] {
-                    let mut buffer = Vec::new();
-                    XzDecoder::new(Cursor::new(&blob)).read_to_end(&mut buffer)?;
-                    Ok(buffer)
-                } else if #[cfg(feature = "lzma-rs")] {
-                    let mut buffer = Vec::new();
-                    xz_decompress(&mut Cursor::new(blob), &mut buffer).map_err(lzma_error_to_io)?;
-                    Ok(buffer)
-                } else {
-                    Err(Error::new(ErrorKind::Other, "Not compiled with LZMA support"))
-                }
-            }
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> binrw::io::Result<usize> {
+        match self {
+            Encoder::Lzo(encoder) => encoder.write(buf),
+            Encoder::None(writer) => writer.write(buf),
+            #[cfg(feature = "flate2")]
+            Encoder::Zlib(encoder) => encoder.write(buf),
+            #[cfg(feature = "xz2")]
+            Encoder::Xz(encoder) => encoder.write(buf),
+            #[cfg(all(feature = "lzma-rs", not(feature = "xz2")))]
+            Encoder::LzmaRs { buffer, .. } => buffer.write(buf),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.write(buf),
         }
-        CompressionType::OTTS => {
-            #[cfg(not(feature = "zstd"))]
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Not compiled with zstd support",
-            ));
+    }
+
+    fn flush(&mut self) -> binrw::io::Result<()> {
+        match self {
+            Encoder::Lzo(encoder) => encoder.flush(),
+            Encoder::None(writer) => writer.flush(),
+            #[cfg(feature = "flate2")]
+            Encoder::Zlib(encoder) => encoder.flush(),
+            #[cfg(feature = "xz2")]
+            Encoder::Xz(encoder) => encoder.flush(),
+            #[cfg(all(feature = "lzma-rs", not(feature = "xz2")))]
+            Encoder::LzmaRs { .. } => Ok(()),
             #[cfg(feature = "zstd")]
-            zstd::stream::decode_all(&mut Cursor::new(blob))
+            Encoder::Zstd(encoder) => encoder.flush(),
         }
     }
 }
 
-/// A parser that performs save file decompression on data taken from a reader
-#[binrw::parser(reader, endian)]
-fn chunk_reader(compression_type: &CompressionType) -> BinResult<Vec<Chunk>> {
-    match compression_type {
-        CompressionType::OTTD => Err(binrw::Error::Io(Error::new(ErrorKind::Other, "Old save file not supported"))),
-        CompressionType::OTTN => Chunks::read_options(reader, endian, ()),
-        CompressionType::OTTZ => Err(binrw::Error::Io(Error::new(ErrorKind::Other, "zlib not supported (yet)"))),
-        CompressionType::OTTX => {
-            cfg_if::cfg_if! {
-                if #[cfg(feature = "xz2")] {
-                    let mut buffer = Vec::new();
-                    let mut new_reader = XzDecoder::new(reader);
-                    new_reader.read_to_end(&mut buffer)?;
-                    Chunks::read_options(&mut Cursor::new(&mut buffer), endian, ())
-                } else if #[cfg(feature = "lzma-rs")] {
-                    let mut buffer = Vec::new();
-                    let mut buf_reader = BufReader::new(reader);
-                    xz_decompress(&mut buf_reader, &mut buffer).map_err(lzma_error_to_io)?;
-                    Chunks::read_options(&mut Cursor::new(&mut buffer), endian, ())
-                } else {
-                    return Err(binrw::Error::Io(Error::new(ErrorKind::Other, "Not compiled with LZMA support")));
+/// Decompresses data as it's read with any of the save file compression formats
+pub(crate) enum Decoder<R: Read> {
+    Lzo(LzoReader<R>),
+    None(R),
+    #[cfg(feature = "flate2")]
+    Zlib(ZlibDecoder<R>),
+    #[cfg(feature = "xz2")]
+    Xz(XzDecoder<R>),
+    /// lzma-rs can only decompress a whole stream at once
+    #[cfg(all(feature = "lzma-rs", not(feature = "xz2")))]
+    LzmaRs(Cursor<Vec<u8>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::read::Decoder<'static, std::io::BufReader<R>>),
+}
+
+impl<R: Read> Decoder<R> {
+    pub(crate) fn new(compression_type: CompressionType, reader: R) -> Result<Decoder<R>> {
+        match compression_type {
+            CompressionType::OTTD => Ok(Decoder::Lzo(LzoReader::new(reader))),
+            CompressionType::OTTN => Ok(Decoder::None(reader)),
+            CompressionType::OTTZ => {
+                cfg_if::cfg_if! {
+                    if #[cfg(feature = "flate2")] {
+                        Ok(Decoder::Zlib(ZlibDecoder::new(reader)))
+                    } else {
+                        Err(Error::MissingFeature { feature: "zlib" })
+                    }
+                }
+            }
+            CompressionType::OTTX => {
+                cfg_if::cfg_if! {
+                    if #[cfg(feature = "xz2")] {
+                        Ok(Decoder::Xz(XzDecoder::new(reader)))
+                    } else if #[cfg(feature = "lzma-rs")] {
+                        let mut buffer = Vec::new();
+                        xz_decompress(&mut BufReader::new(reader), &mut buffer).map_err(lzma_error_to_io)?;
+                        Ok(Decoder::LzmaRs(Cursor::new(buffer)))
+                    } else {
+                        Err(Error::MissingFeature { feature: "LZMA" })
+                    }
+                }
+            }
+            CompressionType::OTTS => {
+                cfg_if::cfg_if! {
+                    if #[cfg(feature = "zstd")] {
+                        Ok(Decoder::Zstd(zstd::stream::read::Decoder::new(reader)?))
+                    } else {
+                        Err(Error::MissingFeature { feature: "zstd" })
+                    }
                 }
             }
         }
+    }
+}
 
-        CompressionType::OTTS => {
-            #[cfg(not(feature = "zstd"))]
-            return Err(binrw::Error::Io(Error::new(ErrorKind::Other, "Not compiled with zstd support")));
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> binrw::io::Result<usize> {
+        match self {
+            Decoder::Lzo(decoder) => decoder.read(buf),
+            Decoder::None(reader) => reader.read(buf),
+            #[cfg(feature = "flate2")]
+            Decoder::Zlib(decoder) => decoder.read(buf),
+            #[cfg(feature = "xz2")]
+            Decoder::Xz(decoder) => decoder.read(buf),
+            #[cfg(all(feature = "lzma-rs", not(feature = "xz2")))]
+            Decoder::LzmaRs(decoder) => decoder.read(buf),
             #[cfg(feature = "zstd")]
-            Chunks::read_options(&mut Cursor::new(zstd::stream::decode_all(reader)?), endian, ())
+            Decoder::Zstd(decoder) => decoder.read(buf),
         }
     }
-    .map(|c| c.chunks)
+}
+
+/// Performs save file compression on a byte slice of the compressed data
+fn compress_save(
+    compression_type: CompressionType,
+    options: &WriteOptions,
+    blob: &[u8],
+) -> Result<Vec<u8>> {
+    let mut encoder = Encoder::new(compression_type, options, Vec::new())?;
+    encoder.write_all(blob)?;
+    encoder.finish()
+}
+
+/// Performs save file decompression on a byte slice of the decompressed data
+fn decompress_save(compression_type: &CompressionType, blob: Vec<u8>) -> Result<Vec<u8>> {
+    if *compression_type == CompressionType::OTTN {
+        return Ok(blob);
+    }
+
+    let mut buffer = Vec::new();
+    Decoder::new(*compression_type, Cursor::new(blob))?.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// A parser that performs save file decompression on data taken from a reader
+#[binrw::parser(reader, endian)]
+fn chunk_reader(compression_type: &CompressionType) -> BinResult<Vec<Chunk>> {
+    if *compression_type == CompressionType::OTTN {
+        return Chunks::read_options(reader, endian, ()).map(|c| c.chunks);
+    }
+
+    let pos = reader.stream_position()?;
+    let mut buffer = Vec::new();
+    Decoder::new(*compression_type, reader)
+        .map_err(|e| e.into_binrw(pos))?
+        .read_to_end(&mut buffer)?;
+    Chunks::read_options(&mut Cursor::new(&mut buffer), endian, ()).map(|c| c.chunks)
 }
 
 /// A writer that performs save file compression on parsed data.
 /// Includes the 0u32 terminator before compressing.
 #[binrw::writer(writer, endian)]
-fn chunk_writer(chunks: &Vec<Chunk>, compression_type: &CompressionType) -> BinResult<()> {
-    match compression_type {
-        CompressionType::OTTD => Err(binrw::Error::Io(Error::new(
-            ErrorKind::Other,
-            "Old save file not supported",
-        ))),
-        CompressionType::OTTN => {
-            chunks.write_options(writer, endian, ())?;
-            // Terminator
-            0u32.write_options(writer, endian, ())
-        }
-        CompressionType::OTTZ => Err(binrw::Error::Io(Error::new(
-            ErrorKind::Other,
-            "zlib not supported (yet)",
-        ))),
-        CompressionType::OTTX => {
-            let mut buffer: Vec<u8> = Vec::new();
-
-            {
-                let mut writer = Cursor::new(&mut buffer);
-                chunks.write_options(&mut writer, endian, ())?;
-                // Terminator
-                0u32.write_options(&mut writer, endian, ())?;
-            }
-
-            cfg_if::cfg_if! {
-                if #[cfg(feature = "xz2")] {
-                    let mut encoder = XzEncoder::new(writer, 2);
-                    encoder.write_all(&mut buffer)?;
-                    Ok(())
-                } else if #[cfg(feature = "lzma-rs")] {
-                    xz_compress(&mut Cursor::new(buffer), writer)?;
-                    Ok(())
-                } else {
-                    Err(binrw::Error::Io(Error::new(ErrorKind::Other, "Not compiled with LZMA support")))
-                }
-            }
-        }
-        CompressionType::OTTS => {
-            cfg_if::cfg_if! {
-                if #[cfg(feature = "zstd")] {
-                    let mut buffer: Vec<u8> = Vec::new();
-
-                    {
-                        let mut writer = Cursor::new(&mut buffer);
-                        chunks.write_options(&mut writer, endian, ())?;
-                        // Terminator
-                        0u32.write_options(&mut writer, endian, ())?;
-                    }
+fn chunk_writer(
+    chunks: &Vec<Chunk>,
+    compression_type: CompressionType,
+    options: &WriteOptions,
+) -> BinResult<()> {
+    if compression_type == CompressionType::OTTN {
+        chunks.write_options(writer, endian, ())?;
+        // Terminator
+        return 0u32.write_options(writer, endian, ());
+    }
 
-                    zstd::stream::copy_encode(&mut Cursor::new(buffer), writer, 0)?;
+    let mut buffer: Vec<u8> = Vec::new();
 
-                    Ok(())
-                } else {
-                    Err(binrw::Error::Io(Error::new(ErrorKind::Other, "Not compiled with zstd support")))
-                }
-            }
-        }
+    {
+        let mut writer = Cursor::new(&mut buffer);
+        chunks.write_options(&mut writer, endian, ())?;
+        // Terminator
+        0u32.write_options(&mut writer, endian, ())?;
     }
+
+    let pos = writer.stream_position()?;
+    let mut encoder =
+        Encoder::new(compression_type, options, writer).map_err(|e| e.into_binrw(pos))?;
+    encoder.write_all(&buffer)?;
+    encoder.finish().map_err(|e| e.into_binrw(pos))?;
+    Ok(())
 }
 
 #[binrw]
 #[brw(big)]
-#[derive(Debug, Serialize, Deserialize)]
+#[bw(import(options: WriteOptions))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Save {
+    #[br(try_map = CompressionType::from_magic)]
+    #[bw(write_with = |c,w,e,_: ()| options.compression_type_or(c).write_options(w, e, ()))]
     pub compression_type: CompressionType,
     // The next two bytes indicate which savegame version used.
     pub version: u16,
@@ -219,18 +405,50 @@ pub struct Save {
     _ignore: u16,
     // Wish I could use map_stream here from the new PR but no rust LZMA decompressers support Read + Seek :(
     #[br(parse_with = |r,e,_: ()| chunk_reader(r, e, (&compression_type,)))]
-    #[bw(write_with = |r,e,d,_: ()| chunk_writer(r, e, d, (&compression_type,)))]
+    #[bw(write_with = |r,e,d,_: ()| chunk_writer(r, e, d, (options.compression_type_or(compression_type), &options)))]
     #[serde(with = "chunk")]
     pub chunks: Vec<Chunk>,
 }
 
 impl Save {
+    /// Reads and decompresses a save file
+    pub fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Save> {
+        Ok(reader.read_be()?)
+    }
+
+    /// Compresses and writes the save file
+    pub fn to_writer<W: Write + Seek>(&self, writer: &mut W) -> Result<()> {
+        Ok(self.write(writer)?)
+    }
+
+    /// Compresses and writes the save file with the given format, level and threads
+    pub fn write_with_options<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> Result<()> {
+        Ok(self.write_args(writer, (options.clone(),))?)
+    }
+
+    /// The savegame version without JGR's flag, which is what fields are saved since or until
+    pub fn base_version(&self) -> u16 {
+        self.version & !SAVEGAME_VERSION_EXT
+    }
+
     pub fn get(&self, tag: &[u8; 4]) -> Option<&ChunkValue> {
         self.chunks
             .iter()
             .find(|x| &x.tag == tag)
             .map(|chunk| &chunk.value)
     }
+
+    /// Replaces the chunk with the same tag, or adds it to the end if there isn't one
+    pub fn set(&mut self, chunk: Chunk) {
+        match self.chunks.iter_mut().find(|x| x.tag == chunk.tag) {
+            Some(existing) => *existing = chunk,
+            None => self.chunks.push(chunk),
+        }
+    }
 }
 
 mod chunk {
@@ -290,18 +508,107 @@ mod chunk {
 
 #[binrw]
 #[brw(big)]
-#[derive(Debug, Deserialize, Serialize)]
+#[bw(import(options: WriteOptions))]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OuterSave {
+    #[br(try_map = CompressionType::from_magic)]
+    #[bw(write_with = |c,w,e,_: ()| options.compression_type_or(c).write_options(w, e, ()))]
     pub compression_type: CompressionType,
     // The next two bytes indicate which savegame version used.
     pub version: u16,
     // The next two bytes can be ignored, and were only used in really old savegames.
     _ignore: u16,
     #[br(parse_with = until_eof, try_map = |blob: Vec<u8>| decompress_save(&compression_type, blob))]
-    #[bw(try_map = |blob: &Vec<u8>| compress_save(&compression_type, blob))]
+    #[bw(try_map = |blob: &Vec<u8>| compress_save(options.compression_type_or(compression_type), &options, blob))]
     pub data: Vec<u8>,
 }
 
+impl OuterSave {
+    /// Reads and decompresses a save file without parsing the chunks
+    pub fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<OuterSave> {
+        Ok(reader.read_be()?)
+    }
+
+    /// Compresses and writes the save file
+    pub fn to_writer<W: Write + Seek>(&self, writer: &mut W) -> Result<()> {
+        Ok(self.write(writer)?)
+    }
+
+    /// Compresses and writes the save file with the given format, level and threads
+    pub fn write_with_options<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> Result<()> {
+        Ok(self.write_args(writer, (options.clone(),))?)
+    }
+}
+
+/// A save file where chunks are kept as raw bytes and only decoded when needed.
+/// Chunks that aren't replaced are written back exactly as they were read.
+#[derive(Debug)]
+pub struct LazySave {
+    pub compression_type: CompressionType,
+    pub version: u16,
+    pub chunks: Vec<RawChunk>,
+}
+
+impl LazySave {
+    /// Reads and decompresses a save file, splitting it into chunks without parsing them
+    pub fn from_reader<R: Read>(reader: &mut R) -> Result<LazySave> {
+        let mut reader = ChunkReader::new(reader)?;
+        let mut chunks = Vec::new();
+        while let Some(chunk) = reader.next_raw() {
+            chunks.push(chunk?);
+        }
+
+        Ok(LazySave {
+            compression_type: reader.compression_type,
+            version: reader.version,
+            chunks,
+        })
+    }
+
+    /// Compresses and writes the save file
+    pub fn to_writer<W: Write>(&self, writer: &mut W) -> Result<()> {
+        self.write_with_options(writer, &WriteOptions::default())
+    }
+
+    /// Compresses and writes the save file with the given format, level and threads
+    pub fn write_with_options<W: Write>(
+        &self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> Result<()> {
+        let mut writer =
+            ChunkWriter::with_options(writer, self.compression_type, self.version, options)?;
+        for chunk in &self.chunks {
+            writer.write_raw(chunk)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// The savegame version without JGR's flag, see [`Save::base_version`]
+    pub fn base_version(&self) -> u16 {
+        self.version & !SAVEGAME_VERSION_EXT
+    }
+
+    pub fn get(&self, tag: &[u8; 4]) -> Option<&RawChunk> {
+        self.chunks.iter().find(|chunk| &chunk.tag() == tag)
+    }
+
+    /// Replaces the chunk with the same tag, or adds it to the end if there isn't one
+    pub fn set(&mut self, chunk: &Chunk) -> Result<()> {
+        let raw = RawChunk::encode(chunk)?;
+        match self.chunks.iter_mut().find(|x| x.tag() == chunk.tag) {
+            Some(existing) => *existing = raw,
+            None => self.chunks.push(raw),
+        }
+        Ok(())
+    }
+}
+
 #[binrw]
 #[brw(big)]
 #[derive(Debug)]
@@ -315,23 +622,104 @@ pub struct Chunks {
 
 #[binrw]
 #[brw(big)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Chunk {
     pub tag: [u8; 4],
     #[br(temp)]
-    #[bw(calc = ChunkType::get_chunk_type(value))]
+    #[bw(try_calc = ChunkType::get_chunk_type(value))]
     chunk_type: ChunkType,
-    #[br(args { chunk_type })]
+    #[br(parse_with = read_chunk_value, args(tag, chunk_type))]
+    #[bw(write_with = write_chunk_value, args(*tag))]
     pub value: ChunkValue,
 }
 
+/// A chunk that hasn't been decoded, stored as the bytes it was read from
+/// (tag, chunk type and value) so it can be written back unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawChunk {
+    bytes: Vec<u8>,
+}
+
+impl RawChunk {
+    /// Creates a raw chunk from the bytes of a whole chunk. The bytes are only checked when decoded.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<RawChunk> {
+        if bytes.len() < 5 {
+            return Err(Error::Malformed {
+                message: format!("chunk of {} bytes is too short", bytes.len()),
+            });
+        }
+        Ok(RawChunk { bytes })
+    }
+
+    /// Encodes a chunk into its raw bytes
+    pub fn encode(chunk: &Chunk) -> Result<RawChunk> {
+        let mut bytes = Vec::new();
+        chunk.write_be(&mut Cursor::new(&mut bytes))?;
+        Ok(RawChunk { bytes })
+    }
+
+    /// Parses the chunk. Errors have offsets relative to the start of the chunk.
+    pub fn decode(&self) -> Result<Chunk> {
+        Ok(Chunk::read_be(&mut Cursor::new(&self.bytes))?)
+    }
+
+    pub fn tag(&self) -> [u8; 4] {
+        [self.bytes[0], self.bytes[1], self.bytes[2], self.bytes[3]]
+    }
+
+    /// The type stored in the chunk header (0 = RIFF, 1 = array, 2 = sparse array, 3 = table, 4 = sparse table)
+    pub fn chunk_type(&self) -> u8 {
+        self.bytes[4] & 0xf
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads a chunk's value, adding the chunk's tag to any errors
+#[binrw::parser(reader, endian)]
+fn read_chunk_value(tag: [u8; 4], chunk_type: ChunkType) -> BinResult<ChunkValue> {
+    let offset = reader.stream_position()?;
+
+    if chunk_type.chunk_type() > 4 {
+        return Err(Error::BadChunkType {
+            chunk_type: chunk_type.chunk_type(),
+        }
+        .in_chunk(tag, offset)
+        .into_binrw(offset));
+    }
+
+    ChunkValue::read_options(reader, endian, binrw::args! { chunk_type }).map_err(|e| {
+        let (pos, error) = Error::from_binrw(e);
+        let pos = pos.unwrap_or(offset);
+        error.in_chunk(tag, pos).into_binrw(pos)
+    })
+}
+
+/// Writes a chunk's value, adding the chunk's tag to any errors
+#[binrw::writer(writer, endian)]
+fn write_chunk_value(value: &ChunkValue, tag: [u8; 4]) -> BinResult<()> {
+    let offset = writer.stream_position()?;
+
+    value.write_options(writer, endian, ()).map_err(|e| {
+        let (pos, error) = Error::from_binrw(e);
+        let pos = pos.unwrap_or(offset);
+        error.in_chunk(tag, pos).into_binrw(pos)
+    })
+}
+
 #[binrw]
 #[brw(big)]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ChArrayElement {
     // Actual length = size - 1
     #[br(temp)]
-    #[bw(calc = Gamma { value: (data.len() + 1).try_into().unwrap() })]
+    #[bw(try_calc = Gamma::try_from(data.len() + 1))]
     size: Gamma,
     #[br(count = size.value.saturating_sub(1))]
     #[serde(with = "serde_bytes")]
@@ -340,11 +728,11 @@ pub struct ChArrayElement {
 
 #[binrw]
 #[brw(big)]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ChSparseArrayElement {
     // Actual length = length - 1
     #[br(temp)]
-    #[bw(calc = Gamma { value: (TryInto::<u32>::try_into(data.len() + 1).unwrap()) + gamma_length(*index) })]
+    #[bw(try_calc = Gamma::try_from(data.len() + 1 + gamma_length(*index) as usize))]
     size: Gamma,
     #[br(parse_with = parse_gamma)]
     #[bw(write_with = write_gamma)]
@@ -365,35 +753,44 @@ pub struct ChunkType {
 }
 
 impl ChunkType {
-    fn get_chunk_type(chunk: &ChunkValue) -> ChunkType {
-        let mut riff_size: u8 = 0;
-        let chunk_type = match chunk {
-            ChunkValue::ChRiff { data, .. } => {
-                riff_size = (data.len() >> 24) as u8;
-                0
+    fn get_chunk_type(chunk: &ChunkValue) -> Result<ChunkType> {
+        let riff_size = match chunk {
+            ChunkValue::ChRiff { data } if data.len() >= 1 << 28 => {
+                return Err(Error::ChunkTooLarge { size: data.len() });
             }
+            ChunkValue::ChRiff { data } => (data.len() >> 24) as u8,
+            _ => 0,
+        };
+
+        Ok(ChunkType::new()
+            .with_riff_size(riff_size)
+            .with_chunk_type(chunk.chunk_type()))
+    }
+}
+
+impl ChunkValue {
+    /// The type stored in the chunk header (0 = RIFF, 1 = array, 2 = sparse array, 3 = table, 4 = sparse table)
+    pub fn chunk_type(&self) -> u8 {
+        match self {
+            ChunkValue::ChRiff { .. } => 0,
             ChunkValue::ChArray { .. } => 1,
             ChunkValue::ChSparseArray { .. } => 2,
             ChunkValue::ChTable { .. } => 3,
             ChunkValue::ChSparseTable { .. } => 4,
-        };
-
-        ChunkType::new()
-            .with_riff_size(riff_size)
-            .with_chunk_type(chunk_type)
+        }
     }
 }
 
 #[binrw]
 #[brw(big)]
 #[br(import { chunk_type: ChunkType })]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "type")]
 pub enum ChunkValue {
     #[br(pre_assert(chunk_type.chunk_type() == 0))]
     ChRiff {
         #[br(temp, map = |x: [u8;3]| ((x[0] as u32) << 16) | ((x[1] as u32) << 8) | (x[2] as u32) | (((chunk_type.riff_size()) as u32) << 24))]
-        #[bw(calc = data.len().try_into().unwrap(), map = |x: u32| { let bytes = x.to_be_bytes(); [bytes[1], bytes[2], bytes[3]] })]
+        #[bw(calc = data.len() as u32, map = |x: u32| { let bytes = x.to_be_bytes(); [bytes[1], bytes[2], bytes[3]] })]
         size: u32,
         #[br(count = size)]
         #[serde(with = "serde_bytes")]
@@ -415,9 +812,9 @@ pub enum ChunkValue {
     ChTable {
         #[br(temp)]
         // This could be optimized by counting rather than doing another clone and into
-        #[bw(calc = Gamma { value: (Into::<StructHeader>::into(header.clone()).byte_len() + 1).try_into().unwrap() })]
+        #[bw(try_calc = Gamma::try_from(StructHeader::from(header.clone()).byte_len() + 1))]
         _header_size: Gamma,
-        #[br(map = |header: StructHeader| header.into())]
+        #[br(try_map = |header: StructHeader| header.try_into())]
         #[bw(map = |props: &Vec<TableHeaderProperty>| -> StructHeader { props.clone().into() })]
         header: Vec<TableHeaderProperty>,
         #[br(parse_with = until_magic_with(0u8, |r,e,_: ()| ChTableElement::read_options(r, e, (&header,)), |reader, endian, arg| {
@@ -430,9 +827,9 @@ pub enum ChunkValue {
     },
     #[br(pre_assert(chunk_type.chunk_type() == 4))]
     ChSparseTable {
-        #[bw(calc = Gamma { value: (Into::<StructHeader>::into(header.clone()).byte_len() + 1).try_into().unwrap() })]
+        #[bw(try_calc = Gamma::try_from(StructHeader::from(header.clone()).byte_len() + 1))]
         _header_size: Gamma,
-        #[br(map = |header: StructHeader| header.into())]
+        #[br(try_map = |header: StructHeader| header.try_into())]
         #[bw(map = |props: &Vec<TableHeaderProperty>| -> StructHeader { props.clone().into() })]
         header: Vec<TableHeaderProperty>,
         #[br(parse_with = until_magic_with(0u8, |r,e,_: ()| ChSparseTableElement::read_options(r, e, (&header,)), |reader, endian, arg| {
@@ -446,12 +843,12 @@ pub enum ChunkValue {
 }
 
 #[cfg(feature = "lzma-rs")]
-fn lzma_error_to_io(error: lzma_rs::error::Error) -> Error {
+fn lzma_error_to_io(error: lzma_rs::error::Error) -> binrw::io::Error {
     match error {
         lzma_rs::error::Error::IoError(e) => e,
         lzma_rs::error::Error::HeaderTooShort(e) => e,
-        lzma_rs::error::Error::LzmaError(str) => Error::new(ErrorKind::Other, str),
-        lzma_rs::error::Error::XzError(str) => Error::new(ErrorKind::Other, str),
+        lzma_rs::error::Error::LzmaError(str) => binrw::io::Error::other(str),
+        lzma_rs::error::Error::XzError(str) => binrw::io::Error::other(str),
     }
 }
 
@@ -463,7 +860,8 @@ mod tests {
         io::{Cursor, Result},
     };
 
-    use crate::save::{Chunks, OuterSave, Save};
+    use crate::error::Error;
+    use crate::save::{Chunks, CompressionType, LazySave, OuterSave, Save, WriteOptions};
 
     #[test]
     fn parse_and_write_outer_tiny() -> Result<()> {
@@ -501,6 +899,248 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg(feature = "flate2")]
+    fn parse_and_write_outer_zlib() -> Result<()> {
+        let mut f = File::open("tests/TinyVanillaTest.sav")?;
+        let outer: OuterSave = f.read_ne().unwrap();
+
+        let zlib = OuterSave {
+            compression_type: CompressionType::OTTZ,
+            ..outer
+        };
+
+        let mut d = vec![];
+        zlib.write(&mut Cursor::new(&mut d)).unwrap();
+        assert_eq!(&d[0..4], b"OTTZ");
+
+        let value: OuterSave = Cursor::new(&d).read_ne().unwrap();
+        assert_eq!(&zlib.data, &value.data);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "flate2")]
+    fn parse_and_write_zlib() -> Result<()> {
+        let mut f = File::open("tests/TinyVanillaTest.sav")?;
+        let mut save: Save = f.read_ne().unwrap();
+        save.compression_type = CompressionType::OTTZ;
+
+        let mut d = vec![];
+        save.write(&mut Cursor::new(&mut d)).unwrap();
+
+        let value: Save = Cursor::new(&d).read_ne().unwrap();
+        assert_eq!(save.chunks.len(), value.chunks.len());
+
+        let mut e = vec![];
+        value.write(&mut Cursor::new(&mut e)).unwrap();
+        assert_eq!(d, e);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_and_write_outer_lzo() -> Result<()> {
+        let mut f = File::open("tests/TinyVanillaTest.sav")?;
+        let outer: OuterSave = f.read_ne().unwrap();
+
+        let lzo = OuterSave {
+            compression_type: CompressionType::OTTD,
+            ..outer
+        };
+
+        let mut d = vec![];
+        lzo.write(&mut Cursor::new(&mut d)).unwrap();
+        assert_eq!(&d[0..4], b"OTTD");
+
+        let value: OuterSave = Cursor::new(&d).read_ne().unwrap();
+        assert_eq!(&lzo.data, &value.data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_and_write_lzo() -> Result<()> {
+        let mut f = File::open("tests/tiny.sav")?;
+        let mut save: Save = f.read_ne().unwrap();
+        save.compression_type = CompressionType::OTTD;
+
+        let mut d = vec![];
+        save.write(&mut Cursor::new(&mut d)).unwrap();
+
+        let value: Save = Cursor::new(&d).read_ne().unwrap();
+        assert_eq!(save.chunks.len(), value.chunks.len());
+
+        let mut e = vec![];
+        value.write(&mut Cursor::new(&mut e)).unwrap();
+        assert_eq!(d, e);
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_with_options_uncompressed() -> Result<()> {
+        let mut f = File::open("tests/TinyVanillaTest.sav")?;
+        let outer: OuterSave = f.read_ne().unwrap();
+
+        let mut d = vec![];
+        outer
+            .write_with_options(
+                &mut Cursor::new(&mut d),
+                &WriteOptions::new(CompressionType::OTTN),
+            )
+            .unwrap();
+        assert_eq!(&d[0..4], b"OTTN");
+        assert_eq!(&d[8..], &outer.data);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn write_with_options_zstd() -> Result<()> {
+        let mut f = File::open("tests/TinyVanillaTest.sav")?;
+        let save: Save = f.read_ne().unwrap();
+
+        let options = WriteOptions {
+            compression_type: Some(CompressionType::OTTS),
+            level: Some(19),
+            threads: 2,
+        };
+        let mut d = vec![];
+        save.write_with_options(&mut Cursor::new(&mut d), &options)
+            .unwrap();
+        assert_eq!(&d[0..4], b"OTTS");
+
+        let value = Save::from_reader(&mut Cursor::new(&d)).unwrap();
+        assert_eq!(value.compression_type, CompressionType::OTTS);
+        assert_eq!(save.chunks.len(), value.chunks.len());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(any(feature = "xz2", feature = "lzma-rs"))]
+    fn write_with_options_lzma() -> Result<()> {
+        let mut f = File::open("tests/tiny.sav")?;
+        let outer: OuterSave = f.read_ne().unwrap();
+
+        let fast = WriteOptions {
+            level: Some(0),
+            ..Default::default()
+        };
+        let best = WriteOptions {
+            level: Some(9),
+            threads: 2,
+            ..Default::default()
+        };
+        for options in [fast, best] {
+            let mut d = vec![];
+            outer
+                .write_with_options(&mut Cursor::new(&mut d), &options)
+                .unwrap();
+            assert_eq!(&d[0..4], b"OTTX");
+
+            let value = OuterSave::from_reader(&mut Cursor::new(&d)).unwrap();
+            assert_eq!(&outer.data, &value.data);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "flate2")]
+    fn invalid_compression_level() {
+        let save =
+            Save::from_reader(&mut Cursor::new(b"OTTN\x01\x2e\x00\x00\x00\x00\x00\x00")).unwrap();
+        let options = WriteOptions {
+            level: Some(10),
+            ..WriteOptions::new(CompressionType::OTTZ)
+        };
+        let err = save
+            .write_with_options(&mut Cursor::new(vec![]), &options)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidCompressionLevel {
+                level: 10,
+                min: 0,
+                max: 9
+            }
+        ));
+    }
+
+    #[test]
+    fn lazy_save() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?).unwrap();
+        let mut lazy =
+            LazySave::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?).unwrap();
+        assert_eq!(lazy.chunks.len(), save.chunks.len());
+
+        let maps = lazy.get(b"MAPS").unwrap().decode().unwrap();
+        assert_eq!(
+            maps.value.chunk_type(),
+            save.get(b"MAPS").unwrap().chunk_type()
+        );
+
+        let options = WriteOptions::new(CompressionType::OTTN);
+        let mut expected = vec![];
+        save.write_with_options(&mut Cursor::new(&mut expected), &options)
+            .unwrap();
+        let mut d = vec![];
+        lazy.write_with_options(&mut d, &options).unwrap();
+        assert_eq!(d, expected);
+
+        // Replacing a chunk with its decoded value keeps the save the same
+        lazy.set(&maps).unwrap();
+        let mut e = vec![];
+        lazy.write_with_options(&mut e, &options).unwrap();
+        assert_eq!(e, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lazy_save_passthrough() {
+        // MAPS can't be decoded as its element is too short for its u32 field
+        let data = b"OTTN\x01\x2e\x00\x00MAPS\x03\x05\x06\x01a\x00\x02\x00\x00\x00\x00\x00\x00";
+        let lazy = LazySave::from_reader(&mut Cursor::new(data)).unwrap();
+        assert!(lazy.get(b"MAPS").unwrap().decode().is_err());
+
+        let mut d = vec![];
+        lazy.to_writer(&mut d).unwrap();
+        assert_eq!(&d, data);
+    }
+
+    #[test]
+    fn unsupported_compression() {
+        let err = Save::from_reader(&mut Cursor::new(b"OTTQ\x01\x2e\x00\x00")).unwrap_err();
+        assert!(matches!(err, Error::UnsupportedCompression { magic } if &magic == b"OTTQ"));
+    }
+
+    #[test]
+    fn bad_chunk_type() {
+        let data = b"OTTN\x01\x2e\x00\x00MAPS\x07\x00\x00\x00\x00";
+        let err = Save::from_reader(&mut Cursor::new(data)).unwrap_err();
+        assert_eq!(err.chunk_tag(), Some(*b"MAPS"));
+        assert!(
+            matches!(err, Error::Chunk { source, .. } if matches!(*source, Error::BadChunkType { chunk_type: 7 }))
+        );
+    }
+
+    #[test]
+    fn length_mismatch() {
+        // A table with a single u32 "a" and an element that claims to be only 1 byte long
+        let data = b"OTTN\x01\x2e\x00\x00MAPS\x03\x05\x06\x01a\x00\x02\x00\x00\x00\x01\x00\x00\x00\x00\x00";
+        let err = Save::from_reader(&mut Cursor::new(data)).unwrap_err();
+        assert_eq!(err.chunk_tag(), Some(*b"MAPS"));
+        assert!(matches!(
+            err,
+            Error::Chunk { source, .. } if matches!(*source, Error::LengthMismatch { expected: 1, actual: 4 })
+        ));
+    }
+
     #[test]
     fn serialize() -> Result<()> {
         let mut f = File::open("tests/TinyVanillaTest.sav")?;
@@ -0,0 +1,1279 @@
+//! Descriptions of the array layouts of chunks, for each savegame version.
+//!
+//! Saves before version 293 store pool items as bare values with no keys, in an order set by
+//! OpenTTD's `SaveLoad` tables where each field has a range of versions it's saved in. JGR saves
+//! add fields when they have an extended feature in `SLXI`. A [`ChunkSchema`] describes those
+//! fields so an element can be read as the same `(key, value)` rows a table chunk has, and the
+//! models only need to understand one layout.
+//!
+//! Only the layouts since the last big change to each chunk are described, so older saves can't
+//! be read through the schema. The oldest savegame version each chunk is supported from:
+//!
+//! | Chunk  | Since | Chunk  | Since |
+//! |--------|-------|--------|-------|
+//! | `ORDR` | 5     | `BKOR` | 176   |
+//! | `CAPA` | 68    | `PLYR` | 170   |
+//! | `STNS` | 69    | `VEHS` | 182   |
+//! | `ORDL` | 105   | `STNN` | 183   |
+//! | `INDY` | 161   | `NGRF` | 0     |
+//! | `CITY` | 165   |        |       |
+//!
+//! [`ChunkSchema::find`] returns an error naming the oldest supported version for saves before it.
+
+use binrw::{io::Cursor, BinReaderExt, BinWrite};
+use serde::Serialize;
+
+use crate::charray::{INDUSTRY_NUM_CARGO, LS_END, MAX_CARGO, MAX_COMPANIES, NUM_TE};
+use crate::chtable::{
+    field_mut, TableData, TableDataType, TableHeaderProperty, TableString, TableStruct,
+};
+use crate::error::{Error, Result};
+use crate::jgr::SLXI;
+use crate::station::FACIL_WAYPOINT;
+use crate::table::{coerce, to_row};
+
+/// The type a value is stored as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    StringId,
+    /// A string with its length as a gamma
+    Str,
+}
+
+impl VarType {
+    /// The table type values of this type are read as
+    pub fn table_type(self) -> TableDataType {
+        match self {
+            VarType::Int8 => TableDataType::Int8,
+            VarType::UInt8 => TableDataType::UInt8,
+            VarType::Int16 => TableDataType::Int16,
+            VarType::UInt16 => TableDataType::UInt16,
+            VarType::Int32 => TableDataType::Int32,
+            VarType::UInt32 => TableDataType::UInt32,
+            VarType::Int64 => TableDataType::Int64,
+            VarType::UInt64 => TableDataType::UInt64,
+            VarType::StringId => TableDataType::StringId,
+            VarType::Str => TableDataType::Str,
+        }
+    }
+
+    /// The table type arrays of this type are read as
+    pub fn list_type(self) -> TableDataType {
+        match self {
+            VarType::Int8 => TableDataType::Int8List,
+            VarType::UInt8 => TableDataType::UInt8List,
+            VarType::Int16 => TableDataType::Int16List,
+            VarType::UInt16 => TableDataType::UInt16List,
+            VarType::Int32 => TableDataType::Int32List,
+            VarType::UInt32 => TableDataType::UInt32List,
+            VarType::Int64 => TableDataType::Int64List,
+            VarType::UInt64 => TableDataType::UInt64List,
+            VarType::StringId => TableDataType::StringIdList,
+            // OpenTTD has no lists of strings
+            VarType::Str => TableDataType::Str,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Var(VarType),
+    /// A fixed number of values, read as a list
+    Array(VarType, usize),
+    /// Values with a 32 bit count, like OpenTTD's vectors and deques, read as a list
+    List(VarType),
+    /// Pool references with a 32 bit count, read as a `UInt32List`
+    RefList,
+    /// A fixed number of rows of other fields, read as a struct
+    Struct(&'static [Field], usize),
+    /// Rows of other fields, as many as the value of an earlier field with the given key. The
+    /// count can be in the same row or a struct in it, and is written from the number of rows.
+    StructList(&'static [Field], &'static str),
+    /// Bytes of a field that isn't used anymore. They're skipped when reading and zero when
+    /// writing.
+    Null(usize),
+    /// The town acceptance cache saves had for a while, which OpenTTD discards when loading.
+    /// It's written back empty.
+    AcceptanceMatrix,
+}
+
+/// A field of an element, saved in versions `since..until` and only when the save has the JGR
+/// extended `feature` if there is one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub field_type: FieldType,
+    pub since: u16,
+    pub until: u16,
+    pub feature: Option<&'static str>,
+    /// Only saved for some elements, depending on an earlier field
+    pub condition: Option<Condition>,
+}
+
+/// Saved only when the bits of `mask` in an earlier field of the row are `value`, like the
+/// struct of a vehicle's type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub key: &'static str,
+    pub mask: u64,
+    pub value: u64,
+}
+
+impl Field {
+    const fn new(name: &'static str, field_type: FieldType) -> Field {
+        Field {
+            name,
+            field_type,
+            since: 0,
+            until: u16::MAX,
+            feature: None,
+            condition: None,
+        }
+    }
+
+    const fn since(self, since: u16) -> Field {
+        Field { since, ..self }
+    }
+
+    const fn until(self, until: u16) -> Field {
+        Field { until, ..self }
+    }
+
+    const fn feature(self, feature: &'static str) -> Field {
+        Field {
+            feature: Some(feature),
+            ..self
+        }
+    }
+
+    const fn when(self, key: &'static str, mask: u64, value: u64) -> Field {
+        Field {
+            condition: Some(Condition { key, mask, value }),
+            ..self
+        }
+    }
+
+    /// Whether the field is saved in a version, with the JGR features of `slxi`. JGR saves
+    /// have to pass their version without the flag, see [`Save::base_version`].
+    ///
+    /// [`Save::base_version`]: crate::save::Save::base_version
+    pub fn exists(&self, version: u16, slxi: &SLXI) -> bool {
+        (self.since..self.until).contains(&version)
+            && self.feature.is_none_or(|name| slxi.has_feature(name))
+    }
+
+    /// Whether the condition of the field holds for a row, which only has to have the fields
+    /// before it
+    fn applies(&self, row: &[(String, TableData)]) -> Result<bool> {
+        let Some(condition) = self.condition else {
+            return Ok(true);
+        };
+        let value = find_int(row, condition.key).ok_or_else(|| Error::Malformed {
+            message: format!(
+                "{} depends on {}, which isn't set",
+                self.name, condition.key
+            ),
+        })?;
+        Ok(value as u64 & condition.mask == condition.value)
+    }
+}
+
+const fn var(name: &'static str, var_type: VarType) -> Field {
+    Field::new(name, FieldType::Var(var_type))
+}
+
+const fn array(name: &'static str, var_type: VarType, len: usize) -> Field {
+    Field::new(name, FieldType::Array(var_type, len))
+}
+
+const fn null(len: usize) -> Field {
+    Field::new("", FieldType::Null(len))
+}
+
+/// The fields of every element of a chunk, for the versions from `since`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkSchema {
+    pub tag: [u8; 4],
+    /// The oldest version the fields are known for
+    pub since: u16,
+    pub fields: &'static [Field],
+}
+
+/// Version 199 doubled the number of cargo types from 32 to 64
+const SLV_EXTEND_CARGOTYPES: u16 = 199;
+const SLV_REMOVE_TOWN_CARGO_CACHE: u16 = 219;
+/// Version 69 made references to other pools 32 bit
+const SLV_REF_32BIT: u16 = 69;
+/// Version 202 gave every industry 16 cargo slots
+const SLV_EXTEND_INDUSTRY_CARGO_SLOTS: u16 = 202;
+const SLV_SERVE_NEUTRAL_INDUSTRIES: u16 = 210;
+const SLV_GS_INDUSTRY_CONTROL: u16 = 287;
+const SLV_INDUSTRY_TEXT: u16 = 289;
+/// Version 216 gave docks a size and a separate docking area
+const SLV_MULTITILE_DOCKS: u16 = 216;
+
+const TOWN_SUPPLIED: &[Field] = &[
+    var("old_max", VarType::UInt32),
+    var("new_max", VarType::UInt32),
+    var("old_act", VarType::UInt32),
+    var("new_act", VarType::UInt32),
+];
+
+const TOWN_RECEIVED: &[Field] = &[
+    var("old_max", VarType::UInt16),
+    var("new_max", VarType::UInt16),
+    var("old_act", VarType::UInt16),
+    var("new_act", VarType::UInt16),
+];
+
+/// `CITY`, from version 165 when town cargo statistics took their current form
+const CITY: ChunkSchema = ChunkSchema {
+    tag: *b"CITY",
+    since: 165,
+    fields: &[
+        var("xy", VarType::UInt32),
+        var("townnamegrfid", VarType::UInt32),
+        var("townnametype", VarType::UInt16),
+        var("townnameparts", VarType::UInt32),
+        var("name", VarType::Str),
+        var("flags", VarType::UInt8),
+        var("church_count", VarType::UInt16).feature("town_multi_building"),
+        var("stadium_count", VarType::UInt16).feature("town_multi_building"),
+        var("statues", VarType::UInt16),
+        var("have_ratings", VarType::UInt16),
+        array("ratings", VarType::Int16, MAX_COMPANIES),
+        array("unwanted", VarType::Int8, MAX_COMPANIES),
+        array("goal", VarType::UInt32, NUM_TE),
+        var("text", VarType::Str).since(168),
+        var("time_until_rebuild", VarType::UInt16),
+        var("grow_counter", VarType::UInt16),
+        var("growth_rate", VarType::UInt16),
+        var("fund_buildings_months", VarType::UInt8),
+        var("road_build_months", VarType::UInt8),
+        var("exclusivity", VarType::UInt8),
+        var("exclusive_counter", VarType::UInt8),
+        var("larger_town", VarType::Int8),
+        var("layout", VarType::UInt8),
+        Field::new("psa_list", FieldType::RefList),
+        var("override_flags", VarType::UInt8).feature("town_setting_override"),
+        var("override_values", VarType::UInt8).feature("town_setting_override"),
+        var("build_tunnels", VarType::UInt8).feature("town_setting_override"),
+        var("max_road_slope", VarType::UInt8).feature("town_setting_override"),
+        // cargo_produced and reserved space
+        null(4).since(166).until(SLV_EXTEND_CARGOTYPES),
+        null(8)
+            .since(SLV_EXTEND_CARGOTYPES)
+            .until(SLV_REMOVE_TOWN_CARGO_CACHE),
+        null(30).until(SLV_REMOVE_TOWN_CARGO_CACHE),
+        Field::new("supplied", FieldType::Struct(TOWN_SUPPLIED, 32)).until(SLV_EXTEND_CARGOTYPES),
+        Field::new("supplied", FieldType::Struct(TOWN_SUPPLIED, MAX_CARGO))
+            .since(SLV_EXTEND_CARGOTYPES),
+        Field::new("received", FieldType::Struct(TOWN_RECEIVED, NUM_TE)),
+        Field::new("", FieldType::AcceptanceMatrix)
+            .since(166)
+            .until(SLV_REMOVE_TOWN_CARGO_CACHE),
+    ],
+};
+
+/// `CAPA`, from version 68 when cargo packets were added
+const CAPA: ChunkSchema = ChunkSchema {
+    tag: *b"CAPA",
+    since: 68,
+    fields: &[
+        var("source", VarType::UInt16),
+        var("source_xy", VarType::UInt32),
+        var("loaded_at_xy", VarType::UInt32),
+        var("count", VarType::UInt16),
+        var("days_in_transit", VarType::UInt8),
+        var("feeder_share", VarType::Int64),
+        var("source_type", VarType::UInt8).since(125),
+        var("source_id", VarType::UInt16).since(125),
+        // paid_for
+        null(1).until(121),
+    ],
+};
+
+const NGRF: ChunkSchema = ChunkSchema {
+    tag: *b"NGRF",
+    since: 0,
+    fields: &[
+        var("filename", VarType::Str),
+        var("ident.grfid", VarType::UInt32),
+        array("ident.md5sum", VarType::UInt8, 16),
+        var("version", VarType::UInt32).since(151),
+        array("param", VarType::UInt32, 0x80),
+        var("num_params", VarType::UInt8),
+        var("palette", VarType::UInt8).since(101),
+    ],
+};
+
+/// `ORDR`, from version 5 when orders got their own chunk
+const ORDR: ChunkSchema = ChunkSchema {
+    tag: *b"ORDR",
+    since: 5,
+    fields: &[
+        var("type", VarType::UInt8),
+        var("flags", VarType::UInt8),
+        var("dest", VarType::UInt16),
+        var("next", VarType::UInt16).until(SLV_REF_32BIT),
+        var("next", VarType::UInt32).since(SLV_REF_32BIT),
+        var("refit_cargo", VarType::UInt8).since(36),
+        // refit_subtype
+        null(1).since(36).until(182),
+        var("wait_time", VarType::UInt16).since(67),
+        var("travel_time", VarType::UInt16).since(67),
+        var("max_speed", VarType::UInt16).since(172),
+        // reserved space
+        null(10).until(36),
+    ],
+};
+
+/// `ORDL`, from version 105 when vehicles sharing orders started sharing a list
+const ORDL: ChunkSchema = ChunkSchema {
+    tag: *b"ORDL",
+    since: 105,
+    fields: &[var("first", VarType::UInt32)],
+};
+
+/// `BKOR`, from version 176 when order backups took their current form
+const BKOR: ChunkSchema = ChunkSchema {
+    tag: *b"BKOR",
+    since: 176,
+    fields: &[
+        var("user", VarType::UInt32),
+        var("tile", VarType::UInt32),
+        var("group", VarType::UInt16),
+        var("service_interval", VarType::UInt32).until(192),
+        var("service_interval", VarType::UInt16).since(192),
+        var("name", VarType::Str),
+        // clone, which used to be saved as 2 bytes of a pointer
+        null(2).until(192),
+        var("clone", VarType::UInt32).since(192),
+        var("cur_real_order_index", VarType::UInt8),
+        var("cur_implicit_order_index", VarType::UInt8),
+        var("current_order_time", VarType::UInt32),
+        var("lateness_counter", VarType::Int32),
+        var("timetable_start", VarType::Int32),
+        var("vehicle_flags", VarType::UInt8).until(180),
+        var("vehicle_flags", VarType::UInt16).since(180),
+        var("orders", VarType::UInt32),
+    ],
+};
+
+const COMPANY_SETTINGS: &[Field] = &[
+    var("engine_renew_list", VarType::UInt32),
+    var("settings.engine_renew", VarType::Int8),
+    var("settings.engine_renew_months", VarType::Int16),
+    var("settings.engine_renew_money", VarType::UInt32),
+    var("settings.renew_keep_length", VarType::Int8),
+    var("settings.vehicle.servint_ispercent", VarType::Int8),
+    var("settings.vehicle.servint_trains", VarType::UInt16),
+    var("settings.vehicle.servint_roadveh", VarType::UInt16),
+    var("settings.vehicle.servint_aircraft", VarType::UInt16),
+    var("settings.vehicle.servint_ships", VarType::UInt16),
+];
+
+const COMPANY_ECONOMY: &[Field] = &[
+    var("income", VarType::Int64),
+    var("expenses", VarType::Int64),
+    var("company_value", VarType::Int64),
+    array("delivered_cargo", VarType::UInt32, 32).until(SLV_EXTEND_CARGOTYPES),
+    array("delivered_cargo", VarType::UInt32, MAX_CARGO).since(SLV_EXTEND_CARGOTYPES),
+    var("performance_history", VarType::Int32),
+];
+
+const LIVERY: &[Field] = &[
+    var("in_use", VarType::UInt8),
+    var("colour1", VarType::UInt8),
+    var("colour2", VarType::UInt8),
+];
+
+/// `PLYR`, from version 170 when deliveries were first counted per cargo type
+const PLYR: ChunkSchema = ChunkSchema {
+    tag: *b"PLYR",
+    since: 170,
+    fields: &[
+        var("name_2", VarType::UInt32),
+        var("name_1", VarType::StringId),
+        var("name", VarType::Str),
+        var("president_name_1", VarType::StringId),
+        var("president_name_2", VarType::UInt32),
+        var("president_name", VarType::Str),
+        var("face", VarType::UInt32),
+        var("money", VarType::Int64),
+        var("current_loan", VarType::Int64),
+        var("colour", VarType::UInt8),
+        var("money_fraction", VarType::UInt8),
+        var("block_preview", VarType::UInt8),
+        var("location_of_HQ", VarType::UInt32),
+        var("last_build_coordinate", VarType::UInt32),
+        var("inaugurated_year", VarType::Int32),
+        array("share_owners", VarType::UInt8, 4),
+        var("num_valid_stat_ent", VarType::UInt8),
+        var("months_of_bankruptcy", VarType::UInt8),
+        var("bankrupt_asked", VarType::UInt16),
+        var("bankrupt_timeout", VarType::Int16),
+        var("bankrupt_value", VarType::Int64),
+        array("yearly_expenses", VarType::Int64, 3 * 13),
+        var("is_ai", VarType::Int8),
+        var("terraform_limit", VarType::UInt32),
+        var("clear_limit", VarType::UInt32),
+        var("tree_limit", VarType::UInt32).since(175),
+        Field::new("settings", FieldType::Struct(COMPANY_SETTINGS, 1)),
+        Field::new("cur_economy", FieldType::Struct(COMPANY_ECONOMY, 1)),
+        Field::new(
+            "old_economy",
+            FieldType::StructList(COMPANY_ECONOMY, "num_valid_stat_ent"),
+        ),
+        Field::new("liveries", FieldType::Struct(LIVERY, LS_END)),
+    ],
+};
+
+/// `INDY`, from version 161 when industries refer to their persistent storage
+const INDY: ChunkSchema = ChunkSchema {
+    tag: *b"INDY",
+    since: 161,
+    fields: &[
+        var("location.tile", VarType::UInt32),
+        var("location.w", VarType::UInt8),
+        var("location.h", VarType::UInt8),
+        var("town", VarType::UInt32),
+        var("neutral_station", VarType::UInt32).since(SLV_SERVE_NEUTRAL_INDUSTRIES),
+        array("produced_cargo", VarType::UInt8, 2).until(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("produced_cargo", VarType::UInt8, INDUSTRY_NUM_CARGO)
+            .since(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("incoming_cargo_waiting", VarType::UInt16, 3).until(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array(
+            "incoming_cargo_waiting",
+            VarType::UInt16,
+            INDUSTRY_NUM_CARGO,
+        )
+        .since(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("produced_cargo_waiting", VarType::UInt16, 2).until(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array(
+            "produced_cargo_waiting",
+            VarType::UInt16,
+            INDUSTRY_NUM_CARGO,
+        )
+        .since(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("production_rate", VarType::UInt8, 2).until(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("production_rate", VarType::UInt8, INDUSTRY_NUM_CARGO)
+            .since(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("accepts_cargo", VarType::UInt8, 3).until(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("accepts_cargo", VarType::UInt8, INDUSTRY_NUM_CARGO)
+            .since(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        var("prod_level", VarType::UInt8),
+        array("this_month_production", VarType::UInt16, 2).until(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("this_month_production", VarType::UInt16, INDUSTRY_NUM_CARGO)
+            .since(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("this_month_transported", VarType::UInt16, 2).until(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array(
+            "this_month_transported",
+            VarType::UInt16,
+            INDUSTRY_NUM_CARGO,
+        )
+        .since(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("last_month_pct_transported", VarType::UInt8, 2)
+            .until(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array(
+            "last_month_pct_transported",
+            VarType::UInt8,
+            INDUSTRY_NUM_CARGO,
+        )
+        .since(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("last_month_production", VarType::UInt16, 2).until(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("last_month_production", VarType::UInt16, INDUSTRY_NUM_CARGO)
+            .since(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("last_month_transported", VarType::UInt16, 2).until(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array(
+            "last_month_transported",
+            VarType::UInt16,
+            INDUSTRY_NUM_CARGO,
+        )
+        .since(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        var("counter", VarType::UInt16),
+        var("type", VarType::UInt8),
+        var("owner", VarType::UInt8),
+        var("random_colour", VarType::UInt8),
+        var("last_prod_year", VarType::Int32),
+        var("was_cargo_delivered", VarType::UInt8),
+        var("ctlflags", VarType::UInt8).since(SLV_GS_INDUSTRY_CONTROL),
+        var("founder", VarType::UInt8),
+        var("construction_date", VarType::Int32),
+        var("construction_type", VarType::UInt8),
+        // Only the first slot's date was kept before
+        array("last_cargo_accepted_at", VarType::Int32, 1).until(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        array("last_cargo_accepted_at", VarType::Int32, INDUSTRY_NUM_CARGO)
+            .since(SLV_EXTEND_INDUSTRY_CARGO_SLOTS),
+        var("selected_layout", VarType::UInt8),
+        var("exclusive_supplier", VarType::UInt8).since(SLV_GS_INDUSTRY_CONTROL),
+        var("exclusive_consumer", VarType::UInt8).since(SLV_GS_INDUSTRY_CONTROL),
+        var("psa", VarType::UInt32),
+        // Random triggers
+        null(1).until(197),
+        var("random", VarType::UInt16),
+        var("text", VarType::Str).since(SLV_INDUSTRY_TEXT),
+    ],
+};
+
+const VEHICLE_COMMON: &[Field] = &[
+    var("subtype", VarType::UInt8),
+    var("next", VarType::UInt32),
+    var("name", VarType::Str),
+    var("unitnumber", VarType::UInt16),
+    var("owner", VarType::UInt8),
+    var("tile", VarType::UInt32),
+    var("dest_tile", VarType::UInt32),
+    var("x_pos", VarType::UInt32),
+    var("y_pos", VarType::UInt32),
+    var("z_pos", VarType::Int32),
+    var("direction", VarType::UInt8),
+    var("spritenum", VarType::UInt8),
+    var("engine_type", VarType::UInt16),
+    var("cur_speed", VarType::UInt16),
+    var("subspeed", VarType::UInt8),
+    var("acceleration", VarType::UInt8),
+    var("motion_counter", VarType::UInt32).since(288),
+    var("progress", VarType::UInt8),
+    var("vehstatus", VarType::UInt8),
+    var("last_station_visited", VarType::UInt16),
+    var("last_loading_station", VarType::UInt16),
+    var("cargo_type", VarType::UInt8),
+    var("cargo_subtype", VarType::UInt8),
+    var("cargo_cap", VarType::UInt16),
+    var("refit_cap", VarType::UInt16),
+    Field::new("cargo.packets", FieldType::RefList),
+    array("cargo.action_counts", VarType::UInt32, 4),
+    var("cargo_age_counter", VarType::UInt16),
+    var("day_counter", VarType::UInt8),
+    var("tick_counter", VarType::UInt8),
+    var("running_ticks", VarType::UInt8),
+    var("cur_implicit_order_index", VarType::UInt8),
+    var("cur_real_order_index", VarType::UInt8),
+    var("current_order.type", VarType::UInt8),
+    var("current_order.flags", VarType::UInt8),
+    var("current_order.dest", VarType::UInt16),
+    var("current_order.refit_cargo", VarType::UInt8),
+    var("current_order.wait_time", VarType::UInt16),
+    var("current_order.travel_time", VarType::UInt16),
+    var("current_order.max_speed", VarType::UInt16),
+    var("timetable_start", VarType::Int32),
+    var("orders", VarType::UInt32),
+    var("age", VarType::Int32),
+    var("max_age", VarType::Int32),
+    var("date_of_last_service", VarType::Int32),
+    var("service_interval", VarType::UInt16),
+    var("reliability", VarType::UInt16),
+    var("reliability_spd_dec", VarType::UInt16),
+    var("breakdown_ctr", VarType::UInt8),
+    var("breakdown_delay", VarType::UInt8),
+    var("breakdowns_since_last_service", VarType::UInt8),
+    var("breakdown_chance", VarType::UInt8),
+    var("build_year", VarType::Int32),
+    var("load_unload_ticks", VarType::UInt16),
+    var("cargo_paid_for", VarType::UInt16),
+    var("vehicle_flags", VarType::UInt16),
+    var("profit_this_year", VarType::Int64),
+    var("profit_last_year", VarType::Int64),
+    var("value", VarType::Int64),
+    var("random_bits", VarType::UInt8),
+    var("waiting_triggers", VarType::UInt8),
+    var("next_shared", VarType::UInt32),
+    var("group_id", VarType::UInt16),
+    var("current_order_time", VarType::UInt32),
+    var("lateness_counter", VarType::Int32),
+];
+
+const TRAIN: &[Field] = &[
+    Field::new("common", FieldType::Struct(VEHICLE_COMMON, 1)),
+    var("crash_anim_pos", VarType::UInt16),
+    var("force_proceed", VarType::UInt8),
+    var("railtype", VarType::UInt8),
+    var("track", VarType::UInt8),
+    var("flags", VarType::UInt16),
+    var("wait_counter", VarType::UInt16),
+    var("gv_flags", VarType::UInt16),
+];
+
+const ROAD_VEHICLE: &[Field] = &[
+    Field::new("common", FieldType::Struct(VEHICLE_COMMON, 1)),
+    var("state", VarType::UInt8),
+    var("frame", VarType::UInt8),
+    var("blocked_ctr", VarType::UInt16),
+    var("overtaking", VarType::UInt8),
+    var("overtaking_ctr", VarType::UInt8),
+    var("crashed_ctr", VarType::UInt16),
+    var("reverse_ctr", VarType::UInt8),
+    Field::new("path.td", FieldType::List(VarType::UInt8)).since(211),
+    Field::new("path.tile", FieldType::List(VarType::UInt32)).since(211),
+    var("gv_flags", VarType::UInt16),
+];
+
+const SHIP: &[Field] = &[
+    Field::new("common", FieldType::Struct(VEHICLE_COMMON, 1)),
+    var("state", VarType::UInt8),
+    Field::new("path", FieldType::List(VarType::UInt8)).since(203),
+    var("rotation", VarType::UInt8).since(204),
+];
+
+const AIRCRAFT: &[Field] = &[
+    Field::new("common", FieldType::Struct(VEHICLE_COMMON, 1)),
+    var("crashed_counter", VarType::UInt16),
+    var("pos", VarType::UInt8),
+    var("targetairport", VarType::UInt16),
+    var("state", VarType::UInt8),
+    var("previous_pos", VarType::UInt8),
+    var("last_direction", VarType::UInt8),
+    var("number_consecutive_turns", VarType::UInt8),
+    var("turn_counter", VarType::UInt8),
+    var("flags", VarType::UInt8),
+];
+
+const EFFECT_VEHICLE: &[Field] = &[
+    var("subtype", VarType::UInt8),
+    var("tile", VarType::UInt32),
+    var("x_pos", VarType::Int32),
+    var("y_pos", VarType::Int32),
+    var("z_pos", VarType::Int32),
+    var("sprite_cache.sprite_seq.seq[0].sprite", VarType::UInt16),
+    var("progress", VarType::UInt8),
+    var("vehstatus", VarType::UInt8),
+    var("animation_state", VarType::UInt16),
+    var("animation_substate", VarType::UInt8),
+    var("spritenum", VarType::UInt8),
+];
+
+const DISASTER_VEHICLE: &[Field] = &[
+    var("next", VarType::UInt32),
+    var("subtype", VarType::UInt8),
+    var("tile", VarType::UInt32),
+    var("dest_tile", VarType::UInt32),
+    var("x_pos", VarType::Int32),
+    var("y_pos", VarType::Int32),
+    var("z_pos", VarType::Int32),
+    var("direction", VarType::UInt8),
+    var("owner", VarType::UInt8),
+    var("vehstatus", VarType::UInt8),
+    var("current_order.dest", VarType::UInt16),
+    var("sprite_cache.sprite_seq.seq[0].sprite", VarType::UInt16),
+    var("age", VarType::Int32),
+    var("tick_counter", VarType::UInt8),
+    var("image_override", VarType::UInt16).until(191),
+    var("image_override", VarType::UInt32).since(191),
+    var("big_ufo_destroyer_target", VarType::UInt16).until(191),
+    var("big_ufo_destroyer_target", VarType::UInt32).since(191),
+    var("flags", VarType::UInt8).since(194),
+];
+
+/// `VEHS`, from version 182 when vehicles started remembering where they last loaded. Only the
+/// struct for the vehicle's `type` is saved.
+const VEHS: ChunkSchema = ChunkSchema {
+    tag: *b"VEHS",
+    since: 182,
+    fields: &[
+        var("type", VarType::UInt8),
+        Field::new("train", FieldType::Struct(TRAIN, 1)).when("type", 0xFF, 0),
+        Field::new("roadveh", FieldType::Struct(ROAD_VEHICLE, 1)).when("type", 0xFF, 1),
+        Field::new("ship", FieldType::Struct(SHIP, 1)).when("type", 0xFF, 2),
+        Field::new("aircraft", FieldType::Struct(AIRCRAFT, 1)).when("type", 0xFF, 3),
+        Field::new("effect", FieldType::Struct(EFFECT_VEHICLE, 1)).when("type", 0xFF, 4),
+        Field::new("disaster", FieldType::Struct(DISASTER_VEHICLE, 1)).when("type", 0xFF, 5),
+    ],
+};
+
+const BASE_STATION: &[Field] = &[
+    var("xy", VarType::UInt32),
+    var("town", VarType::UInt32),
+    var("string_id", VarType::StringId),
+    var("name", VarType::Str),
+    var("delete_ctr", VarType::UInt8),
+    var("owner", VarType::UInt8),
+    var("facilities", VarType::UInt8),
+    var("build_date", VarType::Int32),
+    var("random_bits", VarType::UInt16),
+    var("waiting_triggers", VarType::UInt8),
+    var("num_specs", VarType::UInt8),
+];
+
+const FLOW_SHARE: &[Field] = &[
+    var("source", VarType::UInt16),
+    var("via", VarType::UInt16),
+    var("share", VarType::UInt32),
+    var("restricted", VarType::Int8).since(187),
+];
+
+const STATION_CARGO: &[Field] = &[
+    var("first", VarType::UInt16),
+    Field::new("second", FieldType::RefList),
+];
+
+/// A goods entry followed by its flows and waiting cargo, which the array layout counts in it
+const GOODS_ENTRY: &[Field] = &[
+    var("status", VarType::UInt8),
+    var("time_since_pickup", VarType::UInt8),
+    var("rating", VarType::UInt8),
+    var("last_speed", VarType::UInt8),
+    var("last_age", VarType::UInt8),
+    var("amount_fract", VarType::UInt8),
+    var("num_dests", VarType::UInt32),
+    var("cargo.reserved_count", VarType::UInt32),
+    var("link_graph", VarType::UInt16),
+    var("node", VarType::UInt16),
+    var("num_flows", VarType::UInt32),
+    var("max_waiting_cargo", VarType::UInt32),
+    Field::new("flow", FieldType::StructList(FLOW_SHARE, "num_flows")),
+    Field::new("cargo", FieldType::StructList(STATION_CARGO, "num_dests")),
+];
+
+const NORMAL_STATION: &[Field] = &[
+    Field::new("base", FieldType::Struct(BASE_STATION, 1)),
+    var("train_station.tile", VarType::UInt32),
+    var("train_station.w", VarType::UInt8),
+    var("train_station.h", VarType::UInt8),
+    var("bus_stops", VarType::UInt32),
+    var("truck_stops", VarType::UInt32),
+    var("ship_station.tile", VarType::UInt32),
+    var("ship_station.w", VarType::UInt8).since(SLV_MULTITILE_DOCKS),
+    var("ship_station.h", VarType::UInt8).since(SLV_MULTITILE_DOCKS),
+    var("docking_station.tile", VarType::UInt32).since(SLV_MULTITILE_DOCKS),
+    var("docking_station.w", VarType::UInt8).since(SLV_MULTITILE_DOCKS),
+    var("docking_station.h", VarType::UInt8).since(SLV_MULTITILE_DOCKS),
+    var("airport.tile", VarType::UInt32),
+    var("airport.w", VarType::UInt8),
+    var("airport.h", VarType::UInt8),
+    var("airport.type", VarType::UInt8),
+    var("airport.layout", VarType::UInt8),
+    var("airport.flags", VarType::UInt64),
+    var("airport.rotation", VarType::UInt8),
+    var("airport.psa", VarType::UInt32),
+    var("indtype", VarType::UInt8),
+    var("time_since_load", VarType::UInt8),
+    var("time_since_unload", VarType::UInt8),
+    var("last_vehicle_type", VarType::UInt8),
+    var("had_vehicle_of_type", VarType::UInt8),
+    Field::new("loading_vehicles", FieldType::RefList),
+    var("always_accepted", VarType::UInt32).until(SLV_EXTEND_CARGOTYPES),
+    var("always_accepted", VarType::UInt64).since(SLV_EXTEND_CARGOTYPES),
+    Field::new("goods", FieldType::Struct(GOODS_ENTRY, 32)).until(SLV_EXTEND_CARGOTYPES),
+    Field::new("goods", FieldType::Struct(GOODS_ENTRY, MAX_CARGO)).since(SLV_EXTEND_CARGOTYPES),
+];
+
+const WAYPOINT: &[Field] = &[
+    Field::new("base", FieldType::Struct(BASE_STATION, 1)),
+    var("town_cn", VarType::UInt16),
+    var("train_station.tile", VarType::UInt32),
+    var("train_station.w", VarType::UInt8),
+    var("train_station.h", VarType::UInt8),
+];
+
+const STATION_SPEC: &[Field] = &[
+    var("grfid", VarType::UInt32),
+    var("localidx", VarType::UInt8),
+];
+
+/// `STNN`, from version 183 when cargo started being routed through flows. Waypoints have a
+/// `waypoint` struct instead of the `normal` one.
+const STNN: ChunkSchema = ChunkSchema {
+    tag: *b"STNN",
+    since: 183,
+    fields: &[
+        var("facilities", VarType::UInt8),
+        Field::new("normal", FieldType::Struct(NORMAL_STATION, 1)).when(
+            "facilities",
+            FACIL_WAYPOINT as u64,
+            0,
+        ),
+        Field::new("waypoint", FieldType::Struct(WAYPOINT, 1)).when(
+            "facilities",
+            FACIL_WAYPOINT as u64,
+            FACIL_WAYPOINT as u64,
+        ),
+        Field::new("speclist", FieldType::StructList(STATION_SPEC, "num_specs")),
+    ],
+};
+
+const OLD_GOODS_ENTRY: &[Field] = &[
+    var("status", VarType::UInt8),
+    var("time_since_pickup", VarType::UInt8),
+    var("rating", VarType::UInt8),
+    var("last_speed", VarType::UInt8),
+    var("last_age", VarType::UInt8),
+    Field::new("cargo.packets", FieldType::RefList),
+];
+
+/// `STNS`, which held the stations before waypoints joined them in `STNN` in version 123. Only
+/// known from version 69, when references became 32 bit.
+const STNS: ChunkSchema = ChunkSchema {
+    tag: *b"STNS",
+    since: SLV_REF_32BIT,
+    fields: &[
+        var("xy", VarType::UInt32),
+        var("train_station.tile", VarType::UInt32),
+        var("airport.tile", VarType::UInt32),
+        var("ship_station.tile", VarType::UInt32),
+        var("town", VarType::UInt32),
+        var("train_station.w", VarType::UInt8),
+        var("train_station.h", VarType::UInt8),
+        var("string_id", VarType::StringId),
+        var("name", VarType::Str).since(84),
+        var("indtype", VarType::UInt8).since(103),
+        var("had_vehicle_of_type", VarType::UInt16).until(122),
+        var("had_vehicle_of_type", VarType::UInt8).since(122),
+        var("time_since_load", VarType::UInt8),
+        var("time_since_unload", VarType::UInt8),
+        var("delete_ctr", VarType::UInt8),
+        var("owner", VarType::UInt8),
+        var("facilities", VarType::UInt8),
+        var("airport.type", VarType::UInt8),
+        var("airport.flags", VarType::UInt64),
+        var("last_vehicle_type", VarType::UInt8),
+        var("build_date", VarType::Int32),
+        var("bus_stops", VarType::UInt32),
+        var("truck_stops", VarType::UInt32),
+        var("random_bits", VarType::UInt16),
+        var("waiting_triggers", VarType::UInt8),
+        var("num_specs", VarType::UInt8),
+        Field::new("loading_vehicles", FieldType::RefList),
+        // Reserved space
+        null(32),
+        Field::new("goods", FieldType::Struct(OLD_GOODS_ENTRY, 32)),
+        Field::new("speclist", FieldType::StructList(STATION_SPEC, "num_specs")),
+    ],
+};
+
+/// Every chunk with a known array layout
+pub const SCHEMAS: &[ChunkSchema] = &[
+    CITY, CAPA, NGRF, ORDR, ORDL, BKOR, PLYR, INDY, VEHS, STNN, STNS,
+];
+
+impl ChunkSchema {
+    /// The layout of a chunk in a savegame version, an error if it isn't known
+    pub fn find(tag: [u8; 4], version: u16) -> Result<&'static ChunkSchema> {
+        let name = String::from_utf8_lossy(&tag);
+        let schemas = SCHEMAS.iter().filter(|schema| schema.tag == tag);
+        let Some(oldest) = schemas.clone().map(|schema| schema.since).min() else {
+            return Err(Error::Malformed {
+                message: format!("{name} has no known array layout"),
+            });
+        };
+        schemas
+            .filter(|schema| version >= schema.since)
+            .max_by_key(|schema| schema.since)
+            .ok_or_else(|| Error::Malformed {
+                message: format!(
+                    "{name} in savegame version {version} isn't supported, only from version {oldest}"
+                ),
+            })
+    }
+
+    /// The fields saved in a version, with the JGR features of `slxi`
+    pub fn fields<'a>(
+        &self,
+        version: u16,
+        slxi: &'a SLXI,
+    ) -> impl Iterator<Item = &'static Field> + 'a {
+        self.fields
+            .iter()
+            .filter(move |field| field.exists(version, slxi))
+    }
+
+    /// Reads the fields at the start of an element, returning them with the number of bytes
+    /// they took. Anything after them is left for the caller, patch packs can add fields the
+    /// schema doesn't know about.
+    pub fn read(
+        &self,
+        data: &[u8],
+        version: u16,
+        slxi: &SLXI,
+    ) -> Result<(Vec<(String, TableData)>, usize)> {
+        let mut cursor = Cursor::new(data);
+        let row = read_fields(&mut cursor, self.fields, version, slxi)?;
+        Ok((row, cursor.position() as usize))
+    }
+
+    /// Like `read` but the element can't have anything after the fields
+    pub fn read_all(
+        &self,
+        data: &[u8],
+        version: u16,
+        slxi: &SLXI,
+    ) -> Result<Vec<(String, TableData)>> {
+        let (row, len) = self.read(data, version, slxi)?;
+        if len != data.len() {
+            return Err(Error::Malformed {
+                message: format!(
+                    "{} element is {} bytes but only {len} were expected, it may have fields from a patch pack",
+                    String::from_utf8_lossy(&self.tag),
+                    data.len(),
+                ),
+            });
+        }
+        Ok(row)
+    }
+
+    /// Writes the values of a row in the layout of a version. Every field saved in the version
+    /// has to be in the row, other keys are ignored.
+    pub fn write(&self, row: &[(String, TableData)], version: u16, slxi: &SLXI) -> Result<Vec<u8>> {
+        let mut cursor = Cursor::new(Vec::new());
+        write_fields(&mut cursor, row, self.fields, version, slxi, &[])?;
+        Ok(cursor.into_inner())
+    }
+
+    /// Replaces the values of a row with the fields of a type, like `table::update_table` does
+    /// for tables. Fields the version doesn't save are ignored, keys the schema doesn't have at
+    /// all are an error. Values are converted when the row is written.
+    pub fn update<T: Serialize + ?Sized>(
+        &self,
+        row: &mut [(String, TableData)],
+        value: &T,
+    ) -> Result<()> {
+        for (key, value) in to_row(value)? {
+            match field_mut(row, &key) {
+                Some(to) => *to = value,
+                None if self.fields.iter().any(|field| field.name == key) => {}
+                None => {
+                    return Err(Error::Malformed {
+                        message: format!(
+                            "{} has no field {key}",
+                            String::from_utf8_lossy(&self.tag)
+                        ),
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_fields(
+    cursor: &mut Cursor<&[u8]>,
+    fields: &[Field],
+    version: u16,
+    slxi: &SLXI,
+) -> Result<Vec<(String, TableData)>> {
+    let mut row = Vec::new();
+    for field in fields.iter().filter(|field| field.exists(version, slxi)) {
+        if !field.applies(&row)? {
+            continue;
+        }
+        let value = match field.field_type {
+            FieldType::Var(var_type) => read_var(cursor, var_type)?,
+            FieldType::Array(var_type, len) => read_list(cursor, field, var_type, len)?,
+            FieldType::List(var_type) => {
+                let len: u32 = cursor.read_be()?;
+                read_list(cursor, field, var_type, len as usize)?
+            }
+            FieldType::RefList => {
+                let len: u32 = cursor.read_be()?;
+                TableData::UInt32List(
+                    (0..len)
+                        .map(|_| cursor.read_be())
+                        .collect::<binrw::BinResult<_>>()?,
+                )
+            }
+            FieldType::Struct(fields, count) => TableData::Struct(TableStruct {
+                data: (0..count)
+                    .map(|_| read_fields(cursor, fields, version, slxi))
+                    .collect::<Result<_>>()?,
+            }),
+            FieldType::StructList(fields, key) => {
+                let count = find_int(&row, key).ok_or_else(|| Error::Malformed {
+                    message: format!("{} is counted by {key}, which isn't set", field.name),
+                })?;
+                TableData::Struct(TableStruct {
+                    data: (0..count)
+                        .map(|_| read_fields(cursor, fields, version, slxi))
+                        .collect::<Result<_>>()?,
+                })
+            }
+            FieldType::Null(len) => {
+                cursor.read_be_args::<Vec<u8>>(binrw::VecArgs::builder().count(len).finalize())?;
+                continue;
+            }
+            FieldType::AcceptanceMatrix => {
+                let (_tile, width, height): (u32, u16, u16) = cursor.read_be()?;
+                // One u32 per 4x4 tiles
+                let len = (width as usize / 4) * (height as usize / 4) * 4;
+                cursor.read_be_args::<Vec<u8>>(binrw::VecArgs::builder().count(len).finalize())?;
+                continue;
+            }
+        };
+        row.push((field.name.to_string(), value));
+    }
+    Ok(row)
+}
+
+fn read_var(cursor: &mut Cursor<&[u8]>, var_type: VarType) -> Result<TableData> {
+    Ok(match var_type {
+        VarType::Int8 => TableData::Int8(cursor.read_be()?),
+        VarType::UInt8 => TableData::UInt8(cursor.read_be()?),
+        VarType::Int16 => TableData::Int16(cursor.read_be()?),
+        VarType::UInt16 => TableData::UInt16(cursor.read_be()?),
+        VarType::Int32 => TableData::Int32(cursor.read_be()?),
+        VarType::UInt32 => TableData::UInt32(cursor.read_be()?),
+        VarType::Int64 => TableData::Int64(cursor.read_be()?),
+        VarType::UInt64 => TableData::UInt64(cursor.read_be()?),
+        VarType::StringId => TableData::StringId(cursor.read_be()?),
+        VarType::Str => TableData::Str(cursor.read_be::<TableString>()?.value),
+    })
+}
+
+fn read_list(
+    cursor: &mut Cursor<&[u8]>,
+    field: &Field,
+    var_type: VarType,
+    len: usize,
+) -> Result<TableData> {
+    let values = (0..len)
+        .map(|_| read_var(cursor, var_type)?.as_i64().ok_or_else(no_lists))
+        .collect::<Result<Vec<i64>>>()?;
+    coerce(
+        TableData::Int64List(values),
+        &TableHeaderProperty::new(field.name, var_type.list_type()),
+    )
+}
+
+/// The rows and fields of the structs a row is in, innermost last
+type Outer<'a> = [(&'a [(String, TableData)], &'static [Field])];
+
+fn write_fields(
+    cursor: &mut Cursor<Vec<u8>>,
+    row: &[(String, TableData)],
+    fields: &'static [Field],
+    version: u16,
+    slxi: &SLXI,
+    outer: &Outer,
+) -> Result<()> {
+    let mut levels = outer.to_vec();
+    levels.push((row, fields));
+
+    for field in fields.iter().filter(|field| field.exists(version, slxi)) {
+        if !field.applies(row)? {
+            continue;
+        }
+        let value = || {
+            crate::chtable::field(row, field.name).ok_or_else(|| Error::Malformed {
+                message: format!("row has no key {}", field.name),
+            })
+        };
+        match field.field_type {
+            FieldType::Var(var_type) => {
+                let value = match count_of(&levels, field.name, version, slxi)? {
+                    Some(count) => TableData::Int64(count as i64),
+                    None => value()?.clone(),
+                };
+                coerce(
+                    value,
+                    &TableHeaderProperty::new(field.name, var_type.table_type()),
+                )?
+                .write_be(cursor)?
+            }
+            FieldType::Array(var_type, len) => {
+                let values = value()?.as_i64_list().ok_or_else(|| mismatch(field))?;
+                if values.len() != len {
+                    return Err(Error::Malformed {
+                        message: format!("{} has to have {len} values", field.name),
+                    });
+                }
+                write_list(cursor, field, var_type, values)?;
+            }
+            FieldType::List(var_type) => {
+                let prop = TableHeaderProperty::new(field.name, var_type.list_type());
+                let values = coerce(value()?.clone(), &prop)?
+                    .as_i64_list()
+                    .ok_or_else(|| mismatch(field))?;
+                (values.len() as u32).write_be(cursor)?;
+                write_list(cursor, field, var_type, values)?;
+            }
+            FieldType::RefList => {
+                let prop = TableHeaderProperty::new(field.name, TableDataType::UInt32List);
+                let TableData::UInt32List(values) = coerce(value()?.clone(), &prop)? else {
+                    unreachable!()
+                };
+                (values.len() as u32).write_be(cursor)?;
+                values.write_be(cursor)?;
+            }
+            FieldType::Struct(fields, count) => match value()? {
+                TableData::Struct(TableStruct { data }) if data.len() == count => {
+                    data.iter().try_for_each(|row| {
+                        write_fields(cursor, row, fields, version, slxi, &levels)
+                    })?
+                }
+                _ => {
+                    return Err(Error::Malformed {
+                        message: format!("{} has to have {count} rows", field.name),
+                    })
+                }
+            },
+            FieldType::StructList(fields, _) => match value()? {
+                TableData::Struct(TableStruct { data }) => data.iter().try_for_each(|row| {
+                    write_fields(cursor, row, fields, version, slxi, &levels)
+                })?,
+                _ => return Err(mismatch(field)),
+            },
+            FieldType::Null(len) => vec![0u8; len].write_be(cursor)?,
+            FieldType::AcceptanceMatrix => [0u8; 8].write_be(cursor)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_list(
+    cursor: &mut Cursor<Vec<u8>>,
+    field: &Field,
+    var_type: VarType,
+    values: Vec<i64>,
+) -> Result<()> {
+    let prop = TableHeaderProperty::new(field.name, var_type.table_type());
+    for x in values {
+        coerce(TableData::Int64(x), &prop)?.write_be(cursor)?;
+    }
+    Ok(())
+}
+
+/// The number of rows of the struct list counted by `key`, if a row being written or one it's
+/// in has one
+fn count_of(levels: &Outer, key: &str, version: u16, slxi: &SLXI) -> Result<Option<usize>> {
+    for &(row, fields) in levels.iter().rev() {
+        let list = fields.iter().find(|field| {
+            field.exists(version, slxi)
+                && matches!(field.field_type, FieldType::StructList(_, count) if count == key)
+        });
+        if let Some(list) = list {
+            return match crate::chtable::field(row, list.name) {
+                Some(TableData::Struct(TableStruct { data })) => Ok(Some(data.len())),
+                _ => Err(Error::Malformed {
+                    message: format!("row has no key {}", list.name),
+                }),
+            };
+        }
+    }
+    Ok(None)
+}
+
+/// An integer in a row or in the single row of a struct in it, depth first
+fn find_int(row: &[(String, TableData)], key: &str) -> Option<i64> {
+    row.iter().find_map(|(k, value)| match value {
+        _ if k == key => value.as_i64(),
+        TableData::Struct(TableStruct { data }) if data.len() == 1 => find_int(&data[0], key),
+        _ => None,
+    })
+}
+
+fn no_lists() -> Error {
+    Error::Malformed {
+        message: "arrays of strings aren't supported".to_string(),
+    }
+}
+
+fn mismatch(field: &Field) -> Error {
+    Error::Malformed {
+        message: format!("{} isn't a list", field.name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::chtable::{field, TableData};
+    use crate::error::Result;
+    use crate::jgr::{KNOWN_FEATURES, SLXI};
+    use crate::save::{ChunkValue, Save};
+    use crate::schema::{ChunkSchema, Field, FieldType, SCHEMAS};
+
+    #[test]
+    fn features_are_known() {
+        fn features(fields: &'static [Field]) -> Vec<&'static str> {
+            fields
+                .iter()
+                .flat_map(|field| {
+                    let nested = match field.field_type {
+                        FieldType::Struct(fields, _) | FieldType::StructList(fields, _) => {
+                            features(fields)
+                        }
+                        _ => vec![],
+                    };
+                    field.feature.into_iter().chain(nested)
+                })
+                .collect()
+        }
+
+        // The JGR fields the registry reads are the ones `SLXI::unsupported` counts as known
+        for feature in SCHEMAS.iter().flat_map(|schema| features(schema.fields)) {
+            assert!(
+                KNOWN_FEATURES.iter().any(|known| known.name == feature),
+                "{feature} isn't known"
+            );
+        }
+    }
+
+    #[test]
+    fn town_layouts() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let Some(ChunkValue::ChArray { elements }) = save.get(b"CITY") else {
+            panic!("CITY isn't an array");
+        };
+        let slxi = SLXI::default();
+        let schema = ChunkSchema::find(*b"CITY", save.version)?;
+        let row = schema.read_all(&elements[0].data, save.version, &slxi)?;
+        assert_eq!(
+            field(&row, "townnameparts").and_then(TableData::as_i64),
+            Some(0x620ae2d)
+        );
+        assert_eq!(schema.write(&row, save.version, &slxi)?, elements[0].data);
+
+        // Versions with the cargo cache have 38 more bytes and an empty acceptance matrix
+        let old = schema.write(&row, 200, &slxi)?;
+        assert_eq!(old.len(), elements[0].data.len() + 38 + 8);
+        let old_row = schema.read_all(&old, 200, &slxi)?;
+        assert_eq!(
+            schema.write(&old_row, save.version, &slxi)?,
+            elements[0].data
+        );
+
+        // Before there were 64 cargo types
+        assert!(schema.write(&row, 167, &slxi).is_err());
+        assert_eq!(
+            ChunkSchema::find(*b"CITY", 100).unwrap_err().to_string(),
+            "CITY in savegame version 100 isn't supported, only from version 165"
+        );
+        assert!(ChunkSchema::find(*b"MAPS", 300).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn cargo_packet_layouts() -> Result<()> {
+        let slxi = SLXI::default();
+        let schema = ChunkSchema::find(*b"CAPA", 100)?;
+        // Version 100 still has the unused paid_for byte and no source type
+        let data = [
+            0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 4, 5, 0, 0, 0, 0, 0, 0, 0, 6, 0,
+        ];
+        let row = schema.read_all(&data, 100, &slxi)?;
+        let keys: Vec<&str> = row.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "source",
+                "source_xy",
+                "loaded_at_xy",
+                "count",
+                "days_in_transit",
+                "feeder_share"
+            ]
+        );
+        assert_eq!(schema.write(&row, 100, &slxi)?, data);
+        assert!(schema.read_all(&data, 125, &slxi).is_err());
+        Ok(())
+    }
+}
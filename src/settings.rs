@@ -0,0 +1,503 @@
+use serde::{Deserialize, Serialize};
+
+use crate::chtable::{field_mut, TableData};
+use crate::error::{Error, Result};
+use crate::save::{Chunk, ChunkValue, LazySave, Save};
+
+/// The value of a game setting. Booleans are stored as integers, so they're only told apart
+/// for settings in `KNOWN_SETTINGS`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SettingValue {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+}
+
+/// The values a known setting can have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingKind {
+    Bool,
+    Int { min: i64, max: i64 },
+    Str,
+}
+
+/// A setting OpenTTD saves, and the savegame versions it's saved with this range in.
+/// `until` is the first version that doesn't use it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SettingInfo {
+    pub name: &'static str,
+    pub kind: SettingKind,
+    pub since: u16,
+    pub until: u16,
+}
+
+const fn boolean(name: &'static str) -> SettingInfo {
+    SettingInfo {
+        name,
+        kind: SettingKind::Bool,
+        since: 0,
+        until: u16::MAX,
+    }
+}
+
+const fn int(name: &'static str, min: i64, max: i64) -> SettingInfo {
+    SettingInfo {
+        name,
+        kind: SettingKind::Int { min, max },
+        since: 0,
+        until: u16::MAX,
+    }
+}
+
+const fn string(name: &'static str) -> SettingInfo {
+    SettingInfo {
+        name,
+        kind: SettingKind::Str,
+        since: 0,
+        until: u16::MAX,
+    }
+}
+
+const fn since(info: SettingInfo, version: u16) -> SettingInfo {
+    SettingInfo {
+        since: version,
+        ..info
+    }
+}
+
+const fn until(info: SettingInfo, version: u16) -> SettingInfo {
+    SettingInfo {
+        until: version,
+        ..info
+    }
+}
+
+/// Ranges of the commonly changed settings from OpenTTD's settings tables. Settings that aren't
+/// listed can still be read and changed, they just aren't checked.
+pub const KNOWN_SETTINGS: &[SettingInfo] = &[
+    int("difficulty.max_no_competitors", 0, 14),
+    int("difficulty.number_towns", 0, 4),
+    int("difficulty.industry_density", 0, 5),
+    int("difficulty.max_loan", 0, 2_000_000_000),
+    int("difficulty.initial_interest", 2, 4),
+    int("difficulty.vehicle_costs", 0, 2),
+    int("difficulty.competitor_speed", 0, 4),
+    int("difficulty.vehicle_breakdowns", 0, 2),
+    int("difficulty.subsidy_multiplier", 0, 3),
+    since(int("difficulty.subsidy_duration", 1, 5000), 292),
+    int("difficulty.construction_cost", 0, 2),
+    int("difficulty.terrain_type", 0, 5),
+    int("difficulty.quantity_sea_lakes", 0, 4),
+    boolean("difficulty.economy"),
+    boolean("difficulty.line_reverse_mode"),
+    boolean("difficulty.disasters"),
+    int("difficulty.town_council_tolerance", 0, 3),
+    int("economy.town_layout", 0, 4),
+    boolean("economy.allow_town_roads"),
+    int("economy.found_town", 0, 2),
+    boolean("economy.allow_town_level_crossings"),
+    since(int("economy.town_cargogen_mode", 0, 1), 208),
+    boolean("economy.station_noise_level"),
+    boolean("economy.inflation"),
+    boolean("economy.multiple_industry_per_town"),
+    boolean("economy.bribe"),
+    boolean("economy.exclusive_rights"),
+    boolean("economy.fund_buildings"),
+    boolean("economy.fund_roads"),
+    boolean("economy.give_money"),
+    int("economy.type", 0, 2),
+    boolean("economy.allow_shares"),
+    int("economy.feeder_payment_share", 0, 100),
+    int("economy.town_growth_rate", 0, 4),
+    int("economy.larger_towns", 0, 255),
+    int("economy.initial_city_size", 1, 10),
+    boolean("economy.mod_road_rebuild"),
+    int("economy.dist_local_authority", 5, 60),
+    boolean("economy.infrastructure_maintenance"),
+    boolean("order.no_servicing_if_no_breakdowns"),
+    boolean("order.improved_load"),
+    boolean("order.selectgoods"),
+    boolean("order.serviceathelipad"),
+    boolean("order.gradual_loading"),
+    boolean("station.never_expire_airports"),
+    int("station.station_spread", 4, 64),
+    boolean("station.modified_catchment"),
+    boolean("station.serve_neutral_industries"),
+    boolean("station.adjacent_stations"),
+    boolean("station.distant_join_stations"),
+    int("vehicle.road_side", 0, 1),
+    int("vehicle.train_acceleration_model", 0, 1),
+    int("vehicle.roadveh_acceleration_model", 0, 1),
+    int("vehicle.train_slope_steepness", 0, 10),
+    int("vehicle.roadveh_slope_steepness", 0, 10),
+    int("vehicle.max_train_length", 1, 64),
+    int("vehicle.smoke_amount", 0, 2),
+    boolean("vehicle.never_expire_vehicles"),
+    int("vehicle.max_trains", 0, 5000),
+    int("vehicle.max_roadveh", 0, 5000),
+    int("vehicle.max_aircraft", 0, 5000),
+    int("vehicle.max_ships", 0, 5000),
+    boolean("vehicle.wagon_speed_limits"),
+    boolean("vehicle.disable_elrails"),
+    int("vehicle.freight_trains", 1, 255),
+    int("vehicle.plane_speed", 1, 4),
+    boolean("vehicle.dynamic_engines"),
+    int("vehicle.plane_crashes", 0, 2),
+    int("vehicle.extend_vehicle_life", 0, 100),
+    // Counted in days until they became seconds
+    until(int("linkgraph.recalc_interval", 4, 90), 308),
+    until(int("linkgraph.recalc_time", 1, 9000), 308),
+    int("linkgraph.distribution_pax", 0, 2),
+    int("linkgraph.distribution_mail", 0, 2),
+    int("linkgraph.distribution_armoured", 0, 2),
+    int("linkgraph.distribution_default", 0, 2),
+    int("linkgraph.accuracy", 2, 64),
+    int("linkgraph.demand_distance", 0, 500),
+    int("linkgraph.demand_size", 0, 100),
+    int("linkgraph.short_path_saturation", 50, 250),
+    string("locale.digit_group_separator"),
+    string("locale.digit_group_separator_currency"),
+    string("locale.digit_decimal_separator"),
+    boolean("pf.forbid_90_deg"),
+    boolean("pf.roadveh_queue"),
+    int("pf.pathfinder_for_trains", 1, 2),
+    int("pf.pathfinder_for_roadvehs", 1, 2),
+    int("pf.pathfinder_for_ships", 1, 2),
+    boolean("pf.reverse_at_signals"),
+    boolean("pf.reserve_paths"),
+    boolean("ai.ai_in_multiplayer"),
+    boolean("ai.ai_disable_veh_train"),
+    boolean("ai.ai_disable_veh_roadveh"),
+    boolean("ai.ai_disable_veh_aircraft"),
+    boolean("ai.ai_disable_veh_ship"),
+    int("game_creation.landscape", 0, 3),
+    since(int("game_creation.snow_coverage", 0, 100), 290),
+    since(int("game_creation.desert_coverage", 0, 100), 290),
+    int("game_creation.starting_year", 0, 5_000_000),
+    int("game_creation.ending_year", 0, 5_000_000),
+    int("game_creation.land_generator", 0, 1),
+    int("game_creation.oil_refinery_limit", 12, 128),
+    int("game_creation.tgen_smoothness", 0, 3),
+    int("game_creation.variety", 0, 5),
+    int("game_creation.tree_placer", 0, 2),
+    int("game_creation.water_borders", 0, 16),
+    int("game_creation.amount_of_rivers", 0, 3),
+    since(int("construction.map_height_limit", 15, 255), 194),
+    boolean("construction.build_on_slopes"),
+    int("construction.command_pause_level", 0, 3),
+    boolean("construction.autoslope"),
+    boolean("construction.extra_dynamite"),
+    int("construction.max_bridge_length", 1, 2048),
+    since(int("construction.max_bridge_height", 1, 255), 194),
+    int("construction.max_tunnel_length", 1, 2048),
+    int("construction.train_signal_side", 0, 1),
+    boolean("construction.road_stop_on_town_road"),
+    boolean("construction.road_stop_on_competitor_road"),
+    int("construction.raw_industry_construction", 0, 2),
+    int("construction.industry_platform", 0, 4),
+    boolean("construction.freeform_edges"),
+    int("construction.extra_tree_placement", 0, 3),
+];
+
+impl SettingInfo {
+    /// The entry of `KNOWN_SETTINGS` for a setting in a savegame version
+    pub fn find(name: &str, version: u16) -> Option<&'static SettingInfo> {
+        KNOWN_SETTINGS
+            .iter()
+            .find(|info| info.name == name && (info.since..info.until).contains(&version))
+    }
+
+    /// Checks a value has the right type and is in range
+    pub fn check(&self, value: &SettingValue) -> Result<()> {
+        let valid = match (self.kind, value) {
+            (SettingKind::Bool, SettingValue::Bool(_)) => true,
+            (SettingKind::Int { min, max }, SettingValue::Int(x)) => (min..=max).contains(x),
+            (SettingKind::Str, SettingValue::Str(_)) => true,
+            _ => false,
+        };
+        match valid {
+            true => Ok(()),
+            false => Err(Error::Malformed {
+                message: format!("{value:?} isn't a valid value for {}", self.name),
+            }),
+        }
+    }
+}
+
+/// Settings are saved with their names from this version, when chunks became tables
+pub const SLV_TABLE_CHUNKS: u16 = 293;
+
+/// The game settings from the `PATS` chunk, keyed by name like `difficulty.max_loan`.
+/// Only saves since [`SLV_TABLE_CHUNKS`] are supported. Older saves store `PATS` without the
+/// names, in an order that changes with each version, and the oldest keep the difficulty
+/// settings in `OPTS`. Both give [`Error::UnsupportedVersion`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Settings {
+    /// Savegame version the settings are checked against, without JGR's flag
+    pub version: u16,
+    /// Settings in the order they're saved in. Lists and other types that OpenTTD doesn't use
+    /// for settings are left out, and kept as they are when writing.
+    values: Vec<(String, SettingValue)>,
+}
+
+impl Settings {
+    pub fn from_save(save: &Save) -> Result<Settings> {
+        let version = save.base_version();
+        let chunk = save
+            .get(b"PATS")
+            .ok_or_else(|| missing_settings(save.get(b"OPTS").is_some(), version))?;
+        Settings::from_chunk(chunk, version)
+    }
+
+    pub fn from_lazy_save(save: &LazySave) -> Result<Settings> {
+        let version = save.base_version();
+        let chunk = save
+            .get(b"PATS")
+            .ok_or_else(|| missing_settings(save.get(b"OPTS").is_some(), version))?
+            .decode()?;
+        Settings::from_chunk(&chunk.value, version)
+    }
+
+    /// Reads the contents of the `PATS` chunk of a save with the given version
+    pub fn from_chunk(chunk: &ChunkValue, version: u16) -> Result<Settings> {
+        let data = match chunk {
+            ChunkValue::ChTable { elements, .. } => elements.first().map(|e| &e.data),
+            ChunkValue::ChRiff { .. } => return Err(unsupported_version(*b"PATS", version)),
+            _ => {
+                return Err(Error::BadChunkType {
+                    chunk_type: chunk.chunk_type(),
+                })
+            }
+        };
+        let values = data
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, data)| {
+                let value = match (SettingInfo::find(name, version), data) {
+                    (_, TableData::Str(x)) => SettingValue::Str(x.clone()),
+                    (Some(info), data) if info.kind == SettingKind::Bool => {
+                        SettingValue::Bool(data.as_i64()? != 0)
+                    }
+                    (_, data) => SettingValue::Int(data.as_i64()?),
+                };
+                Some((name.clone(), value))
+            })
+            .collect();
+        Ok(Settings { version, values })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SettingValue> {
+        self.values.iter().find(|(k, _)| k == name).map(|(_, v)| v)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &SettingValue)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Changes a setting the save has. Known settings have to be in range, and any setting has
+    /// to keep its type.
+    pub fn set(&mut self, name: &str, value: SettingValue) -> Result<()> {
+        if let Some(info) = SettingInfo::find(name, self.version) {
+            info.check(&value)?;
+        }
+        let current = self
+            .values
+            .iter_mut()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v)
+            .ok_or_else(|| Error::Malformed {
+                message: format!("the save has no setting {name}"),
+            })?;
+        if std::mem::discriminant(current) != std::mem::discriminant(&value) {
+            return Err(Error::Malformed {
+                message: format!("{value:?} has the wrong type for {name}"),
+            });
+        }
+        *current = value;
+        Ok(())
+    }
+
+    /// The known settings whose values are out of range for the save's version
+    pub fn invalid(&self) -> Vec<&str> {
+        self.values
+            .iter()
+            .filter(|(name, value)| {
+                SettingInfo::find(name, self.version).is_some_and(|info| info.check(value).is_err())
+            })
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    pub fn write_to_save(&self, save: &mut Save) -> Result<()> {
+        let mut chunk = save
+            .get(b"PATS")
+            .ok_or(Error::MissingChunk { tag: *b"PATS" })?
+            .clone();
+        self.write_to_chunk(&mut chunk)?;
+        save.set(Chunk {
+            tag: *b"PATS",
+            value: chunk,
+        });
+        Ok(())
+    }
+
+    pub fn write_to_lazy_save(&self, save: &mut LazySave) -> Result<()> {
+        let mut chunk = save
+            .get(b"PATS")
+            .ok_or(Error::MissingChunk { tag: *b"PATS" })?
+            .decode()?;
+        self.write_to_chunk(&mut chunk.value)?;
+        save.set(&chunk)
+    }
+
+    /// Writes the values back into the contents of the `PATS` chunk, keeping the type each
+    /// setting is stored with. Integers that don't fit their type are an error.
+    pub fn write_to_chunk(&self, chunk: &mut ChunkValue) -> Result<()> {
+        let data = match chunk {
+            ChunkValue::ChTable { elements, .. } => elements
+                .first_mut()
+                .map(|e| &mut e.data)
+                .ok_or_else(|| Error::Malformed {
+                    message: "there are no settings to write to".to_string(),
+                })?,
+            ChunkValue::ChRiff { .. } => return Err(unsupported_version(*b"PATS", self.version)),
+            _ => {
+                return Err(Error::BadChunkType {
+                    chunk_type: chunk.chunk_type(),
+                })
+            }
+        };
+        self.values.iter().try_for_each(|(name, value)| {
+            let data = field_mut(data, name).ok_or_else(|| Error::Malformed {
+                message: format!("there is no setting {name} to write to"),
+            })?;
+            let fits = match value {
+                SettingValue::Str(x) => {
+                    *data = TableData::Str(x.clone());
+                    true
+                }
+                SettingValue::Bool(x) => data.set_int(*x as i64),
+                SettingValue::Int(x) => data.set_int(*x) && data.as_i64() == Some(*x),
+            };
+            match fits {
+                true => Ok(()),
+                false => Err(Error::Malformed {
+                    message: format!("{value:?} doesn't fit in the type of {name}"),
+                }),
+            }
+        })
+    }
+}
+
+fn unsupported_version(tag: [u8; 4], version: u16) -> Error {
+    Error::UnsupportedVersion {
+        tag,
+        version,
+        since: SLV_TABLE_CHUNKS,
+    }
+}
+
+/// The error for a save without `PATS`, which only the oldest saves with `OPTS` are
+fn missing_settings(has_opts: bool, version: u16) -> Error {
+    match has_opts {
+        true => unsupported_version(*b"OPTS", version),
+        false => Error::MissingChunk { tag: *b"PATS" },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::error::{Error, Result};
+    use crate::save::{Chunk, ChunkValue, Save};
+    use crate::settings::{SettingValue, Settings};
+
+    #[test]
+    fn read_settings() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let settings = Settings::from_save(&save)?;
+        assert_eq!(
+            settings.get("difficulty.max_loan"),
+            Some(&SettingValue::Int(300000))
+        );
+        assert_eq!(
+            settings.get("economy.inflation"),
+            Some(&SettingValue::Bool(false))
+        );
+        assert_eq!(
+            settings.get("locale.digit_group_separator"),
+            Some(&SettingValue::Str(String::new()))
+        );
+        assert_eq!(
+            settings.get("pf.yapf.rail_look_ahead_signal_p1"),
+            Some(&SettingValue::Int(-100))
+        );
+        assert!(settings.invalid().is_empty());
+
+        // Older saves don't have the names, so they can't be read or written
+        let mut save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let message = format!(
+            "PATS in savegame version {} isn't supported, only from version 293",
+            save.version
+        );
+        assert_eq!(Settings::from_save(&save).unwrap_err().to_string(), message);
+        let mut chunk = save.get(b"PATS").unwrap().clone();
+        assert!(matches!(
+            settings.write_to_chunk(&mut chunk),
+            Err(Error::UnsupportedVersion { .. })
+        ));
+
+        // And the oldest keep some of them in OPTS instead
+        save.chunks.retain(|chunk| chunk.tag != *b"PATS");
+        save.set(Chunk {
+            tag: *b"OPTS",
+            value: ChunkValue::ChRiff { data: vec![0; 4] },
+        });
+        assert!(matches!(
+            Settings::from_save(&save),
+            Err(Error::UnsupportedVersion { tag, .. }) if tag == *b"OPTS"
+        ));
+        save.chunks.retain(|chunk| chunk.tag != *b"OPTS");
+        assert!(matches!(
+            Settings::from_save(&save),
+            Err(Error::MissingChunk { tag }) if tag == *b"PATS"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_settings() -> Result<()> {
+        let mut save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let mut settings = Settings::from_save(&save)?;
+
+        // Out of range, the wrong type and unknown names are rejected
+        assert!(settings
+            .set("station.station_spread", SettingValue::Int(100))
+            .is_err());
+        assert!(settings
+            .set("economy.inflation", SettingValue::Int(1))
+            .is_err());
+        assert!(settings
+            .set("economy.no_such_setting", SettingValue::Bool(true))
+            .is_err());
+
+        settings.set("difficulty.max_loan", SettingValue::Int(1_000_000))?;
+        settings.set("economy.inflation", SettingValue::Bool(true))?;
+        settings.set("station.station_spread", SettingValue::Int(64))?;
+        settings.write_to_save(&mut save)?;
+
+        let mut buffer = Vec::new();
+        save.to_writer(&mut std::io::Cursor::new(&mut buffer))?;
+        let save = Save::from_reader(&mut std::io::Cursor::new(buffer))?;
+        assert_eq!(Settings::from_save(&save)?, settings);
+
+        // Unknown settings are only checked against the type they're stored with
+        settings.set("pf.wait_oneway_signal", SettingValue::Int(300))?;
+        assert!(settings.write_to_save(&mut save.clone()).is_err());
+
+        Ok(())
+    }
+}
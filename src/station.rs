@@ -0,0 +1,763 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cargo::CargoPacket;
+use crate::chtable::{ChTableElement, TableHeaderProperty};
+use crate::error::{Error, Result};
+use crate::industry::INVALID_INDUSTRY_TYPE;
+use crate::jgr::SLXI;
+use crate::save::{ChArrayElement, Chunk, ChunkValue, LazySave, Save};
+use crate::schema::ChunkSchema;
+use crate::table::{from_table, to_row, update_table};
+
+/// Bits of [`Station::facilities`]
+pub const FACIL_TRAIN: u8 = 0x01;
+pub const FACIL_TRUCK_STOP: u8 = 0x02;
+pub const FACIL_BUS_STOP: u8 = 0x04;
+pub const FACIL_AIRPORT: u8 = 0x08;
+pub const FACIL_DOCK: u8 = 0x10;
+pub const FACIL_WAYPOINT: u8 = 0x80;
+
+/// Bits of [`GoodsEntry::status`]
+pub const GES_ACCEPTANCE: u8 = 0x01;
+pub const GES_RATING: u8 = 0x02;
+
+/// Station index used for cargo that can go to any next hop
+pub const INVALID_STATION: u16 = 0xFFFF;
+/// `link_graph` and `node` of cargo that isn't in a link graph
+pub const INVALID_LINK_GRAPH: u16 = 0xFFFF;
+pub const INVALID_NODE: u16 = 0xFFFF;
+
+/// A station or waypoint from the `STNN` chunk, read from either the array layout of savegame
+/// versions 183 to 292 or the table layout of OpenTTD 13 and later. Stations of saves from
+/// version 69 to 122 are read from the legacy `STNS` chunk, see [`Station::from_legacy_chunk`].
+///
+/// Only one of `normal` and `waypoint` is set, depending on [`FACIL_WAYPOINT`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Station {
+    pub facilities: u8,
+    pub normal: Option<NormalStation>,
+    pub waypoint: Option<Waypoint>,
+    /// Custom station types from NewGRFs used by the station
+    pub speclist: Vec<StationSpec>,
+}
+
+/// Fields stations and waypoints share
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaseStation {
+    /// Tile the sign is above
+    pub xy: u32,
+    /// Town the station is named after
+    pub town: u32,
+    /// Generated name, used when `name` is empty
+    pub string_id: u16,
+    pub name: String,
+    pub delete_ctr: u8,
+    pub owner: u8,
+    pub facilities: u8,
+    pub build_date: i32,
+    pub random_bits: u16,
+    pub waiting_triggers: u8,
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NormalStation {
+    pub base: BaseStation,
+    #[serde(rename = "train_station.tile")]
+    pub train_station_tile: u32,
+    #[serde(rename = "train_station.w")]
+    pub train_station_w: u8,
+    #[serde(rename = "train_station.h")]
+    pub train_station_h: u8,
+    /// First road stop in the `ROAD` chunk, as a reference
+    pub bus_stops: u32,
+    pub truck_stops: u32,
+    #[serde(rename = "ship_station.tile")]
+    pub ship_station_tile: u32,
+    /// Size of the dock, 0 before savegame version 216 when docks were a single tile
+    #[serde(rename = "ship_station.w", default)]
+    pub ship_station_w: u8,
+    #[serde(rename = "ship_station.h", default)]
+    pub ship_station_h: u8,
+    /// Area ships can dock in, 0 before savegame version 216
+    #[serde(rename = "docking_station.tile", default)]
+    pub docking_station_tile: u32,
+    #[serde(rename = "docking_station.w", default)]
+    pub docking_station_w: u8,
+    #[serde(rename = "docking_station.h", default)]
+    pub docking_station_h: u8,
+    #[serde(rename = "airport.tile")]
+    pub airport_tile: u32,
+    #[serde(rename = "airport.w")]
+    pub airport_w: u8,
+    #[serde(rename = "airport.h")]
+    pub airport_h: u8,
+    #[serde(rename = "airport.type")]
+    pub airport_type: u8,
+    #[serde(rename = "airport.layout")]
+    pub airport_layout: u8,
+    #[serde(rename = "airport.flags")]
+    pub airport_flags: u64,
+    #[serde(rename = "airport.rotation")]
+    pub airport_rotation: u8,
+    #[serde(rename = "airport.psa")]
+    pub airport_psa: u32,
+    pub indtype: u8,
+    pub time_since_load: u8,
+    pub time_since_unload: u8,
+    pub last_vehicle_type: u8,
+    pub had_vehicle_of_type: u8,
+    /// Vehicles loading at the station, as references
+    pub loading_vehicles: Vec<u32>,
+    /// Cargo types accepted regardless of the surrounding tiles, as a bit set
+    pub always_accepted: u64,
+    /// One entry per cargo type, for 32 types before savegame version 199 and 64 since
+    pub goods: Vec<GoodsEntry>,
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Waypoint {
+    pub base: BaseStation,
+    /// Number of the waypoint within its town, for the generated name
+    pub town_cn: u16,
+    #[serde(rename = "train_station.tile")]
+    pub train_station_tile: u32,
+    #[serde(rename = "train_station.w")]
+    pub train_station_w: u8,
+    #[serde(rename = "train_station.h")]
+    pub train_station_h: u8,
+}
+
+/// The state of one cargo type at a station
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GoodsEntry {
+    pub status: u8,
+    pub time_since_pickup: u8,
+    /// 0 to 255, shown in game as a percentage
+    pub rating: u8,
+    pub last_speed: u8,
+    pub last_age: u8,
+    pub amount_fract: u8,
+    #[serde(rename = "cargo.reserved_count")]
+    pub reserved_count: u32,
+    pub link_graph: u16,
+    pub node: u16,
+    pub max_waiting_cargo: u32,
+    #[serde(rename = "flow")]
+    pub flows: Vec<FlowShare>,
+    /// Cargo waiting at the station, grouped by the station it goes to next
+    pub cargo: Vec<StationCargo>,
+}
+
+/// A share of the cargo from `source` that is routed `via` the next station
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlowShare {
+    pub source: u16,
+    pub via: u16,
+    pub share: u32,
+    /// Only vehicles without a next hop may take the cargo, false before savegame version 187
+    #[serde(default)]
+    pub restricted: bool,
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StationCargo {
+    /// Station the cargo goes to next, [`INVALID_STATION`] for any
+    #[serde(rename = "first")]
+    pub next_hop: u16,
+    /// Cargo packets in the `CAPA` chunk, as references
+    #[serde(rename = "second")]
+    pub packets: Vec<u32>,
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StationSpec {
+    pub grfid: u32,
+    pub localidx: u8,
+}
+
+/// A station in the legacy `STNS` layout, with the keys of `STNN` where they match
+#[derive(Debug, Deserialize)]
+struct LegacyStation {
+    xy: u32,
+    #[serde(rename = "train_station.tile")]
+    train_station_tile: u32,
+    #[serde(rename = "airport.tile")]
+    airport_tile: u32,
+    #[serde(rename = "ship_station.tile")]
+    ship_station_tile: u32,
+    town: u32,
+    #[serde(rename = "train_station.w")]
+    train_station_w: u8,
+    #[serde(rename = "train_station.h")]
+    train_station_h: u8,
+    string_id: u16,
+    #[serde(default)]
+    name: String,
+    /// Industry the station belongs to, none before savegame version 103
+    #[serde(default = "no_industry")]
+    indtype: u8,
+    had_vehicle_of_type: u8,
+    time_since_load: u8,
+    time_since_unload: u8,
+    delete_ctr: u8,
+    owner: u8,
+    facilities: u8,
+    #[serde(rename = "airport.type")]
+    airport_type: u8,
+    #[serde(rename = "airport.flags")]
+    airport_flags: u64,
+    last_vehicle_type: u8,
+    build_date: i32,
+    bus_stops: u32,
+    truck_stops: u32,
+    random_bits: u16,
+    waiting_triggers: u8,
+    loading_vehicles: Vec<u32>,
+    goods: Vec<LegacyGoodsEntry>,
+    speclist: Vec<StationSpec>,
+}
+
+fn no_industry() -> u8 {
+    INVALID_INDUSTRY_TYPE
+}
+
+#[derive(Debug, Deserialize)]
+struct LegacyGoodsEntry {
+    status: u8,
+    time_since_pickup: u8,
+    rating: u8,
+    last_speed: u8,
+    last_age: u8,
+    /// Every packet waiting, before cargo had a next hop
+    #[serde(rename = "cargo.packets")]
+    packets: Vec<u32>,
+}
+
+impl Station {
+    pub fn is_waypoint(&self) -> bool {
+        self.facilities & FACIL_WAYPOINT != 0
+    }
+
+    pub fn base(&self) -> Option<&BaseStation> {
+        match (&self.normal, &self.waypoint) {
+            (Some(normal), _) => Some(&normal.base),
+            (_, Some(waypoint)) => Some(&waypoint.base),
+            _ => None,
+        }
+    }
+
+    pub fn base_mut(&mut self) -> Option<&mut BaseStation> {
+        match (&mut self.normal, &mut self.waypoint) {
+            (Some(normal), _) => Some(&mut normal.base),
+            (_, Some(waypoint)) => Some(&mut waypoint.base),
+            _ => None,
+        }
+    }
+
+    /// The name the player gave the station, None if it uses its generated name
+    pub fn name(&self) -> Option<&str> {
+        self.base()
+            .map(|base| base.name.as_str())
+            .filter(|name| !name.is_empty())
+    }
+
+    /// The x and y of the tile the sign is above
+    pub fn sign_position(&self, dim_x: u32) -> Option<(u32, u32)> {
+        self.base().map(|base| (base.xy % dim_x, base.xy / dim_x))
+    }
+
+    /// The goods entries by cargo type, empty for waypoints
+    pub fn goods(&self) -> &[GoodsEntry] {
+        self.normal.as_ref().map_or(&[], |normal| &normal.goods)
+    }
+
+    /// Reads the stations of `STNN`, or of the legacy `STNS` chunk in saves before version 123
+    pub fn from_save(save: &Save) -> Result<BTreeMap<u32, Station>> {
+        match (save.get(b"STNN"), save.get(b"STNS")) {
+            (Some(chunk), _) => Station::from_chunk(chunk, save.base_version()),
+            (None, Some(legacy)) => Station::from_legacy_chunk(legacy, save.base_version()),
+            (None, None) => Err(Error::MissingChunk { tag: *b"STNN" }),
+        }
+    }
+
+    pub fn from_lazy_save(save: &LazySave) -> Result<BTreeMap<u32, Station>> {
+        match (save.get(b"STNN"), save.get(b"STNS")) {
+            (Some(chunk), _) => Station::from_chunk(&chunk.decode()?.value, save.base_version()),
+            (None, Some(legacy)) => {
+                Station::from_legacy_chunk(&legacy.decode()?.value, save.base_version())
+            }
+            (None, None) => Err(Error::MissingChunk { tag: *b"STNN" }),
+        }
+    }
+
+    /// Parses every station and waypoint in the contents of the `STNN` chunk, by their index
+    /// in the pool
+    pub fn from_chunk(chunk: &ChunkValue, version: u16) -> Result<BTreeMap<u32, Station>> {
+        let stations = match chunk {
+            ChunkValue::ChArray { elements } => {
+                ChunkSchema::find(*b"STNN", version).and_then(|schema| {
+                    elements
+                        .iter()
+                        .enumerate()
+                        // Empty elements are gaps in the pool
+                        .filter(|(_, element)| !element.data.is_empty())
+                        .map(|(index, element)| {
+                            let row = schema.read_all(&element.data, version, &SLXI::default())?;
+                            Ok((index as u32, from_table(&row)?))
+                        })
+                        .collect()
+                })
+            }
+            ChunkValue::ChTable { elements, .. } => elements
+                .iter()
+                .enumerate()
+                .filter(|(_, element)| !element.data.is_empty())
+                .map(|(index, element)| Ok((index as u32, from_table(&element.data)?)))
+                .collect(),
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        stations.map_err(|e| e.in_chunk(*b"STNN", 0))
+    }
+
+    /// Parses the stations in the contents of the legacy `STNS` chunk of savegame versions 69
+    /// to 122, which had no waypoints. Fields the layout doesn't have are 0, like the size of
+    /// airports and docks which the game takes from their type, and the cargo waiting at each
+    /// station is a single group for any next hop.
+    ///
+    /// Stations can only be written to `STNN`, so saves this old can't be edited.
+    pub fn from_legacy_chunk(chunk: &ChunkValue, version: u16) -> Result<BTreeMap<u32, Station>> {
+        let stations = match chunk {
+            ChunkValue::ChArray { elements } => {
+                ChunkSchema::find(*b"STNS", version).and_then(|schema| {
+                    elements
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, element)| !element.data.is_empty())
+                        .map(|(index, element)| {
+                            let row = schema.read_all(&element.data, version, &SLXI::default())?;
+                            Ok((index as u32, Station::from_legacy(from_table(&row)?)))
+                        })
+                        .collect()
+                })
+            }
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        stations.map_err(|e| e.in_chunk(*b"STNS", 0))
+    }
+
+    fn from_legacy(old: LegacyStation) -> Station {
+        let goods = old
+            .goods
+            .into_iter()
+            .map(|goods| GoodsEntry {
+                status: goods.status,
+                time_since_pickup: goods.time_since_pickup,
+                rating: goods.rating,
+                last_speed: goods.last_speed,
+                last_age: goods.last_age,
+                amount_fract: 0,
+                reserved_count: 0,
+                link_graph: INVALID_LINK_GRAPH,
+                node: INVALID_NODE,
+                max_waiting_cargo: 0,
+                flows: vec![],
+                cargo: Some(goods.packets)
+                    .filter(|packets| !packets.is_empty())
+                    .map(|packets| StationCargo {
+                        next_hop: INVALID_STATION,
+                        packets,
+                    })
+                    .into_iter()
+                    .collect(),
+            })
+            .collect();
+
+        Station {
+            facilities: old.facilities,
+            normal: Some(NormalStation {
+                base: BaseStation {
+                    xy: old.xy,
+                    town: old.town,
+                    string_id: old.string_id,
+                    name: old.name,
+                    delete_ctr: old.delete_ctr,
+                    owner: old.owner,
+                    facilities: old.facilities,
+                    build_date: old.build_date,
+                    random_bits: old.random_bits,
+                    waiting_triggers: old.waiting_triggers,
+                },
+                train_station_tile: old.train_station_tile,
+                train_station_w: old.train_station_w,
+                train_station_h: old.train_station_h,
+                bus_stops: old.bus_stops,
+                truck_stops: old.truck_stops,
+                ship_station_tile: old.ship_station_tile,
+                ship_station_w: 0,
+                ship_station_h: 0,
+                docking_station_tile: 0,
+                docking_station_w: 0,
+                docking_station_h: 0,
+                airport_tile: old.airport_tile,
+                airport_w: 0,
+                airport_h: 0,
+                airport_type: old.airport_type,
+                airport_layout: 0,
+                airport_flags: old.airport_flags,
+                airport_rotation: 0,
+                airport_psa: 0,
+                indtype: old.indtype,
+                time_since_load: old.time_since_load,
+                time_since_unload: old.time_since_unload,
+                last_vehicle_type: old.last_vehicle_type,
+                had_vehicle_of_type: old.had_vehicle_of_type,
+                loading_vehicles: old.loading_vehicles,
+                always_accepted: 0,
+                goods,
+            }),
+            waypoint: None,
+            speclist: old.speclist,
+        }
+    }
+
+    pub fn write_to_save(stations: &BTreeMap<u32, Station>, save: &mut Save) -> Result<()> {
+        let mut chunk = save
+            .get(b"STNN")
+            .ok_or(Error::MissingChunk { tag: *b"STNN" })?
+            .clone();
+        Station::write_to_chunk(stations, &mut chunk, save.base_version())?;
+        save.set(Chunk {
+            tag: *b"STNN",
+            value: chunk,
+        });
+        Ok(())
+    }
+
+    pub fn write_to_lazy_save(
+        stations: &BTreeMap<u32, Station>,
+        save: &mut LazySave,
+    ) -> Result<()> {
+        let mut chunk = save
+            .get(b"STNN")
+            .ok_or(Error::MissingChunk { tag: *b"STNN" })?
+            .decode()?;
+        Station::write_to_chunk(stations, &mut chunk.value, save.base_version())?;
+        save.set(&chunk)
+    }
+
+    /// Writes the stations back into the contents of the `STNN` chunk in the layout it already
+    /// has. Each station replaces the element with its index, stations that aren't in
+    /// `stations` are kept.
+    pub fn write_to_chunk(
+        stations: &BTreeMap<u32, Station>,
+        chunk: &mut ChunkValue,
+        version: u16,
+    ) -> Result<()> {
+        let result = match chunk {
+            ChunkValue::ChArray { elements } => {
+                ChunkSchema::find(*b"STNN", version).and_then(|schema| {
+                    let slxi = SLXI::default();
+                    stations.iter().try_for_each(|(&index, station)| {
+                        let element = existing(elements, index, |e| e.data.is_empty())?;
+                        // Checks the existing element is in a layout we can write. The station
+                        // may have become a waypoint, so it's written from its own row.
+                        schema.read_all(&element.data, version, &slxi)?;
+                        *element = ChArrayElement {
+                            data: schema.write(&to_row(station)?, version, &slxi)?,
+                        };
+                        Ok(())
+                    })
+                })
+            }
+            ChunkValue::ChTable { header, elements } => {
+                stations.iter().try_for_each(|(&index, station)| {
+                    let element = existing(elements, index, |e| e.data.is_empty())?;
+                    station.update_table(element, header)
+                })
+            }
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        result.map_err(|e| e.in_chunk(*b"STNN", 0))
+    }
+
+    fn update_table(
+        &self,
+        element: &mut ChTableElement,
+        header: &[TableHeaderProperty],
+    ) -> Result<()> {
+        update_table(&mut element.data, header, self)
+    }
+}
+
+impl GoodsEntry {
+    /// Whether a vehicle ever tried to load this cargo here, which is when the rating counts
+    pub fn has_rating(&self) -> bool {
+        self.status & GES_RATING != 0
+    }
+
+    pub fn is_accepted(&self) -> bool {
+        self.status & GES_ACCEPTANCE != 0
+    }
+
+    /// The rating as the percentage shown in game
+    pub fn rating_percent(&self) -> u8 {
+        (self.rating as u32 * 100 / 255) as u8
+    }
+
+    /// Units of cargo waiting, counted from the packets of the `CAPA` chunk
+    pub fn waiting(&self, packets: &BTreeMap<u32, CargoPacket>) -> u32 {
+        self.cargo
+            .iter()
+            .map(|cargo| CargoPacket::total(packets, &cargo.packets))
+            .sum()
+    }
+}
+
+fn existing<T>(elements: &mut [T], index: u32, is_empty: impl Fn(&T) -> bool) -> Result<&mut T> {
+    elements
+        .get_mut(index as usize)
+        .filter(|element| !is_empty(element))
+        .ok_or_else(|| Error::Malformed {
+            message: format!("there is no station {index} to write to"),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use binrw::io::Cursor;
+    use std::fs::File;
+
+    use crate::cargo::CargoPacket;
+    use crate::charray::{Maps, MAX_CARGO};
+    use crate::chtable::{TableData, TableStruct};
+    use crate::error::Result;
+    use crate::jgr::SLXI;
+    use crate::save::{ChArrayElement, ChunkValue, CompressionType, Save, WriteOptions};
+    use crate::schema::ChunkSchema;
+    use crate::station::{
+        Station, StationCargo, StationSpec, INVALID_LINK_GRAPH, INVALID_NODE, INVALID_STATION,
+    };
+    use crate::table::{from_table, to_row, to_table};
+
+    /// Stations of tiny.sav without the fields savegame version 190 doesn't have
+    fn stations_at_190() -> Result<Vec<Station>> {
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let mut stations: Vec<Station> = Station::from_save(&save)?.into_values().collect();
+        for normal in stations.iter_mut().filter_map(|s| s.normal.as_mut()) {
+            normal.ship_station_w = 0;
+            normal.ship_station_h = 0;
+            normal.docking_station_tile = 0;
+            normal.docking_station_w = 0;
+            normal.docking_station_h = 0;
+            normal.goods.truncate(32);
+        }
+        Ok(stations)
+    }
+
+    fn array_chunk(
+        rows: Vec<Vec<(String, TableData)>>,
+        schema: &ChunkSchema,
+        version: u16,
+    ) -> Result<ChunkValue> {
+        let elements = rows
+            .iter()
+            .map(|row| {
+                let data = schema.write(row, version, &SLXI::default())?;
+                Ok(ChArrayElement { data })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ChunkValue::ChArray { elements })
+    }
+
+    #[test]
+    fn read_stations() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        assert!(Station::from_save(&save)?.is_empty());
+
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let stations = Station::from_save(&save)?;
+        assert_eq!(stations.len(), 13);
+
+        let maps = Maps::from_chunk(save.get(b"MAPS").unwrap())?;
+        let packets = CargoPacket::from_save(&save)?;
+        let mut waiting = 0;
+        let waypoints: Vec<u32> = stations
+            .iter()
+            .filter(|(_, station)| station.is_waypoint())
+            .map(|(&index, _)| index)
+            .collect();
+        assert_eq!(waypoints, [5]);
+        assert!(stations[&5].goods().is_empty());
+
+        for station in stations.values().filter(|station| !station.is_waypoint()) {
+            assert_eq!(station.goods().len(), MAX_CARGO);
+            let (x, y) = station.sign_position(maps.dim_x).unwrap();
+            assert!(x < maps.dim_x && y < maps.dim_y);
+            waiting += station
+                .goods()
+                .iter()
+                .map(|goods| goods.waiting(&packets))
+                .sum::<u32>();
+        }
+        assert!(waiting > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_older_station_layouts() -> Result<()> {
+        let stations = stations_at_190()?;
+        let version = 190;
+        let schema = ChunkSchema::find(*b"STNN", version)?;
+        let rows = stations.iter().map(to_row).collect::<Result<Vec<_>>>()?;
+        let chunk = array_chunk(rows, schema, version)?;
+        let read: Vec<Station> = Station::from_chunk(&chunk, version)?
+            .into_values()
+            .collect();
+        assert_eq!(read, stations);
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_legacy_stations() -> Result<()> {
+        let stations: Vec<Station> = stations_at_190()?
+            .into_iter()
+            .filter(|station| !station.is_waypoint())
+            .collect();
+
+        // STNS has the fields of the base station beside the others, and no next hops
+        let version = 110;
+        let rows = stations
+            .iter()
+            .map(|station| {
+                let normal = station.normal.as_ref().unwrap();
+                let mut row = to_row(&normal.base)?;
+                row.extend(
+                    to_row(normal)?
+                        .into_iter()
+                        .filter(|(key, _)| key != "base" && key != "goods"),
+                );
+                let goods = normal
+                    .goods
+                    .iter()
+                    .map(|goods| {
+                        let mut goods_row = to_row(goods)?;
+                        let packets = goods.cargo.iter().flat_map(|c| c.packets.clone());
+                        goods_row.push((
+                            "cargo.packets".to_string(),
+                            TableData::UInt32List(packets.collect()),
+                        ));
+                        Ok(goods_row)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                row.push((
+                    "goods".to_string(),
+                    TableData::Struct(TableStruct { data: goods }),
+                ));
+                row.extend(
+                    to_row(station)?
+                        .into_iter()
+                        .filter(|(key, _)| key == "speclist"),
+                );
+                Ok(row)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let chunk = array_chunk(rows, ChunkSchema::find(*b"STNS", version)?, version)?;
+        let read: Vec<Station> = Station::from_legacy_chunk(&chunk, version)?
+            .into_values()
+            .collect();
+
+        let expected: Vec<Station> = stations
+            .into_iter()
+            .map(|mut station| {
+                let normal = station.normal.as_mut().unwrap();
+                normal.airport_w = 0;
+                normal.airport_h = 0;
+                normal.airport_layout = 0;
+                normal.airport_rotation = 0;
+                normal.airport_psa = 0;
+                normal.always_accepted = 0;
+                for goods in normal.goods.iter_mut() {
+                    goods.amount_fract = 0;
+                    goods.reserved_count = 0;
+                    goods.link_graph = INVALID_LINK_GRAPH;
+                    goods.node = INVALID_NODE;
+                    goods.max_waiting_cargo = 0;
+                    goods.flows.clear();
+                    let packets: Vec<u32> = goods.cargo.drain(..).flat_map(|c| c.packets).collect();
+                    if !packets.is_empty() {
+                        goods.cargo.push(StationCargo {
+                            next_hop: INVALID_STATION,
+                            packets,
+                        });
+                    }
+                }
+                station
+            })
+            .collect();
+        assert_eq!(read, expected);
+        // Some cargo is waiting, so the packets were mapped too
+        assert!(read
+            .iter()
+            .flat_map(|station| station.goods())
+            .any(|goods| !goods.cargo.is_empty()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_stations() -> Result<()> {
+        let mut save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let options = WriteOptions::new(CompressionType::OTTN);
+        let mut expected = vec![];
+        save.write_with_options(&mut Cursor::new(&mut expected), &options)?;
+
+        // Writing the stations back unchanged keeps the save the same
+        let mut stations = Station::from_save(&save)?;
+        Station::write_to_save(&stations, &mut save)?;
+        let mut d = vec![];
+        save.write_with_options(&mut Cursor::new(&mut d), &options)?;
+        assert_eq!(d, expected);
+
+        for station in stations.values_mut() {
+            station.base_mut().unwrap().name = "Central".to_string();
+            if let Some(normal) = station.normal.as_mut() {
+                normal.goods[0].rating = 255;
+            }
+            station.speclist.push(StationSpec {
+                grfid: 0x12345678,
+                localidx: 1,
+            });
+        }
+        Station::write_to_save(&stations, &mut save)?;
+        let written = Station::from_save(&save)?;
+        assert_eq!(written, stations);
+        assert_eq!(written[&0].name(), Some("Central"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn station_table_round_trip() -> Result<()> {
+        // The table save has no stations, so move the stations of the array save into its layout
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let Some(ChunkValue::ChTable { header, .. }) = save.get(b"STNN") else {
+            panic!("STNN isn't a table");
+        };
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        for station in Station::from_save(&save)?.into_values() {
+            let data = to_table(&station, header)?;
+            assert_eq!(from_table::<Station>(&data)?, station);
+        }
+
+        Ok(())
+    }
+}
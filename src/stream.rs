@@ -0,0 +1,278 @@
+use crate::error::{Error, Result};
+use crate::gamma::parse_gamma;
+use crate::save::{Chunk, CompressionType, Decoder, Encoder, RawChunk, WriteOptions};
+use binrw::{
+    io::{Cursor, ErrorKind, Read, Write},
+    Endian,
+};
+use std::io::BufReader;
+
+/// Moves the offset of an error from the start of a chunk to the start of the save data
+fn offset_error(error: Error, start: u64) -> Error {
+    match error {
+        Error::Chunk {
+            tag,
+            offset,
+            source,
+        } => Error::Chunk {
+            tag,
+            offset: offset + start,
+            source,
+        },
+        error => error,
+    }
+}
+
+/// Reads the chunks of a save file one at a time as they're decompressed,
+/// so only one chunk is held in memory at once.
+///
+/// The lzma-rs backend can't stream, so with it the decompressed data is still buffered.
+pub struct ChunkReader<R: Read> {
+    pub compression_type: CompressionType,
+    pub version: u16,
+    reader: BufReader<Decoder<R>>,
+    // Bytes of the current chunk
+    buffer: Vec<u8>,
+    // Offset of the current chunk in the decompressed data
+    offset: u64,
+    finished: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// Reads the header of a save file, the chunks are then read by iterating
+    pub fn new(mut reader: R) -> Result<ChunkReader<R>> {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+
+        let compression_type =
+            CompressionType::from_magic([header[0], header[1], header[2], header[3]])?;
+        let version = u16::from_be_bytes([header[4], header[5]]);
+
+        Ok(ChunkReader {
+            compression_type,
+            version,
+            reader: BufReader::new(Decoder::new(compression_type, reader)?),
+            buffer: Vec::new(),
+            offset: 0,
+            finished: false,
+        })
+    }
+
+    /// Reads the next chunk without decoding it, returning None after the terminator
+    pub fn next_raw(&mut self) -> Option<Result<RawChunk>> {
+        if self.finished {
+            return None;
+        }
+
+        let chunk = self.read_raw().transpose();
+        if !matches!(chunk, Some(Ok(_))) {
+            self.finished = true;
+        }
+        chunk
+    }
+
+    fn read_raw(&mut self) -> Result<Option<RawChunk>> {
+        self.buffer.clear();
+
+        let mut tag = [0u8; 4];
+        self.reader.read_exact(&mut tag)?;
+        if tag == [0u8; 4] {
+            return Ok(None);
+        }
+        self.buffer.extend_from_slice(&tag);
+
+        // Copy the chunk's bytes without parsing them
+        self.copy_chunk()
+            .map_err(|e| e.in_chunk(tag, self.offset + self.buffer.len() as u64))?;
+        self.offset += self.buffer.len() as u64;
+
+        Ok(Some(RawChunk::from_bytes(std::mem::take(
+            &mut self.buffer,
+        ))?))
+    }
+
+    fn copy_chunk(&mut self) -> Result<()> {
+        self.copy(1)?;
+        let chunk_type = self.buffer[4];
+
+        match chunk_type & 0xf {
+            0 => {
+                self.copy(3)?;
+                let size = ((chunk_type as usize >> 4) << 24)
+                    | ((self.buffer[5] as usize) << 16)
+                    | ((self.buffer[6] as usize) << 8)
+                    | (self.buffer[7] as usize);
+                self.copy(size)
+            }
+            1 | 2 => self.copy_elements(),
+            3 | 4 => {
+                let header_size = self.copy_gamma()?;
+                self.copy(header_size.saturating_sub(1))?;
+                self.copy_elements()
+            }
+            chunk_type => Err(Error::BadChunkType { chunk_type }),
+        }
+    }
+
+    /// Copies elements prefixed by their size + 1 until a size of 0
+    fn copy_elements(&mut self) -> Result<()> {
+        loop {
+            match self.copy_gamma()? {
+                0 => return Ok(()),
+                size => self.copy(size - 1)?,
+            }
+        }
+    }
+
+    fn copy_gamma(&mut self) -> Result<usize> {
+        let start = self.buffer.len();
+        self.copy(1)?;
+        let extra = self.buffer[start].leading_ones().min(4) as usize;
+        self.copy(extra)?;
+
+        let value = parse_gamma(&mut Cursor::new(&self.buffer[start..]), Endian::Big, ())?;
+        Ok(value as usize)
+    }
+
+    fn copy(&mut self, len: usize) -> Result<()> {
+        // Using take avoids allocating a huge buffer for a corrupt length
+        let read = (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut self.buffer)?;
+        if read != len {
+            return Err(binrw::io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.offset;
+        self.next_raw()
+            .map(|chunk| chunk?.decode().map_err(|e| offset_error(e, start)))
+    }
+}
+
+/// Compresses and writes the chunks of a save file one at a time.
+/// [`ChunkWriter::finish`] must be called to write the terminator and end of the compressed stream.
+pub struct ChunkWriter<W: Write> {
+    encoder: Encoder<W>,
+    // Offset of the current chunk in the decompressed data
+    offset: u64,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    /// Writes the header of a save file using the default compression level
+    pub fn new(
+        writer: W,
+        compression_type: CompressionType,
+        version: u16,
+    ) -> Result<ChunkWriter<W>> {
+        ChunkWriter::with_options(writer, compression_type, version, &WriteOptions::default())
+    }
+
+    /// Writes the header of a save file, the compression type in the options takes priority if set
+    pub fn with_options(
+        mut writer: W,
+        compression_type: CompressionType,
+        version: u16,
+        options: &WriteOptions,
+    ) -> Result<ChunkWriter<W>> {
+        let compression_type = options.compression_type_or(&compression_type);
+
+        writer.write_all(&compression_type.magic())?;
+        writer.write_all(&version.to_be_bytes())?;
+        writer.write_all(&[0, 0])?;
+
+        Ok(ChunkWriter {
+            encoder: Encoder::new(compression_type, options, writer)?,
+            offset: 0,
+        })
+    }
+
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> Result<()> {
+        let chunk = RawChunk::encode(chunk).map_err(|e| offset_error(e, self.offset))?;
+        self.write_raw(&chunk)
+    }
+
+    /// Writes a chunk's bytes without any changes
+    pub fn write_raw(&mut self, chunk: &RawChunk) -> Result<()> {
+        self.encoder.write_all(chunk.as_bytes())?;
+        self.offset += chunk.as_bytes().len() as u64;
+        Ok(())
+    }
+
+    /// Writes the terminator and finishes compression, returning the inner writer
+    pub fn finish(mut self) -> Result<W> {
+        self.encoder.write_all(&[0u8; 4])?;
+        self.encoder.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use binrw::io::Cursor;
+    use std::fs::File;
+
+    use crate::error::{Error, Result};
+    use crate::save::{CompressionType, Save, WriteOptions};
+    use crate::stream::{ChunkReader, ChunkWriter};
+
+    #[test]
+    fn stream_matches_save() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let mut expected = vec![];
+        save.write_with_options(
+            &mut Cursor::new(&mut expected),
+            &WriteOptions::new(CompressionType::OTTN),
+        )?;
+
+        let reader = ChunkReader::new(File::open("tests/TinyVanillaTest.sav")?)?;
+        assert_eq!(reader.compression_type, save.compression_type);
+        let mut writer = ChunkWriter::new(vec![], CompressionType::OTTN, reader.version)?;
+        for chunk in reader {
+            writer.write_chunk(&chunk?)?;
+        }
+        assert_eq!(writer.finish()?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn stream_lzo() -> Result<()> {
+        let reader = ChunkReader::new(File::open("tests/tiny.sav")?)?;
+        let version = reader.version;
+        let chunks = reader.collect::<Result<Vec<_>>>()?;
+
+        let mut writer = ChunkWriter::new(vec![], CompressionType::OTTD, version)?;
+        for chunk in chunks.iter().filter(|chunk| &chunk.tag != b"MAPT") {
+            writer.write_chunk(chunk)?;
+        }
+        let data = writer.finish()?;
+
+        let save = Save::from_reader(&mut Cursor::new(&data))?;
+        assert_eq!(save.compression_type, CompressionType::OTTD);
+        assert_eq!(save.chunks.len(), chunks.len() - 1);
+        assert!(save.get(b"MAPT").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn stream_errors() {
+        // A valid RIFF chunk followed by one with a bad chunk type
+        let data = b"OTTN\x01\x2e\x00\x00MAPS\x00\x00\x00\x02abMAPT\x07\x00\x00\x00\x00";
+        let mut reader = ChunkReader::new(Cursor::new(data)).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Chunk { tag, offset: 15, source } if &tag == b"MAPT" && matches!(*source, Error::BadChunkType { chunk_type: 7 })
+        ));
+        assert!(reader.next().is_none());
+    }
+}
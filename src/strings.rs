@@ -0,0 +1,529 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::chtable::field;
+use crate::company::Company;
+use crate::error::Result;
+use crate::save::{ChunkValue, Save};
+use crate::station::Station;
+use crate::tile::gb;
+use crate::town::Town;
+use crate::townname::TownNameStyle;
+use crate::vehicle::Vehicle;
+
+// String IDs follow OpenTTD's strings_type.h and the `##id 0x6000` block of english.txt, which
+// keeps the strings saves refer to at the same ID across versions.
+
+/// Town name type of the first built-in generator, the others follow in OpenTTD's order
+pub const SPECSTR_TOWNNAME_START: u16 = 0x20C0;
+/// Number of built-in town name generators
+pub const TOWNNAME_GENERATORS: u16 = 21;
+pub const SPECSTR_SILLY_NAME: u16 = 0x70E5;
+/// `<president surname> & Co.` with the president's seed
+pub const SPECSTR_ANDCO_NAME: u16 = 0x70E6;
+pub const SPECSTR_PRESIDENT_NAME: u16 = 0x70E7;
+/// `<town name> Transport` for the first built-in generator, the others follow
+pub const SPECSTR_COMPANY_NAME_START: u16 = 0x70EA;
+
+pub const STR_SV_UNNAMED: u16 = 0x6001;
+pub const STR_SV_TRAIN_NAME: u16 = 0x6002;
+pub const STR_SV_STNAME: u16 = 0x6006;
+pub const STR_SV_STNAME_WAYPOINT: u16 = 0x6018;
+
+/// The base strings saves refer to, with OpenTTD's format codes
+const BASE_STRINGS: &[(u16, &str)] = &[
+    (0x6000, ""),
+    (0x6001, "Unnamed"),
+    (0x6002, "Train #{COMMA}"),
+    (0x6003, "Road Vehicle #{COMMA}"),
+    (0x6004, "Ship #{COMMA}"),
+    (0x6005, "Aircraft #{COMMA}"),
+    (0x6006, "{STRING1}"),
+    (0x6007, "{STRING1} North"),
+    (0x6008, "{STRING1} South"),
+    (0x6009, "{STRING1} East"),
+    (0x600A, "{STRING1} West"),
+    (0x600B, "{STRING1} Central"),
+    (0x600C, "{STRING1} Transfer"),
+    (0x600D, "{STRING1} Halt"),
+    (0x600E, "{STRING1} Valley"),
+    (0x600F, "{STRING1} Heights"),
+    (0x6010, "{STRING1} Woods"),
+    (0x6011, "{STRING1} Lakeside"),
+    (0x6012, "{STRING1} Exchange"),
+    (0x6013, "{STRING1} Airport"),
+    (0x6014, "{STRING1} Oilfield"),
+    (0x6015, "{STRING1} Mines"),
+    (0x6016, "{STRING1} Docks"),
+    (0x6017, "{STRING1} Buoy"),
+    (0x6018, "{STRING1} Waypoint"),
+    (0x6020, "{STRING1} Annexe"),
+    (0x6021, "{STRING1} Sidings"),
+    (0x6022, "{STRING1} Branch"),
+    (0x6023, "Upper {STRING1}"),
+    (0x6024, "Lower {STRING1}"),
+    (0x6025, "{STRING1} Heliport"),
+    (0x6026, "{STRING1} Forest"),
+    (0x6027, "{STRING1}, Station #{NUM}"),
+];
+
+const SURNAMES: &[&str] = &[
+    "Adams",
+    "Allan",
+    "Baker",
+    "Bigwig",
+    "Black",
+    "Bloggs",
+    "Brown",
+    "Campbell",
+    "Gordon",
+    "Hamilton",
+    "Hawthorn",
+    "Higgins",
+    "Green",
+    "Gribble",
+    "Jones",
+    "McAlpine",
+    "MacDonald",
+    "McIntosh",
+    "Muir",
+    "Murphy",
+    "Nelson",
+    "O'Donnell",
+    "Parker",
+    "Phillips",
+    "Pilkington",
+    "Quigley",
+    "Sharkey",
+    "Thomson",
+    "Watkins",
+];
+
+/// Surnames used in the toyland climate instead
+const SILLY_SURNAMES: &[&str] = &[
+    "Grumpy", "Dozy", "Speedy", "Nosey", "Dribble", "Mushroom", "Cabbage", "Sniffle", "Fishy",
+    "Swindle", "Sneaky", "Nutty",
+];
+
+const INITIALS: &[u8] = b"ABCDEFGHIJKLMNPRSTW";
+
+/// Value of `game_creation.landscape` for the toyland climate
+const LT_TOYLAND: i64 = 3;
+
+/// Where a name shown in game comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum StringSource {
+    /// Typed in by a player
+    Custom,
+    /// Made by a town name generator from the seed in `parts`. `generator` is the index of
+    /// a built-in generator when `grfid` is 0, otherwise the NewGRF's own name style.
+    TownName {
+        grfid: u32,
+        generator: u16,
+        parts: u32,
+    },
+    /// `<town name> Transport`, from a built-in town name generator
+    CompanyTownName { generator: u16, parts: u32 },
+    /// `<surname> & Co.`
+    AndCo { seed: u32 },
+    /// Initials and a surname
+    PresidentName { seed: u32 },
+    /// One of the base strings saves refer to, filled in with the names it mentions
+    Base { id: u16 },
+    /// A string ID that isn't known, like the ones from NewGRFs
+    Unknown { id: u16 },
+}
+
+/// A name as it's shown in game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Name {
+    pub source: StringSource,
+    /// The English text, None when it can't be worked out from the save alone
+    pub text: Option<String>,
+}
+
+impl Name {
+    fn custom(text: &str) -> Name {
+        Name {
+            source: StringSource::Custom,
+            text: Some(text.to_string()),
+        }
+    }
+}
+
+/// Works out the names of towns, stations, companies and vehicles, which saves store as custom
+/// names or as string IDs with parameters
+#[derive(Debug, Clone, Default)]
+pub struct Names {
+    towns: BTreeMap<u32, Name>,
+    /// Toyland uses silly surnames for presidents and company names
+    pub toyland: bool,
+}
+
+impl Names {
+    pub fn new(towns: &[Town], toyland: bool) -> Names {
+        Names {
+            towns: towns
+                .iter()
+                .map(|town| (town.index, Names::town_name(town)))
+                .collect(),
+            toyland,
+        }
+    }
+
+    /// Reads the towns of the save. The climate is only known from saves where `PATS` is a
+    /// table, others are treated as not being toyland. Saves whose towns can't be read, like
+    /// ones older than the known `CITY` layouts, have no town names.
+    pub fn from_save(save: &Save) -> Result<Names> {
+        let toyland = match save.get(b"PATS") {
+            Some(ChunkValue::ChTable { elements, .. }) => elements
+                .first()
+                .and_then(|element| field(&element.data, "game_creation.landscape"))
+                .and_then(|value| value.as_i64())
+                .is_some_and(|landscape| landscape == LT_TOYLAND),
+            _ => false,
+        };
+        let towns = Town::from_save(save).unwrap_or_default();
+        Ok(Names::new(&towns, toyland))
+    }
+
+    pub fn town_name(town: &Town) -> Name {
+        if !town.name.is_empty() {
+            return Name::custom(&town.name);
+        }
+        let generator = match town.townnamegrfid {
+            0 => town.townnametype.wrapping_sub(SPECSTR_TOWNNAME_START),
+            _ => town.townnametype,
+        };
+        Name {
+            source: StringSource::TownName {
+                grfid: town.townnamegrfid,
+                generator,
+                parts: town.townnameparts,
+            },
+            text: town.generated_name(),
+        }
+    }
+
+    /// The name of the town with the given index
+    pub fn town(&self, index: u32) -> Option<&Name> {
+        self.towns.get(&index)
+    }
+
+    /// Resolves a string ID with the one parameter saves store alongside it
+    pub fn resolve(&self, id: u16, param: u32) -> Name {
+        let surname = |seed: u32| {
+            let surnames = if self.toyland {
+                SILLY_SURNAMES
+            } else {
+                SURNAMES
+            };
+            surnames[scale(surnames.len(), gb(seed, 16, 8))]
+        };
+
+        match id {
+            SPECSTR_ANDCO_NAME => Name {
+                source: StringSource::AndCo { seed: param },
+                text: Some(format!("{} & Co.", surname(param))),
+            },
+            SPECSTR_PRESIDENT_NAME => {
+                let mut text = String::new();
+                let first = scale(INITIALS.len(), gb(param, 0, 8));
+                text.push_str(&format!("{}. ", INITIALS[first] as char));
+                // The second initial is only there some of the time
+                let second = scale(INITIALS.len() + 35, gb(param, 8, 8));
+                if let Some(&initial) = INITIALS.get(second) {
+                    text.push_str(&format!("{}. ", initial as char));
+                }
+                text.push_str(surname(param));
+                Name {
+                    source: StringSource::PresidentName { seed: param },
+                    text: Some(text),
+                }
+            }
+            id if (SPECSTR_COMPANY_NAME_START
+                ..SPECSTR_COMPANY_NAME_START + TOWNNAME_GENERATORS)
+                .contains(&id) =>
+            {
+                let generator = id - SPECSTR_COMPANY_NAME_START;
+                Name {
+                    source: StringSource::CompanyTownName {
+                        generator,
+                        parts: param,
+                    },
+                    text: TownNameStyle::from_raw(generator as u32)
+                        .ok()
+                        .and_then(|style| style.generate(param))
+                        .map(|text| format!("{text} Transport")),
+                }
+            }
+            id if (SPECSTR_TOWNNAME_START..SPECSTR_TOWNNAME_START + TOWNNAME_GENERATORS)
+                .contains(&id) =>
+            {
+                Name {
+                    source: StringSource::TownName {
+                        grfid: 0,
+                        generator: id - SPECSTR_TOWNNAME_START,
+                        parts: param,
+                    },
+                    text: TownNameStyle::from_town_name_type(id)
+                        .and_then(|style| style.generate(param)),
+                }
+            }
+            id => self.resolve_with_town(id, 0, param),
+        }
+    }
+
+    /// Fills in a base string, which can mention the town with the reference `town` and a number
+    fn resolve_with_town(&self, id: u16, town: u32, number: u32) -> Name {
+        let Some(format) = base_string(id) else {
+            return Name {
+                source: StringSource::Unknown { id },
+                text: None,
+            };
+        };
+        let town_name = town
+            .checked_sub(1)
+            .and_then(|index| self.town(index))
+            .and_then(|name| name.text.as_deref());
+        let text = match (format.contains("{STRING1}"), town_name) {
+            (true, None) => None,
+            (_, town_name) => Some(format_base(format, town_name, number)),
+        };
+        Name {
+            source: StringSource::Base { id },
+            text,
+        }
+    }
+
+    /// The name of the station with the given index
+    pub fn station(&self, index: u32, station: &Station) -> Option<Name> {
+        let base = station.base()?;
+        if !base.name.is_empty() {
+            return Some(Name::custom(&base.name));
+        }
+        Some(match &station.waypoint {
+            // Waypoints after the first in a town are numbered
+            Some(waypoint) if waypoint.town_cn > 0 => {
+                let mut name = self.resolve_with_town(STR_SV_STNAME_WAYPOINT, base.town, index);
+                name.text = name
+                    .text
+                    .map(|text| format!("{text} #{}", waypoint.town_cn + 1));
+                name
+            }
+            _ => self.resolve_with_town(base.string_id, base.town, index),
+        })
+    }
+
+    pub fn company(&self, company: &Company) -> Name {
+        if !company.name.is_empty() {
+            return Name::custom(&company.name);
+        }
+        self.resolve(company.name_1, company.name_2)
+    }
+
+    pub fn president(&self, company: &Company) -> Name {
+        if !company.president_name.is_empty() {
+            return Name::custom(&company.president_name);
+        }
+        self.resolve(company.president_name_1, company.president_name_2)
+    }
+
+    /// The name of a train, road vehicle, ship or aircraft, None for other vehicles
+    pub fn vehicle(&self, vehicle: &Vehicle) -> Option<Name> {
+        let common = vehicle.common()?;
+        if !common.name.is_empty() {
+            return Some(Name::custom(&common.name));
+        }
+        Some(self.resolve(
+            STR_SV_TRAIN_NAME + vehicle.vehicle_type() as u16,
+            common.unitnumber as u32,
+        ))
+    }
+}
+
+/// The names of everything in a save, by index in their pool. Parts of the save the models can't
+/// read, like the stations of a version without a known layout, are None.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveNames {
+    pub towns: BTreeMap<u32, Name>,
+    pub stations: Option<BTreeMap<u32, Name>>,
+    pub companies: Option<BTreeMap<u32, Name>>,
+    pub presidents: Option<BTreeMap<u32, Name>>,
+    /// Only trains, road vehicles, ships and aircraft have names
+    pub vehicles: Option<BTreeMap<u32, Name>>,
+}
+
+impl SaveNames {
+    pub fn from_save(save: &Save) -> Result<SaveNames> {
+        let names = Names::from_save(save)?;
+        let companies = Company::from_save(save).ok();
+        let names_of = |name: fn(&Names, &Company) -> Name| {
+            companies.as_ref().map(|companies| {
+                companies
+                    .iter()
+                    .map(|company| (company.index, name(&names, company)))
+                    .collect()
+            })
+        };
+        Ok(SaveNames {
+            towns: names.towns.clone(),
+            stations: Station::from_save(save).ok().map(|stations| {
+                stations
+                    .iter()
+                    .filter_map(|(&index, station)| Some((index, names.station(index, station)?)))
+                    .collect()
+            }),
+            companies: names_of(Names::company),
+            presidents: names_of(Names::president),
+            vehicles: Vehicle::from_save(save).ok().map(|vehicles| {
+                vehicles
+                    .iter()
+                    .filter_map(|(&index, vehicle)| Some((index, names.vehicle(vehicle)?)))
+                    .collect()
+            }),
+        })
+    }
+}
+
+/// A save with the names of what's in it, which is what the JSON export shows
+#[derive(Debug, Clone, Serialize)]
+pub struct NamedSave<'a> {
+    #[serde(flatten)]
+    pub save: &'a Save,
+    pub names: SaveNames,
+}
+
+impl NamedSave<'_> {
+    pub fn new(save: &Save) -> Result<NamedSave<'_>> {
+        Ok(NamedSave {
+            save,
+            names: SaveNames::from_save(save)?,
+        })
+    }
+}
+
+/// The English text of a base string saves refer to, with OpenTTD's format codes like
+/// `{STRING1}` for a town name
+pub fn base_string(id: u16) -> Option<&'static str> {
+    BASE_STRINGS
+        .binary_search_by_key(&id, |&(key, _)| key)
+        .ok()
+        .map(|index| BASE_STRINGS[index].1)
+}
+
+/// Picks from `len` items with 8 random bits, like OpenTTD does
+fn scale(len: usize, bits: u32) -> usize {
+    (len * bits as usize) >> 8
+}
+
+fn format_base(format: &str, town: Option<&str>, number: u32) -> String {
+    format
+        .replace("{STRING1}", town.unwrap_or_default())
+        .replace("{COMMA}", &number.to_string())
+        .replace("{NUM}", &number.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::company::Company;
+    use crate::error::Result;
+    use crate::save::Save;
+    use crate::station::Station;
+    use crate::strings::{NamedSave, Names, SaveNames, StringSource};
+    use crate::town::Town;
+    use crate::vehicle::Vehicle;
+
+    #[test]
+    fn resolve_names() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let mut towns = Town::from_save(&save)?;
+        let names = Names::new(&towns, false);
+        assert_eq!(
+            names.town(0).unwrap().source,
+            StringSource::TownName {
+                grfid: 0,
+                generator: 0,
+                parts: towns[0].townnameparts,
+            }
+        );
+
+        // The company is named after the first town and has a generated president
+        let company = &Company::from_save(&save)?[0];
+        assert_eq!(
+            names.company(company).source,
+            StringSource::CompanyTownName {
+                generator: 0,
+                parts: towns[0].townnameparts,
+            }
+        );
+        assert_eq!(
+            names.company(company).text,
+            Some(format!("{} Transport", towns[0].generated_name().unwrap()))
+        );
+        let president = names.president(company);
+        assert_eq!(president.text.as_deref(), Some("B. McAlpine"));
+
+        let vehicles = Vehicle::from_save(&save)?;
+        let train = names.vehicle(&vehicles[&1]).unwrap();
+        assert_eq!(train.text.as_deref(), Some("Train #1"));
+        assert!(names.vehicle(&vehicles[&0]).is_none());
+
+        // Station names fill in the name of their town
+        towns[0].name = "Springfield".to_string();
+        let names = Names::new(&towns, false);
+        let stations = Station::from_save(&save)?;
+        let text = |index| names.station(index, &stations[&index]).unwrap().text;
+        assert_eq!(text(0).as_deref(), Some("Springfield Valley"));
+        assert_eq!(text(5).as_deref(), Some("Springfield Waypoint"));
+        assert_eq!(text(12).as_deref(), Some("Springfield Heliport"));
+
+        let json = serde_json::to_string(&names.station(0, &stations[&0])).unwrap();
+        assert_eq!(
+            json,
+            r#"{"source":{"kind":"Base","id":24590},"text":"Springfield Valley"}"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn export_names_to_json() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let json = serde_json::to_value(NamedSave::new(&save)?).unwrap();
+
+        // The save is exported as before, with the names beside it
+        let names = &json["names"];
+        let town = Town::from_save(&save)?[0].generated_name();
+        assert_eq!(names["towns"]["0"]["text"].as_str(), town.as_deref());
+        assert_eq!(
+            names["stations"]["0"]["text"].as_str(),
+            Some(format!("{} Valley", town.unwrap()).as_str())
+        );
+        assert_eq!(names["presidents"]["0"]["text"], "B. McAlpine");
+        assert_eq!(names["vehicles"]["1"]["text"], "Train #1");
+        let read: Save = serde_json::from_value(json).unwrap();
+        assert_eq!(read.chunks.len(), save.chunks.len());
+
+        // Saves in the table layout too
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let names = SaveNames::from_save(&save)?;
+        assert!(!names.towns.is_empty());
+        assert!(names.companies.is_some());
+
+        // Saves the models can't read still export, without the names
+        let mut save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        save.chunks.retain(|chunk| chunk.tag != *b"CITY");
+        let names = NamedSave::new(&save)?.names;
+        assert!(names.towns.is_empty());
+        assert!(names.companies.is_some());
+        save.version = 100;
+        assert_eq!(NamedSave::new(&save)?.names.vehicles, None);
+
+        Ok(())
+    }
+}
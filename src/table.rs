@@ -0,0 +1,771 @@
+//! Maps rows of table chunks to Rust types with serde.
+//!
+//! Any type deriving `Deserialize` can be read from the `(key, value)` pairs of a
+//! `ChTableElement` with [`from_table`], and any type deriving `Serialize` can be turned back
+//! into them with [`to_table`] or [`update_table`]:
+//!
+//! ```
+//! # use ottd_map_parser::{chtable::TableData, table::from_table};
+//! #[derive(serde::Deserialize)]
+//! struct Maps {
+//!     dim_x: u32,
+//!     dim_y: u32,
+//! }
+//!
+//! let data = vec![
+//!     ("dim_x".to_string(), TableData::UInt32(256)),
+//!     ("dim_y".to_string(), TableData::UInt32(512)),
+//! ];
+//! let maps: Maps = from_table(&data).unwrap();
+//! assert_eq!((maps.dim_x, maps.dim_y), (256, 512));
+//! ```
+//!
+//! Integers convert to any integer type they fit in, lists map to `Vec`s or arrays, and
+//! struct fields map to a `Vec` of structs, a single struct when there's exactly one row, or an
+//! `Option` of a struct when there can be none. Keys the type doesn't have are ignored when reading.
+
+use serde::de::{
+    value::{MapDeserializer, SeqDeserializer},
+    Deserialize, Deserializer, IntoDeserializer, Visitor,
+};
+use serde::ser::{self, Impossible, Serialize, Serializer};
+
+use crate::chtable::{field_mut, TableData, TableDataType, TableHeaderProperty, TableStruct};
+use crate::error::{Error, Result};
+
+/// Reads a type from the values of a table element or a row of a struct
+pub fn from_table<'de, T: Deserialize<'de>>(data: &'de [(String, TableData)]) -> Result<T> {
+    T::deserialize(RowDeserializer { row: data })
+}
+
+/// Writes a type as the values of a table element, in the order and with the types of `header`.
+/// Every key in the header has to be in the type, which can't have any others.
+pub fn to_table<T: Serialize + ?Sized>(
+    value: &T,
+    header: &[TableHeaderProperty],
+) -> Result<Vec<(String, TableData)>> {
+    coerce_row(into_row(value.serialize(ValueSerializer)?)?, header)
+}
+
+/// Writes a type as `(key, value)` pairs with the types serde gives them, for layouts that
+/// convert the values themselves like `ChunkSchema::write`
+pub fn to_row<T: Serialize + ?Sized>(value: &T) -> Result<Vec<(String, TableData)>> {
+    into_row(value.serialize(ValueSerializer)?)
+}
+
+/// Replaces the values in an existing element with the fields of a type, keeping any
+/// values the type doesn't have. Values are converted to the types in `header`.
+pub fn update_table<T: Serialize + ?Sized>(
+    data: &mut [(String, TableData)],
+    header: &[TableHeaderProperty],
+    value: &T,
+) -> Result<()> {
+    for (key, value) in into_row(value.serialize(ValueSerializer)?)? {
+        let prop = header
+            .iter()
+            .find(|prop| prop.key() == key)
+            .ok_or_else(|| unknown_key(&key))?;
+        *field_mut(data, &key).ok_or_else(|| unknown_key(&key))? = coerce(value, prop)?;
+    }
+    Ok(())
+}
+
+fn unknown_key(key: &str) -> Error {
+    Error::Malformed {
+        message: format!("table has no key {key}"),
+    }
+}
+
+fn into_row(value: TableData) -> Result<Vec<(String, TableData)>> {
+    match value {
+        TableData::Struct(TableStruct { mut data }) if data.len() == 1 => Ok(data.remove(0)),
+        _ => Err(Error::Malformed {
+            message: "only structs and maps can be written as a table".to_string(),
+        }),
+    }
+}
+
+fn coerce_row(
+    mut row: Vec<(String, TableData)>,
+    header: &[TableHeaderProperty],
+) -> Result<Vec<(String, TableData)>> {
+    let data = header
+        .iter()
+        .map(|prop| {
+            let index = row
+                .iter()
+                .position(|(key, _)| key == prop.key())
+                .ok_or_else(|| Error::Malformed {
+                    message: format!("missing table key {}", prop.key()),
+                })?;
+            let (key, value) = row.remove(index);
+            Ok((key, coerce(value, prop)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    match row.first() {
+        Some((key, _)) => Err(unknown_key(key)),
+        None => Ok(data),
+    }
+}
+
+/// Converts a value to the type the header says it's stored as
+pub(crate) fn coerce(value: TableData, prop: &TableHeaderProperty) -> Result<TableData> {
+    let key = prop.key();
+    let mismatch = || Error::Malformed {
+        message: format!("table key {key} can't be stored as {:?}", prop.data_type()),
+    };
+    let out_of_range = || Error::Malformed {
+        message: format!("value of table key {key} is out of range"),
+    };
+
+    fn int<T: TryFrom<i64>>(value: &TableData) -> Option<core::result::Result<T, ()>> {
+        value.as_i64().map(|x| T::try_from(x).map_err(|_| ()))
+    }
+    fn list<T: TryFrom<i64>>(value: &TableData) -> Option<core::result::Result<Vec<T>, ()>> {
+        // Empty sequences don't say what they hold
+        let values = match value {
+            TableData::Struct(list) if list.data.is_empty() => vec![],
+            value => value.as_i64_list()?,
+        };
+        Some(
+            values
+                .into_iter()
+                .map(|x| T::try_from(x).map_err(|_| ()))
+                .collect(),
+        )
+    }
+    macro_rules! convert {
+        ($f:ident, $variant:ident, $value:expr) => {
+            $f(&$value)
+                .ok_or_else(mismatch)?
+                .map(TableData::$variant)
+                .map_err(|_| out_of_range())
+        };
+    }
+
+    match prop.data_type() {
+        TableDataType::Int8 => convert!(int, Int8, value),
+        TableDataType::UInt8 => convert!(int, UInt8, value),
+        TableDataType::Int16 => convert!(int, Int16, value),
+        TableDataType::UInt16 => convert!(int, UInt16, value),
+        TableDataType::Int32 => convert!(int, Int32, value),
+        TableDataType::UInt32 => convert!(int, UInt32, value),
+        TableDataType::Int64 => convert!(int, Int64, value),
+        TableDataType::UInt64 => match value {
+            TableData::UInt64(x) => Ok(TableData::UInt64(x)),
+            other => convert!(int, UInt64, other),
+        },
+        TableDataType::StringId => convert!(int, StringId, value),
+        TableDataType::Str => match value {
+            TableData::Str(x) => Ok(TableData::Str(x)),
+            _ => Err(mismatch()),
+        },
+        TableDataType::Struct(header) => match value {
+            TableData::Struct(TableStruct { data }) => Ok(TableData::Struct(TableStruct {
+                data: data
+                    .into_iter()
+                    .map(|row| coerce_row(row, header))
+                    .collect::<Result<_>>()?,
+            })),
+            _ => Err(mismatch()),
+        },
+        TableDataType::Int8List => convert!(list, Int8List, value),
+        TableDataType::UInt8List => convert!(list, UInt8List, value),
+        TableDataType::Int16List => convert!(list, Int16List, value),
+        TableDataType::UInt16List => convert!(list, UInt16List, value),
+        TableDataType::Int32List => convert!(list, Int32List, value),
+        TableDataType::UInt32List => convert!(list, UInt32List, value),
+        TableDataType::Int64List => convert!(list, Int64List, value),
+        TableDataType::UInt64List => match value {
+            TableData::UInt64List(x) => Ok(TableData::UInt64List(x)),
+            other => convert!(list, UInt64List, other),
+        },
+        TableDataType::StringIdList => convert!(list, StringIdList, value),
+    }
+}
+
+/// Deserializes the values of an element or a row of a struct as a map
+struct RowDeserializer<'a> {
+    row: &'a [(String, TableData)],
+}
+
+impl<'de> Deserializer<'de> for RowDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_map(MapDeserializer::new(
+            self.row
+                .iter()
+                .map(|(key, value)| (key.as_str(), ValueDeserializer { value })),
+        ))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for RowDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct ValueDeserializer<'a> {
+    value: &'a TableData,
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        fn seq<'de, T, V>(list: &'de [T], visitor: V) -> Result<V::Value>
+        where
+            T: Copy + IntoDeserializer<'de, Error>,
+            V: Visitor<'de>,
+        {
+            visitor.visit_seq(SeqDeserializer::new(list.iter().copied()))
+        }
+
+        match self.value {
+            TableData::Int8(x) => visitor.visit_i8(*x),
+            TableData::UInt8(x) => visitor.visit_u8(*x),
+            TableData::Int16(x) => visitor.visit_i16(*x),
+            TableData::UInt16(x) => visitor.visit_u16(*x),
+            TableData::Int32(x) => visitor.visit_i32(*x),
+            TableData::UInt32(x) => visitor.visit_u32(*x),
+            TableData::Int64(x) => visitor.visit_i64(*x),
+            TableData::UInt64(x) => visitor.visit_u64(*x),
+            TableData::StringId(x) => visitor.visit_u16(*x),
+            TableData::Str(x) => visitor.visit_borrowed_str(x),
+            TableData::Struct(list) => visitor.visit_seq(SeqDeserializer::new(
+                list.data.iter().map(|row| RowDeserializer { row }),
+            )),
+            TableData::Int8List(list) => seq(list, visitor),
+            TableData::UInt8List(list) => seq(list, visitor),
+            TableData::Int16List(list) => seq(list, visitor),
+            TableData::UInt16List(list) => seq(list, visitor),
+            TableData::Int32List(list) => seq(list, visitor),
+            TableData::UInt32List(list) => seq(list, visitor),
+            TableData::Int64List(list) => seq(list, visitor),
+            TableData::UInt64List(list) => seq(list, visitor),
+            TableData::StringIdList(list) => seq(list, visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value.as_i64() {
+            Some(x) => visitor.visit_bool(x != 0),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            TableData::UInt8List(list) => visitor.visit_borrowed_bytes(list),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            TableData::Struct(list) if list.data.is_empty() => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            TableData::Struct(list) if list.data.len() == 1 => {
+                RowDeserializer { row: &list.data[0] }.deserialize_any(visitor)
+            }
+            TableData::Struct(list) => Err(Error::Malformed {
+                message: format!("expected one row in struct but found {}", list.data.len()),
+            }),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct enum identifier ignored_any
+    }
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::Malformed {
+        message: format!("{what} can't be stored in a table"),
+    }
+}
+
+/// Serializes a value to the table type closest to its Rust type, which `coerce` then
+/// converts to the type in the header
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+    type Ok = TableData;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = Impossible<TableData, Error>;
+    type SerializeMap = RowSerializer;
+    type SerializeStruct = RowSerializer;
+    type SerializeStructVariant = Impossible<TableData, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<TableData> {
+        Ok(TableData::UInt8(v as u8))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<TableData> {
+        Ok(TableData::Int8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<TableData> {
+        Ok(TableData::Int16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<TableData> {
+        Ok(TableData::Int32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<TableData> {
+        Ok(TableData::Int64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<TableData> {
+        Ok(TableData::UInt8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<TableData> {
+        Ok(TableData::UInt16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<TableData> {
+        Ok(TableData::UInt32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<TableData> {
+        Ok(TableData::UInt64(v))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<TableData> {
+        Err(unsupported("floats"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<TableData> {
+        Err(unsupported("floats"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<TableData> {
+        Ok(TableData::Str(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<TableData> {
+        Ok(TableData::Str(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<TableData> {
+        Ok(TableData::UInt8List(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<TableData> {
+        Ok(TableData::Struct(TableStruct { data: vec![] }))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<TableData> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<TableData> {
+        Err(unsupported("units"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<TableData> {
+        Err(unsupported("units"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<TableData> {
+        Err(unsupported("enums"))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<TableData> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<TableData> {
+        Err(unsupported("enums"))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Impossible<TableData, Error>> {
+        Err(unsupported("enums"))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<RowSerializer> {
+        Ok(RowSerializer {
+            row: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<RowSerializer> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Impossible<TableData, Error>> {
+        Err(unsupported("enums"))
+    }
+}
+
+struct SeqSerializer {
+    items: Vec<TableData>,
+}
+
+impl SeqSerializer {
+    fn finish(self) -> Result<TableData> {
+        macro_rules! list {
+            ($($variant:ident => $list:ident),*) => {
+                match self.items.first() {
+                    // Empty sequences are stored as an empty struct and converted to the right list later
+                    None => Ok(TableData::Struct(TableStruct { data: vec![] })),
+                    Some(TableData::Struct(_)) => {
+                        let mut data = vec![];
+                        for item in self.items {
+                            match item {
+                                TableData::Struct(list) => data.extend(list.data),
+                                _ => return Err(unsupported("mixed sequences")),
+                            }
+                        }
+                        Ok(TableData::Struct(TableStruct { data }))
+                    }
+                    $(Some(TableData::$variant(_)) => self
+                        .items
+                        .into_iter()
+                        .map(|item| match item {
+                            TableData::$variant(x) => Ok(x),
+                            _ => Err(unsupported("mixed sequences")),
+                        })
+                        .collect::<Result<_>>()
+                        .map(TableData::$list),)*
+                    Some(_) => Err(unsupported("sequences of strings or lists")),
+                }
+            };
+        }
+
+        list!(
+            Int8 => Int8List,
+            UInt8 => UInt8List,
+            Int16 => Int16List,
+            UInt16 => UInt16List,
+            Int32 => Int32List,
+            UInt32 => UInt32List,
+            Int64 => Int64List,
+            UInt64 => UInt64List,
+            StringId => StringIdList
+        )
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = TableData;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<TableData> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = TableData;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<TableData> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = TableData;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<TableData> {
+        self.finish()
+    }
+}
+
+/// Serializes a struct or map as a struct with a single row
+struct RowSerializer {
+    row: Vec<(String, TableData)>,
+    key: Option<String>,
+}
+
+impl ser::SerializeStruct for RowSerializer {
+    type Ok = TableData;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.row
+            .push((key.to_string(), value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<TableData> {
+        Ok(TableData::Struct(TableStruct {
+            data: vec![self.row],
+        }))
+    }
+}
+
+impl ser::SerializeMap for RowSerializer {
+    type Ok = TableData;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        match key.serialize(ValueSerializer)? {
+            TableData::Str(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(unsupported("maps with keys that aren't strings")),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| unsupported("values without keys"))?;
+        self.row.push((key, value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<TableData> {
+        ser::SerializeStruct::end(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use std::fs::File;
+
+    use crate::chtable::TableData;
+    use crate::error::Result;
+    use crate::save::{ChunkValue, Save};
+    use crate::table::{from_table, to_table, update_table};
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Stat {
+        old_max: u32,
+        new_max: u32,
+        old_act: u32,
+        new_act: u32,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct City {
+        xy: u32,
+        townnamegrfid: u32,
+        townnametype: u16,
+        townnameparts: u32,
+        name: String,
+        flags: u8,
+        statues: u16,
+        have_ratings: u16,
+        ratings: [i16; 15],
+        unwanted: Vec<i8>,
+        goal: Vec<u32>,
+        text: String,
+        time_until_rebuild: u16,
+        grow_counter: u16,
+        growth_rate: u16,
+        fund_buildings_months: u8,
+        road_build_months: u8,
+        exclusivity: u8,
+        exclusive_counter: u8,
+        larger_town: i8,
+        layout: u8,
+        psa_list: Vec<u32>,
+        supplied: Vec<Stat>,
+        received: Vec<Stat>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Growth {
+        growth_rate: u32,
+        supplied: Vec<Stat>,
+    }
+
+    #[test]
+    fn table_round_trip() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let Some(ChunkValue::ChTable { header, elements }) = save.get(b"CITY") else {
+            panic!("CITY isn't a table");
+        };
+        let data = &elements[0].data;
+
+        let city: City = from_table(data)?;
+        assert_eq!(city.ratings, [500; 15]);
+        assert_eq!(city.supplied.len(), 64);
+        assert_eq!(city.received[0].old_max, 0);
+
+        // Writing it back gives the same values with the same types
+        let written = to_table(&city, header)?;
+        assert_eq!(format!("{written:?}"), format!("{data:?}"));
+
+        let mut data = data.clone();
+        let mut growth: Growth = from_table(&data)?;
+        growth.growth_rate = 100;
+        growth.supplied[1].new_act = 7;
+        update_table(&mut data, header, &growth)?;
+        let city: City = from_table(&data)?;
+        assert_eq!((city.growth_rate, city.supplied[1].new_act), (100, 7));
+        assert!(matches!(
+            data.iter().find(|(k, _)| k == "growth_rate"),
+            Some((_, TableData::UInt16(100)))
+        ));
+
+        // Values that don't fit the type in the header are an error
+        growth.growth_rate = 0x10000;
+        assert!(update_table(&mut data, header, &growth).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn table_optional_struct() -> Result<()> {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Row {
+            a: bool,
+            b: Option<Stat>,
+            c: Option<Stat>,
+        }
+
+        let stat = vec![
+            ("old_max".to_string(), TableData::UInt32(1)),
+            ("new_max".to_string(), TableData::UInt32(2)),
+            ("old_act".to_string(), TableData::UInt32(3)),
+            ("new_act".to_string(), TableData::UInt32(4)),
+        ];
+        let data = vec![
+            ("a".to_string(), TableData::UInt8(1)),
+            (
+                "b".to_string(),
+                TableData::Struct(crate::chtable::TableStruct { data: vec![stat] }),
+            ),
+            (
+                "c".to_string(),
+                TableData::Struct(crate::chtable::TableStruct { data: vec![] }),
+            ),
+        ];
+
+        let row: Row = from_table(&data)?;
+        assert_eq!(
+            row,
+            Row {
+                a: true,
+                b: Some(Stat {
+                    old_max: 1,
+                    new_max: 2,
+                    old_act: 3,
+                    new_act: 4,
+                }),
+                c: None,
+            }
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,831 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::map::{RawTile, TileType};
+
+// Bit layouts follow OpenTTD's docs/landscape.html and the *_map.h accessors as of
+// OpenTTD 13. Bits a variant doesn't describe are left alone by `Tile::encode_into`.
+
+/// Road or tram type stored when a tile doesn't have that kind of road
+pub const INVALID_ROAD_TYPE: u8 = 0x3F;
+
+/// Get `len` bits of `x` starting at `start`, like OpenTTD's `GB`
+pub(crate) fn gb(x: impl Into<u32>, start: u32, len: u32) -> u32 {
+    (x.into() >> start) & ((1 << len) - 1)
+}
+
+/// Set `len` bits of `x` starting at `start`, like OpenTTD's `SB`
+pub(crate) fn sb(x: u32, start: u32, len: u32, value: impl Into<u32>) -> u32 {
+    let mask = ((1 << len) - 1) << start;
+    (x & !mask) | ((value.into() << start) & mask)
+}
+
+pub(crate) fn sb8(x: &mut u8, start: u32, len: u32, value: impl Into<u32>) {
+    *x = sb(*x as u32, start, len, value) as u8;
+}
+
+pub(crate) fn sb16(x: &mut u16, start: u32, len: u32, value: impl Into<u32>) {
+    *x = sb(*x as u32, start, len, value) as u16;
+}
+
+pub(crate) fn has_bit(x: impl Into<u32>, bit: u32) -> bool {
+    gb(x, bit, 1) == 1
+}
+
+pub(crate) fn invalid(what: &str, value: u32) -> Error {
+    Error::Malformed {
+        message: format!("invalid {what} {value}"),
+    }
+}
+
+macro_rules! raw_enum {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident = $value:literal),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+        pub enum $name {
+            $($variant = $value),*
+        }
+
+        impl $name {
+            pub fn from_raw(value: u32) -> $crate::error::Result<$name> {
+                match value {
+                    $($value => Ok($name::$variant),)*
+                    _ => Err($crate::tile::invalid(stringify!($name), value)),
+                }
+            }
+        }
+    };
+}
+pub(crate) use raw_enum;
+
+raw_enum!(
+    /// Ground of a clear tile
+    ClearGround {
+        Grass = 0,
+        Rough = 1,
+        Rocks = 2,
+        Fields = 3,
+        Snow = 4,
+        Desert = 5,
+    }
+);
+
+raw_enum!(
+    /// Ground under trees
+    TreeGround {
+        Grass = 0,
+        Rough = 1,
+        SnowDesert = 2,
+        Shore = 3,
+        RoughSnow = 4,
+    }
+);
+
+raw_enum!(
+    /// What kind of water a tile has, also stored for tiles that can be built on water
+    WaterClass {
+        Sea = 0,
+        Canal = 1,
+        River = 2,
+        Invalid = 3,
+    }
+);
+
+raw_enum!(
+    StationType {
+        Rail = 0,
+        Airport = 1,
+        Truck = 2,
+        Bus = 3,
+        Oilrig = 4,
+        Dock = 5,
+        Buoy = 6,
+        Waypoint = 7,
+    }
+);
+
+raw_enum!(
+    /// What goes through a tunnel or over a bridge
+    TransportType {
+        Rail = 0,
+        Road = 1,
+        Water = 2,
+    }
+);
+
+/// A tile decoded into what it represents in game.
+///
+/// Directions are stored as OpenTTD's `DiagDirection` (0 = NE, 1 = SE, 2 = SW, 3 = NW),
+/// axes as 0 = X and 1 = Y, and track/road bits as OpenTTD's `TrackBits`/`RoadBits`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Tile {
+    Clear(ClearTile),
+    Rail(RailTile),
+    Road(RoadTile),
+    House(HouseTile),
+    Trees(TreeTile),
+    Station(StationTile),
+    Water(WaterTile),
+    Void,
+    Industry(IndustryTile),
+    TunnelBridge(TunnelBridgeTile),
+    Object(ObjectTile),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClearTile {
+    pub owner: u8,
+    pub ground: ClearGround,
+    /// 0-3, how much of the ground type covers the tile
+    pub density: u8,
+    pub counter: u8,
+    pub snow: bool,
+    /// Only used by fields
+    pub field_type: u8,
+    /// Industry that planted the fields
+    pub industry: u16,
+    /// Fence type on the NE, SE, SW and NW edges, only used by fields
+    pub fences: [u8; 4],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RailTile {
+    pub owner: u8,
+    pub rail_type: u8,
+    /// Ground around the track (grass, fences, snow, ...)
+    pub ground: u8,
+    pub kind: RailKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RailKind {
+    Track {
+        tracks: u8,
+        /// Reserved track, stored as track + 1 in bits 0..2 with bit 3 for the opposite track
+        reservation: u8,
+        signals: Option<Signals>,
+    },
+    Depot {
+        direction: u8,
+        depot: u16,
+        reserved: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signals {
+    /// Which of the 4 possible signals exist
+    pub present: u8,
+    /// Which of the signals are green
+    pub states: u8,
+    /// Signal type for the lower and upper track
+    pub types: [u8; 2],
+    /// Whether the signals on the lower and upper track are semaphores
+    pub semaphore: [bool; 2],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoadTile {
+    /// Owner of the road on normal road tiles, the rail on crossings and the depot on depots
+    pub owner: u8,
+    pub road_type: u8,
+    pub tram_type: u8,
+    pub tram_owner: u8,
+    pub snow_or_desert: bool,
+    pub kind: RoadKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoadKind {
+    Normal {
+        road_bits: u8,
+        tram_bits: u8,
+        /// One way road directions
+        disallowed: u8,
+        roadside: u8,
+        roadworks_counter: u8,
+        town: u16,
+    },
+    Crossing {
+        road_axis: u8,
+        reserved: bool,
+        barred: bool,
+        rail_type: u8,
+        road_owner: u8,
+        roadside: u8,
+        town: u16,
+    },
+    Depot {
+        direction: u8,
+        depot: u16,
+        road_owner: u8,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HouseTile {
+    pub town: u16,
+    pub house_type: u16,
+    pub random_bits: u8,
+    pub triggers: u8,
+    pub animation_frame: u8,
+    pub state: HouseState,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HouseState {
+    Building { stage: u8, counter: u8 },
+    Completed { age: u8 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeTile {
+    pub owner: u8,
+    pub tree_type: u8,
+    /// 1-4 trees on the tile
+    pub count: u8,
+    pub growth: u8,
+    pub ground: TreeGround,
+    pub density: u8,
+    pub counter: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StationTile {
+    pub owner: u8,
+    pub station: u16,
+    pub station_type: StationType,
+    /// Graphics of the tile which also says which part of the station it is
+    pub gfx: u8,
+    pub water_class: WaterClass,
+    pub docking: bool,
+    pub reserved: bool,
+    pub random_bits: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WaterTile {
+    pub owner: u8,
+    pub water_class: WaterClass,
+    pub docking: bool,
+    pub kind: WaterKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WaterKind {
+    Clear,
+    Coast,
+    /// Part 0 is the middle, 1 the lower end and 2 the upper end
+    Lock {
+        direction: u8,
+        part: u8,
+    },
+    /// Part 0 is the northern half and 1 the southern half
+    Depot {
+        axis: u8,
+        part: u8,
+        depot: u16,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndustryTile {
+    pub industry: u16,
+    pub gfx: u16,
+    pub completed: bool,
+    pub construction_stage: u8,
+    pub construction_counter: u8,
+    pub water_class: WaterClass,
+    pub random_bits: u8,
+    pub triggers: u8,
+    pub animation_frame: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TunnelBridgeTile {
+    pub owner: u8,
+    pub bridge: bool,
+    pub direction: u8,
+    pub transport_type: TransportType,
+    pub reserved: bool,
+    pub snow_or_desert: bool,
+    /// Only used by bridges
+    pub bridge_type: u8,
+    pub rail_type: u8,
+    pub road_type: u8,
+    pub tram_type: u8,
+    pub road_owner: u8,
+    pub tram_owner: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectTile {
+    pub owner: u8,
+    pub object: u32,
+    pub water_class: WaterClass,
+    pub random_bits: u8,
+    pub animation_frame: u8,
+}
+
+/// Tram owners are stored in 4 bits, with OWNER_TOWN used for OWNER_NONE
+fn tram_owner(raw: &RawTile) -> u8 {
+    match gb(raw.m3, 4, 4) as u8 {
+        0x0F => 0x10,
+        owner => owner,
+    }
+}
+
+fn set_tram_owner(raw: &mut RawTile, owner: u8) {
+    sb8(&mut raw.m3, 4, 4, if owner == 0x10 { 0x0F } else { owner });
+}
+
+impl Tile {
+    /// Decodes the meaning of a tile's bytes
+    pub fn decode(raw: &RawTile) -> Result<Tile> {
+        let tile_type = raw
+            .get_type()
+            .ok_or_else(|| invalid("tile type", gb(raw.tile_type, 4, 4)))?;
+        let owner = gb(raw.m1, 0, 5) as u8;
+
+        Ok(match tile_type {
+            TileType::Clear => Tile::Clear(ClearTile {
+                owner,
+                ground: ClearGround::from_raw(gb(raw.m5, 2, 3))?,
+                density: gb(raw.m5, 0, 2) as u8,
+                counter: gb(raw.m5, 5, 3) as u8,
+                snow: has_bit(raw.m3, 4),
+                field_type: gb(raw.m3, 0, 4) as u8,
+                industry: raw.m2,
+                fences: [
+                    gb(raw.m3, 5, 3) as u8,
+                    gb(raw.m4, 2, 3) as u8,
+                    gb(raw.m4, 5, 3) as u8,
+                    gb(raw.m6, 2, 3) as u8,
+                ],
+            }),
+            TileType::Railway => Tile::Rail(RailTile {
+                owner,
+                rail_type: gb(raw.m8, 0, 6) as u8,
+                ground: gb(raw.m4, 0, 4) as u8,
+                kind: match gb(raw.m5, 6, 2) {
+                    0 | 1 => RailKind::Track {
+                        tracks: gb(raw.m5, 0, 6) as u8,
+                        reservation: gb(raw.m2, 8, 4) as u8,
+                        signals: has_bit(raw.m5, 6).then(|| Signals {
+                            present: gb(raw.m3, 4, 4) as u8,
+                            states: gb(raw.m4, 4, 4) as u8,
+                            types: [gb(raw.m2, 0, 3) as u8, gb(raw.m2, 4, 3) as u8],
+                            semaphore: [has_bit(raw.m2, 3), has_bit(raw.m2, 7)],
+                        }),
+                    },
+                    3 => RailKind::Depot {
+                        direction: gb(raw.m5, 0, 2) as u8,
+                        depot: raw.m2,
+                        reserved: has_bit(raw.m5, 4),
+                    },
+                    kind => return Err(invalid("rail tile type", kind)),
+                },
+            }),
+            TileType::Road => Tile::Road(RoadTile {
+                owner,
+                road_type: gb(raw.m4, 0, 6) as u8,
+                tram_type: gb(raw.m8, 6, 6) as u8,
+                tram_owner: tram_owner(raw),
+                snow_or_desert: has_bit(raw.m7, 5),
+                kind: match gb(raw.m5, 6, 2) {
+                    0 => RoadKind::Normal {
+                        road_bits: gb(raw.m5, 0, 4) as u8,
+                        tram_bits: gb(raw.m3, 0, 4) as u8,
+                        disallowed: gb(raw.m5, 4, 2) as u8,
+                        roadside: gb(raw.m6, 3, 3) as u8,
+                        roadworks_counter: gb(raw.m7, 0, 4) as u8,
+                        town: raw.m2,
+                    },
+                    1 => RoadKind::Crossing {
+                        road_axis: gb(raw.m5, 0, 1) as u8,
+                        reserved: has_bit(raw.m5, 4),
+                        barred: has_bit(raw.m5, 5),
+                        rail_type: gb(raw.m8, 0, 6) as u8,
+                        road_owner: gb(raw.m7, 0, 5) as u8,
+                        roadside: gb(raw.m6, 3, 3) as u8,
+                        town: raw.m2,
+                    },
+                    2 => RoadKind::Depot {
+                        direction: gb(raw.m5, 0, 2) as u8,
+                        depot: raw.m2,
+                        road_owner: gb(raw.m7, 0, 5) as u8,
+                    },
+                    kind => return Err(invalid("road tile type", kind)),
+                },
+            }),
+            TileType::House => Tile::House(HouseTile {
+                town: raw.m2,
+                house_type: gb(raw.m8, 0, 12) as u16,
+                random_bits: raw.m1,
+                triggers: gb(raw.m3, 0, 5) as u8,
+                animation_frame: raw.m7,
+                state: if has_bit(raw.m3, 7) {
+                    HouseState::Completed { age: raw.m5 }
+                } else {
+                    HouseState::Building {
+                        stage: gb(raw.m5, 3, 2) as u8,
+                        counter: gb(raw.m5, 0, 3) as u8,
+                    }
+                },
+            }),
+            TileType::Trees => Tile::Trees(TreeTile {
+                owner,
+                tree_type: raw.m3,
+                count: gb(raw.m5, 6, 2) as u8 + 1,
+                growth: gb(raw.m5, 0, 3) as u8,
+                ground: TreeGround::from_raw(gb(raw.m2, 6, 3))?,
+                density: gb(raw.m2, 4, 2) as u8,
+                counter: gb(raw.m2, 0, 4) as u8,
+            }),
+            TileType::Station => Tile::Station(StationTile {
+                owner,
+                station: raw.m2,
+                station_type: StationType::from_raw(gb(raw.m6, 3, 3))?,
+                gfx: raw.m5,
+                water_class: WaterClass::from_raw(gb(raw.m1, 5, 2))?,
+                docking: has_bit(raw.m1, 7),
+                reserved: has_bit(raw.m6, 2),
+                random_bits: gb(raw.m3, 4, 4) as u8,
+            }),
+            TileType::Water => Tile::Water(WaterTile {
+                owner,
+                water_class: WaterClass::from_raw(gb(raw.m1, 5, 2))?,
+                docking: has_bit(raw.m1, 7),
+                kind: match gb(raw.m5, 4, 4) {
+                    0 if has_bit(raw.m5, 0) => WaterKind::Coast,
+                    0 => WaterKind::Clear,
+                    1 => WaterKind::Lock {
+                        direction: gb(raw.m5, 0, 2) as u8,
+                        part: gb(raw.m5, 2, 2) as u8,
+                    },
+                    8 => WaterKind::Depot {
+                        axis: gb(raw.m5, 1, 1) as u8,
+                        part: gb(raw.m5, 0, 1) as u8,
+                        depot: raw.m2,
+                    },
+                    kind => return Err(invalid("water tile type", kind)),
+                },
+            }),
+            TileType::Void => Tile::Void,
+            TileType::Industry => Tile::Industry(IndustryTile {
+                industry: raw.m2,
+                gfx: raw.m5 as u16 | (gb(raw.m6, 2, 1) as u16) << 8,
+                completed: has_bit(raw.m1, 7),
+                construction_stage: gb(raw.m1, 0, 2) as u8,
+                construction_counter: gb(raw.m1, 2, 2) as u8,
+                water_class: WaterClass::from_raw(gb(raw.m1, 5, 2))?,
+                random_bits: raw.m3,
+                triggers: gb(raw.m6, 3, 3) as u8,
+                animation_frame: raw.m7,
+            }),
+            TileType::TunnelBridge => Tile::TunnelBridge(TunnelBridgeTile {
+                owner,
+                bridge: has_bit(raw.m5, 7),
+                direction: gb(raw.m5, 0, 2) as u8,
+                transport_type: TransportType::from_raw(gb(raw.m5, 2, 2))?,
+                reserved: has_bit(raw.m5, 4),
+                snow_or_desert: has_bit(raw.m7, 5),
+                bridge_type: gb(raw.m6, 2, 4) as u8,
+                rail_type: gb(raw.m8, 0, 6) as u8,
+                road_type: gb(raw.m4, 0, 6) as u8,
+                tram_type: gb(raw.m8, 6, 6) as u8,
+                road_owner: gb(raw.m7, 0, 5) as u8,
+                tram_owner: tram_owner(raw),
+            }),
+            TileType::Object => Tile::Object(ObjectTile {
+                owner,
+                object: raw.m2 as u32 | (raw.m5 as u32) << 16,
+                water_class: WaterClass::from_raw(gb(raw.m1, 5, 2))?,
+                random_bits: raw.m3,
+                animation_frame: raw.m7,
+            }),
+        })
+    }
+
+    pub fn tile_type(&self) -> TileType {
+        match self {
+            Tile::Clear(_) => TileType::Clear,
+            Tile::Rail(_) => TileType::Railway,
+            Tile::Road(_) => TileType::Road,
+            Tile::House(_) => TileType::House,
+            Tile::Trees(_) => TileType::Trees,
+            Tile::Station(_) => TileType::Station,
+            Tile::Water(_) => TileType::Water,
+            Tile::Void => TileType::Void,
+            Tile::Industry(_) => TileType::Industry,
+            Tile::TunnelBridge(_) => TileType::TunnelBridge,
+            Tile::Object(_) => TileType::Object,
+        }
+    }
+
+    /// Encodes the tile into a new set of bytes, with a height of 0
+    pub fn to_raw(&self) -> RawTile {
+        let mut raw = RawTile::default();
+        self.encode_into(&mut raw);
+        raw
+    }
+
+    /// Writes the tile into existing bytes, keeping the height, tropic zone, bridge above
+    /// and any bits this tile type doesn't use
+    pub fn encode_into(&self, raw: &mut RawTile) {
+        raw.set_type(self.tile_type());
+
+        match self {
+            Tile::Clear(clear) => {
+                sb8(&mut raw.m1, 0, 5, clear.owner);
+                sb8(&mut raw.m5, 2, 3, clear.ground as u8);
+                sb8(&mut raw.m5, 0, 2, clear.density);
+                sb8(&mut raw.m5, 5, 3, clear.counter);
+                sb8(&mut raw.m3, 4, 1, clear.snow);
+                sb8(&mut raw.m3, 0, 4, clear.field_type);
+                raw.m2 = clear.industry;
+                sb8(&mut raw.m3, 5, 3, clear.fences[0]);
+                sb8(&mut raw.m4, 2, 3, clear.fences[1]);
+                sb8(&mut raw.m4, 5, 3, clear.fences[2]);
+                sb8(&mut raw.m6, 2, 3, clear.fences[3]);
+            }
+            Tile::Rail(rail) => {
+                sb8(&mut raw.m1, 0, 5, rail.owner);
+                sb16(&mut raw.m8, 0, 6, rail.rail_type);
+                sb8(&mut raw.m4, 0, 4, rail.ground);
+                match &rail.kind {
+                    RailKind::Track {
+                        tracks,
+                        reservation,
+                        signals,
+                    } => {
+                        sb8(&mut raw.m5, 6, 2, signals.is_some());
+                        sb8(&mut raw.m5, 0, 6, *tracks);
+                        sb16(&mut raw.m2, 8, 4, *reservation);
+                        if let Some(signals) = signals {
+                            sb8(&mut raw.m3, 4, 4, signals.present);
+                            sb8(&mut raw.m4, 4, 4, signals.states);
+                            sb16(&mut raw.m2, 0, 3, signals.types[0]);
+                            sb16(&mut raw.m2, 4, 3, signals.types[1]);
+                            sb16(&mut raw.m2, 3, 1, signals.semaphore[0]);
+                            sb16(&mut raw.m2, 7, 1, signals.semaphore[1]);
+                        }
+                    }
+                    RailKind::Depot {
+                        direction,
+                        depot,
+                        reserved,
+                    } => {
+                        sb8(&mut raw.m5, 6, 2, 3u8);
+                        sb8(&mut raw.m5, 0, 2, *direction);
+                        sb8(&mut raw.m5, 4, 1, *reserved);
+                        raw.m2 = *depot;
+                    }
+                }
+            }
+            Tile::Road(road) => {
+                sb8(&mut raw.m1, 0, 5, road.owner);
+                sb8(&mut raw.m4, 0, 6, road.road_type);
+                sb16(&mut raw.m8, 6, 6, road.tram_type);
+                set_tram_owner(raw, road.tram_owner);
+                sb8(&mut raw.m7, 5, 1, road.snow_or_desert);
+                match &road.kind {
+                    RoadKind::Normal {
+                        road_bits,
+                        tram_bits,
+                        disallowed,
+                        roadside,
+                        roadworks_counter,
+                        town,
+                    } => {
+                        sb8(&mut raw.m5, 6, 2, 0u8);
+                        sb8(&mut raw.m5, 0, 4, *road_bits);
+                        sb8(&mut raw.m3, 0, 4, *tram_bits);
+                        sb8(&mut raw.m5, 4, 2, *disallowed);
+                        sb8(&mut raw.m6, 3, 3, *roadside);
+                        sb8(&mut raw.m7, 0, 4, *roadworks_counter);
+                        raw.m2 = *town;
+                    }
+                    RoadKind::Crossing {
+                        road_axis,
+                        reserved,
+                        barred,
+                        rail_type,
+                        road_owner,
+                        roadside,
+                        town,
+                    } => {
+                        sb8(&mut raw.m5, 6, 2, 1u8);
+                        sb8(&mut raw.m5, 0, 1, *road_axis);
+                        sb8(&mut raw.m5, 4, 1, *reserved);
+                        sb8(&mut raw.m5, 5, 1, *barred);
+                        sb16(&mut raw.m8, 0, 6, *rail_type);
+                        sb8(&mut raw.m7, 0, 5, *road_owner);
+                        sb8(&mut raw.m6, 3, 3, *roadside);
+                        raw.m2 = *town;
+                    }
+                    RoadKind::Depot {
+                        direction,
+                        depot,
+                        road_owner,
+                    } => {
+                        sb8(&mut raw.m5, 6, 2, 2u8);
+                        sb8(&mut raw.m5, 0, 2, *direction);
+                        sb8(&mut raw.m7, 0, 5, *road_owner);
+                        raw.m2 = *depot;
+                    }
+                }
+            }
+            Tile::House(house) => {
+                raw.m2 = house.town;
+                sb16(&mut raw.m8, 0, 12, house.house_type);
+                raw.m1 = house.random_bits;
+                sb8(&mut raw.m3, 0, 5, house.triggers);
+                raw.m7 = house.animation_frame;
+                match house.state {
+                    HouseState::Completed { age } => {
+                        sb8(&mut raw.m3, 7, 1, true);
+                        raw.m5 = age;
+                    }
+                    HouseState::Building { stage, counter } => {
+                        sb8(&mut raw.m3, 7, 1, false);
+                        sb8(&mut raw.m5, 3, 2, stage);
+                        sb8(&mut raw.m5, 0, 3, counter);
+                    }
+                }
+            }
+            Tile::Trees(trees) => {
+                sb8(&mut raw.m1, 0, 5, trees.owner);
+                raw.m3 = trees.tree_type;
+                sb8(&mut raw.m5, 6, 2, trees.count.saturating_sub(1));
+                sb8(&mut raw.m5, 0, 3, trees.growth);
+                sb16(&mut raw.m2, 6, 3, trees.ground as u8);
+                sb16(&mut raw.m2, 4, 2, trees.density);
+                sb16(&mut raw.m2, 0, 4, trees.counter);
+            }
+            Tile::Station(station) => {
+                sb8(&mut raw.m1, 0, 5, station.owner);
+                raw.m2 = station.station;
+                sb8(&mut raw.m6, 3, 3, station.station_type as u8);
+                raw.m5 = station.gfx;
+                sb8(&mut raw.m1, 5, 2, station.water_class as u8);
+                sb8(&mut raw.m1, 7, 1, station.docking);
+                sb8(&mut raw.m6, 2, 1, station.reserved);
+                sb8(&mut raw.m3, 4, 4, station.random_bits);
+            }
+            Tile::Water(water) => {
+                sb8(&mut raw.m1, 0, 5, water.owner);
+                sb8(&mut raw.m1, 5, 2, water.water_class as u8);
+                sb8(&mut raw.m1, 7, 1, water.docking);
+                match water.kind {
+                    WaterKind::Clear => {
+                        sb8(&mut raw.m5, 4, 4, 0u8);
+                        sb8(&mut raw.m5, 0, 1, false);
+                    }
+                    WaterKind::Coast => {
+                        sb8(&mut raw.m5, 4, 4, 0u8);
+                        sb8(&mut raw.m5, 0, 1, true);
+                    }
+                    WaterKind::Lock { direction, part } => {
+                        sb8(&mut raw.m5, 4, 4, 1u8);
+                        sb8(&mut raw.m5, 0, 2, direction);
+                        sb8(&mut raw.m5, 2, 2, part);
+                    }
+                    WaterKind::Depot { axis, part, depot } => {
+                        sb8(&mut raw.m5, 4, 4, 8u8);
+                        sb8(&mut raw.m5, 1, 1, axis);
+                        sb8(&mut raw.m5, 0, 1, part);
+                        raw.m2 = depot;
+                    }
+                }
+            }
+            Tile::Void => {}
+            Tile::Industry(industry) => {
+                raw.m2 = industry.industry;
+                raw.m5 = industry.gfx as u8;
+                sb8(&mut raw.m6, 2, 1, (industry.gfx >> 8) as u8);
+                sb8(&mut raw.m1, 7, 1, industry.completed);
+                sb8(&mut raw.m1, 0, 2, industry.construction_stage);
+                sb8(&mut raw.m1, 2, 2, industry.construction_counter);
+                sb8(&mut raw.m1, 5, 2, industry.water_class as u8);
+                raw.m3 = industry.random_bits;
+                sb8(&mut raw.m6, 3, 3, industry.triggers);
+                raw.m7 = industry.animation_frame;
+            }
+            Tile::TunnelBridge(tunnel_bridge) => {
+                sb8(&mut raw.m1, 0, 5, tunnel_bridge.owner);
+                sb8(&mut raw.m5, 7, 1, tunnel_bridge.bridge);
+                sb8(&mut raw.m5, 0, 2, tunnel_bridge.direction);
+                sb8(&mut raw.m5, 2, 2, tunnel_bridge.transport_type as u8);
+                sb8(&mut raw.m5, 4, 1, tunnel_bridge.reserved);
+                sb8(&mut raw.m7, 5, 1, tunnel_bridge.snow_or_desert);
+                sb8(&mut raw.m6, 2, 4, tunnel_bridge.bridge_type);
+                sb16(&mut raw.m8, 0, 6, tunnel_bridge.rail_type);
+                sb8(&mut raw.m4, 0, 6, tunnel_bridge.road_type);
+                sb16(&mut raw.m8, 6, 6, tunnel_bridge.tram_type);
+                sb8(&mut raw.m7, 0, 5, tunnel_bridge.road_owner);
+                set_tram_owner(raw, tunnel_bridge.tram_owner);
+            }
+            Tile::Object(object) => {
+                sb8(&mut raw.m1, 0, 5, object.owner);
+                raw.m2 = object.object as u16;
+                raw.m5 = (object.object >> 16) as u8;
+                sb8(&mut raw.m1, 5, 2, object.water_class as u8);
+                raw.m3 = object.random_bits;
+                raw.m7 = object.animation_frame;
+            }
+        }
+    }
+}
+
+impl RawTile {
+    /// Decodes what the tile represents, see [`Tile::decode`]
+    pub fn decode(&self) -> Result<Tile> {
+        Tile::decode(self)
+    }
+
+    /// Replaces the tile with one of a possibly different type, keeping only its height,
+    /// tropic zone and any bridge above
+    pub fn replace(&mut self, tile: &Tile) {
+        let mut new = tile.to_raw();
+        new.height = self.height;
+        new.tile_type = (new.tile_type & 0xF0) | (self.tile_type & 0x0F);
+        *self = new;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::error::Result;
+    use crate::map::{RawTile, TileMap, TileType};
+    use crate::save::Save;
+    use crate::tile::{ClearGround, ClearTile, RailKind, RailTile, Tile};
+
+    #[test]
+    fn decode_tiles() -> Result<()> {
+        for path in ["tests/tiny.sav", "tests/TinyVanillaTest.sav"] {
+            let save = Save::from_reader(&mut File::open(path)?)?;
+            let map = TileMap::from_save(&save)?;
+
+            for raw in &map.tiles {
+                let tile = raw.decode()?;
+                assert_eq!(Some(tile.tile_type()), raw.get_type());
+
+                // Writing the tile back over its bytes doesn't change anything
+                let mut encoded = *raw;
+                tile.encode_into(&mut encoded);
+                assert_eq!(&encoded, raw);
+
+                // Every field survives encoding into new bytes
+                assert_eq!(tile.to_raw().decode()?, tile);
+            }
+
+            assert_eq!(map.tile(63, 63).unwrap().decode()?, Tile::Void);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_tiles() -> Result<()> {
+        let rail = Tile::Rail(RailTile {
+            owner: 1,
+            rail_type: 2,
+            ground: 0,
+            kind: RailKind::Depot {
+                direction: 3,
+                depot: 500,
+                reserved: true,
+            },
+        });
+        let raw = rail.to_raw();
+        assert_eq!(raw.get_type(), Some(TileType::Railway));
+        assert_eq!((raw.m1, raw.m2, raw.m5, raw.m8), (1, 500, 0b1101_0011, 2));
+        assert_eq!(raw.decode()?, rail);
+
+        // Height and unrelated bits are kept
+        let mut raw = RawTile {
+            height: 4,
+            ..Default::default()
+        };
+        raw.set_tropic_zone(2);
+        let clear = Tile::Clear(ClearTile {
+            owner: 0x10,
+            ground: ClearGround::Rough,
+            density: 3,
+            counter: 0,
+            snow: false,
+            field_type: 0,
+            industry: 0,
+            fences: [0; 4],
+        });
+        clear.encode_into(&mut raw);
+        assert_eq!((raw.height, raw.tropic_zone()), (4, 2));
+        assert_eq!(raw.decode()?, clear);
+
+        Ok(())
+    }
+}
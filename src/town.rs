@@ -0,0 +1,412 @@
+use serde::{Deserialize, Serialize};
+
+use crate::chtable::{field, field_mut, TableData};
+use crate::error::{Error, Result};
+use crate::jgr::SLXI;
+use crate::save::{ChArrayElement, Chunk, ChunkValue, LazySave, Save};
+use crate::schema::ChunkSchema;
+use crate::townname::TownNameStyle;
+
+/// Cargo a town supplied or received last month and this month
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CargoStat {
+    pub old_max: u32,
+    pub new_max: u32,
+    pub old_act: u32,
+    pub new_act: u32,
+}
+
+/// A town from the `CITY` chunk, read from either the array layout of older and JGR
+/// saves or the table layout of OpenTTD 13 and later.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Town {
+    /// Index of the town in the pool, which other chunks use to refer to it
+    pub index: u32,
+    /// Tile of the town centre
+    pub xy: u32,
+    pub townnamegrfid: u32,
+    pub townnametype: u16,
+    pub townnameparts: u32,
+    /// Custom name, empty if the name is generated from `townnameparts`
+    pub name: String,
+    pub flags: u8,
+    /// Companies that have built a statue, one bit per company
+    pub statues: u16,
+    /// Companies that have a rating, one bit per company
+    pub have_ratings: u16,
+    /// Rating of each company, from -1000 to 1000
+    pub ratings: Vec<i16>,
+    /// Months each company is unwanted in the town for after bribing
+    pub unwanted: Vec<i8>,
+    /// Cargo needed per town effect to grow
+    pub goal: Vec<u32>,
+    pub text: String,
+    pub time_until_rebuild: u16,
+    pub grow_counter: u16,
+    pub growth_rate: u16,
+    pub fund_buildings_months: u8,
+    pub road_build_months: u8,
+    pub exclusivity: u8,
+    pub exclusive_counter: u8,
+    pub larger_town: i8,
+    pub layout: u8,
+    pub psa_list: Vec<u32>,
+    /// Cargo supplied per cargo type
+    pub supplied: Vec<CargoStat>,
+    /// Cargo received per town effect
+    pub received: Vec<CargoStat>,
+}
+
+impl Town {
+    /// The x and y of the town centre
+    pub fn location(&self, dim_x: u32) -> (u32, u32) {
+        (self.xy % dim_x, self.xy / dim_x)
+    }
+
+    /// The name generated from `townnameparts`, None for towns using a NewGRF's names or a
+    /// style that hasn't been ported. It's only shown in game when `name` is empty.
+    pub fn generated_name(&self) -> Option<String> {
+        match self.townnamegrfid {
+            0 => {
+                TownNameStyle::from_town_name_type(self.townnametype)?.generate(self.townnameparts)
+            }
+            _ => None,
+        }
+    }
+
+    /// Gives the town the generated name for a new seed, removing any custom name
+    pub fn reseed(&mut self, parts: u32) {
+        self.townnameparts = parts;
+        self.name.clear();
+    }
+
+    pub fn from_save(save: &Save) -> Result<Vec<Town>> {
+        let chunk = save
+            .get(b"CITY")
+            .ok_or(Error::MissingChunk { tag: *b"CITY" })?;
+        Town::from_chunk(chunk, save.base_version(), &SLXI::from_save(save)?)
+    }
+
+    pub fn from_lazy_save(save: &LazySave) -> Result<Vec<Town>> {
+        let chunk = save
+            .get(b"CITY")
+            .ok_or(Error::MissingChunk { tag: *b"CITY" })?
+            .decode()?;
+        Town::from_chunk(
+            &chunk.value,
+            save.base_version(),
+            &SLXI::from_lazy_save(save)?,
+        )
+    }
+
+    /// Parses every town in the contents of the `CITY` chunk of a save with the given version.
+    /// `slxi` is only needed for the array layout, where JGR saves add fields.
+    pub fn from_chunk(chunk: &ChunkValue, version: u16, slxi: &SLXI) -> Result<Vec<Town>> {
+        let towns = match chunk {
+            ChunkValue::ChArray { elements } => {
+                let schema = ChunkSchema::find(*b"CITY", version)?;
+                elements
+                    .iter()
+                    .enumerate()
+                    // Empty elements are gaps in the pool
+                    .filter(|(_, element)| !element.data.is_empty())
+                    .map(|(index, element)| {
+                        let (row, _) = schema.read(&element.data, version, slxi)?;
+                        Town::from_row(index as u32, &row)
+                    })
+                    .collect::<Result<Vec<Town>>>()
+            }
+            ChunkValue::ChTable { elements, .. } => elements
+                .iter()
+                .enumerate()
+                .filter(|(_, element)| !element.data.is_empty())
+                .map(|(index, element)| Town::from_row(index as u32, &element.data))
+                .collect(),
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        towns.map_err(|e| e.in_chunk(*b"CITY", 0))
+    }
+
+    /// Reads a town from a table element, or an array element read with its schema
+    fn from_row(index: u32, data: &[(String, TableData)]) -> Result<Town> {
+        let int = |key: &str| -> Result<i64> {
+            field(data, key)
+                .and_then(TableData::as_i64)
+                .ok_or_else(|| missing(key))
+        };
+        let list = |key: &str| -> Result<Vec<i64>> {
+            field(data, key)
+                .and_then(TableData::as_i64_list)
+                .ok_or_else(|| missing(key))
+        };
+        let string = |key: &str| -> Result<String> {
+            field(data, key)
+                .and_then(TableData::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| missing(key))
+        };
+        let stats = |key: &str| -> Result<Vec<CargoStat>> {
+            match field(data, key) {
+                Some(TableData::Struct(list)) => list
+                    .data
+                    .iter()
+                    .map(|row| {
+                        let int = |key: &str| {
+                            field(row, key)
+                                .and_then(TableData::as_i64)
+                                .map(|x| x as u32)
+                                .ok_or_else(|| missing(key))
+                        };
+                        Ok(CargoStat {
+                            old_max: int("old_max")?,
+                            new_max: int("new_max")?,
+                            old_act: int("old_act")?,
+                            new_act: int("new_act")?,
+                        })
+                    })
+                    .collect(),
+                _ => Err(missing(key)),
+            }
+        };
+
+        Ok(Town {
+            index,
+            xy: int("xy")? as u32,
+            townnamegrfid: int("townnamegrfid")? as u32,
+            townnametype: int("townnametype")? as u16,
+            townnameparts: int("townnameparts")? as u32,
+            name: string("name")?,
+            flags: int("flags")? as u8,
+            statues: int("statues")? as u16,
+            have_ratings: int("have_ratings")? as u16,
+            ratings: list("ratings")?.into_iter().map(|x| x as i16).collect(),
+            unwanted: list("unwanted")?.into_iter().map(|x| x as i8).collect(),
+            goal: list("goal")?.into_iter().map(|x| x as u32).collect(),
+            // Saves before version 168 don't have it
+            text: field(data, "text")
+                .and_then(TableData::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            time_until_rebuild: int("time_until_rebuild")? as u16,
+            grow_counter: int("grow_counter")? as u16,
+            growth_rate: int("growth_rate")? as u16,
+            fund_buildings_months: int("fund_buildings_months")? as u8,
+            road_build_months: int("road_build_months")? as u8,
+            exclusivity: int("exclusivity")? as u8,
+            exclusive_counter: int("exclusive_counter")? as u8,
+            larger_town: int("larger_town")? as i8,
+            layout: int("layout")? as u8,
+            psa_list: list("psa_list")?.into_iter().map(|x| x as u32).collect(),
+            supplied: stats("supplied")?,
+            received: stats("received")?,
+        })
+    }
+
+    pub fn write_to_save(towns: &[Town], save: &mut Save) -> Result<()> {
+        let slxi = SLXI::from_save(save)?;
+        let mut chunk = save
+            .get(b"CITY")
+            .ok_or(Error::MissingChunk { tag: *b"CITY" })?
+            .clone();
+        Town::write_to_chunk(towns, &mut chunk, save.base_version(), &slxi)?;
+        save.set(Chunk {
+            tag: *b"CITY",
+            value: chunk,
+        });
+        Ok(())
+    }
+
+    pub fn write_to_lazy_save(towns: &[Town], save: &mut LazySave) -> Result<()> {
+        let slxi = SLXI::from_lazy_save(save)?;
+        let mut chunk = save
+            .get(b"CITY")
+            .ok_or(Error::MissingChunk { tag: *b"CITY" })?
+            .decode()?;
+        Town::write_to_chunk(towns, &mut chunk.value, save.base_version(), &slxi)?;
+        save.set(&chunk)
+    }
+
+    /// Writes the towns back into the contents of the `CITY` chunk in the layout it already has.
+    ///
+    /// Each town replaces the element at its index, and fields the model doesn't know about are
+    /// kept. Towns can't be added or removed this way since other chunks refer to them.
+    pub fn write_to_chunk(
+        towns: &[Town],
+        chunk: &mut ChunkValue,
+        version: u16,
+        slxi: &SLXI,
+    ) -> Result<()> {
+        let result = match chunk {
+            ChunkValue::ChArray { elements } => {
+                ChunkSchema::find(*b"CITY", version).and_then(|schema| {
+                    towns.iter().try_for_each(|town| {
+                        let element = existing(elements, town.index, |e| e.data.is_empty())?;
+                        let (mut row, len) = schema.read(&element.data, version, slxi)?;
+                        // Anything after the fields we know about is kept as is
+                        let rest = element.data[len..].to_vec();
+                        town.update_row(&mut row)?;
+
+                        let mut data = schema.write(&row, version, slxi)?;
+                        data.extend(rest);
+                        *element = ChArrayElement { data };
+                        Ok(())
+                    })
+                })
+            }
+            ChunkValue::ChTable { elements, .. } => towns.iter().try_for_each(|town| {
+                let element = existing(elements, town.index, |e| e.data.is_empty())?;
+                town.update_row(&mut element.data)
+            }),
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        result.map_err(|e| e.in_chunk(*b"CITY", 0))
+    }
+
+    /// Replaces the values of a table element, or an array element read with its schema
+    fn update_row(&self, data: &mut [(String, TableData)]) -> Result<()> {
+        let mut int = |key: &str, value: i64| {
+            field_mut(data, key)
+                .map(|v| v.set_int(value))
+                .filter(|&set| set)
+                .map(|_| ())
+                .ok_or_else(|| missing(key))
+        };
+        int("xy", self.xy as i64)?;
+        int("townnamegrfid", self.townnamegrfid as i64)?;
+        int("townnametype", self.townnametype as i64)?;
+        int("townnameparts", self.townnameparts as i64)?;
+        int("flags", self.flags as i64)?;
+        int("statues", self.statues as i64)?;
+        int("have_ratings", self.have_ratings as i64)?;
+        int("time_until_rebuild", self.time_until_rebuild as i64)?;
+        int("grow_counter", self.grow_counter as i64)?;
+        int("growth_rate", self.growth_rate as i64)?;
+        int("fund_buildings_months", self.fund_buildings_months as i64)?;
+        int("road_build_months", self.road_build_months as i64)?;
+        int("exclusivity", self.exclusivity as i64)?;
+        int("exclusive_counter", self.exclusive_counter as i64)?;
+        int("larger_town", self.larger_town as i64)?;
+        int("layout", self.layout as i64)?;
+
+        let mut list = |key: &str, values: Vec<i64>| {
+            field_mut(data, key)
+                .map(|v| v.set_int_list(&values))
+                .filter(|&set| set)
+                .map(|_| ())
+                .ok_or_else(|| missing(key))
+        };
+        list("ratings", self.ratings.iter().map(|&x| x as i64).collect())?;
+        list(
+            "unwanted",
+            self.unwanted.iter().map(|&x| x as i64).collect(),
+        )?;
+        list("goal", self.goal.iter().map(|&x| x as i64).collect())?;
+        list(
+            "psa_list",
+            self.psa_list.iter().map(|&x| x as i64).collect(),
+        )?;
+
+        match field_mut(data, "name") {
+            Some(TableData::Str(x)) => x.clone_from(&self.name),
+            _ => return Err(missing("name")),
+        }
+        if let Some(TableData::Str(x)) = field_mut(data, "text") {
+            x.clone_from(&self.text);
+        }
+
+        for (key, stats) in [("supplied", &self.supplied), ("received", &self.received)] {
+            let Some(TableData::Struct(list)) = field_mut(data, key) else {
+                return Err(missing(key));
+            };
+            for (row, stat) in list.data.iter_mut().zip(stats) {
+                for (key, value) in [
+                    ("old_max", stat.old_max),
+                    ("new_max", stat.new_max),
+                    ("old_act", stat.old_act),
+                    ("new_act", stat.new_act),
+                ] {
+                    field_mut(row, key)
+                        .map(|v| v.set_int(value as i64))
+                        .filter(|&set| set)
+                        .ok_or_else(|| missing(key))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn missing(key: &str) -> Error {
+    Error::Malformed {
+        message: format!("missing or mistyped town field {key}"),
+    }
+}
+
+/// The element for a town that's already in the pool
+fn existing<T>(elements: &mut [T], index: u32, is_empty: impl Fn(&T) -> bool) -> Result<&mut T> {
+    elements
+        .get_mut(index as usize)
+        .filter(|element| !is_empty(element))
+        .ok_or_else(|| Error::Malformed {
+            message: format!("there is no town {index} to write to"),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use binrw::io::Cursor;
+    use std::fs::File;
+
+    use crate::error::Result;
+    use crate::save::{CompressionType, Save, WriteOptions};
+    use crate::town::Town;
+
+    #[test]
+    fn read_towns() -> Result<()> {
+        for path in ["tests/tiny.sav", "tests/TinyVanillaTest.sav"] {
+            let save = Save::from_reader(&mut File::open(path)?)?;
+            let towns = Town::from_save(&save)?;
+            assert!(!towns.is_empty());
+            for town in &towns {
+                assert!(town.xy < 64 * 64);
+                assert_eq!(town.ratings.len(), 15);
+                assert_eq!(town.received.len(), 6);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_towns() -> Result<()> {
+        for path in ["tests/tiny.sav", "tests/TinyVanillaTest.sav"] {
+            let mut save = Save::from_reader(&mut File::open(path)?)?;
+            let options = WriteOptions::new(CompressionType::OTTN);
+            let mut expected = vec![];
+            save.write_with_options(&mut Cursor::new(&mut expected), &options)?;
+
+            // Writing the towns back unchanged keeps the save the same
+            let mut towns = Town::from_save(&save)?;
+            Town::write_to_save(&towns, &mut save)?;
+            let mut d = vec![];
+            save.write_with_options(&mut Cursor::new(&mut d), &options)?;
+            assert_eq!(d, expected);
+
+            towns[0].name = "Renamed".to_string();
+            towns[0].growth_rate = 1234;
+            towns[0].ratings[2] = -200;
+            towns[0].supplied[0].new_act = 42;
+            Town::write_to_save(&towns, &mut save)?;
+            assert_eq!(Town::from_save(&save)?, towns);
+        }
+
+        Ok(())
+    }
+}
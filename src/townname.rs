@@ -0,0 +1,873 @@
+//! OpenTTD's built-in town name generators, following `townname.cpp` and `table/townname.h`.
+//!
+//! Only the styles in [`TownNameStyle::GENERATED`] are ported: both English styles, French,
+//! German, Swedish and Austrian. The other fifteen give None, so their towns keep showing the
+//! name saved in the town, and can't be re-seeded with a name known in advance.
+
+use crate::strings::SPECSTR_TOWNNAME_START;
+use crate::tile::{gb, raw_enum};
+
+raw_enum!(
+    /// OpenTTD's built-in town name styles, in the order of their town name types
+    TownNameStyle {
+        English = 0,
+        French = 1,
+        German = 2,
+        EnglishAdditional = 3,
+        LatinAmerican = 4,
+        Silly = 5,
+        Swedish = 6,
+        Dutch = 7,
+        Finnish = 8,
+        Polish = 9,
+        Slovak = 10,
+        Norwegian = 11,
+        Hungarian = 12,
+        Austrian = 13,
+        Romanian = 14,
+        Czech = 15,
+        Swiss = 16,
+        Danish = 17,
+        Turkish = 18,
+        Italian = 19,
+        Catalan = 20,
+    }
+);
+
+impl TownNameStyle {
+    /// The styles [`TownNameStyle::generate`] can make names for
+    pub const GENERATED: &'static [TownNameStyle] = &[
+        TownNameStyle::English,
+        TownNameStyle::EnglishAdditional,
+        TownNameStyle::French,
+        TownNameStyle::German,
+        TownNameStyle::Swedish,
+        TownNameStyle::Austrian,
+    ];
+
+    /// The style of a town's `townnametype`, for towns that don't use a NewGRF's names
+    pub fn from_town_name_type(town_name_type: u16) -> Option<TownNameStyle> {
+        let index = town_name_type.checked_sub(SPECSTR_TOWNNAME_START)?;
+        TownNameStyle::from_raw(index as u32).ok()
+    }
+
+    pub fn town_name_type(self) -> u16 {
+        SPECSTR_TOWNNAME_START + self as u16
+    }
+
+    /// Generates the name OpenTTD shows for a town with this style and `townnameparts`, None
+    /// if the style isn't one of [`TownNameStyle::GENERATED`]
+    pub fn generate(self, seed: u32) -> Option<String> {
+        match self {
+            TownNameStyle::English => Some(english_original(seed)),
+            TownNameStyle::EnglishAdditional => Some(english_additional(seed)),
+            TownNameStyle::French => Some(french(seed)),
+            TownNameStyle::German => Some(german(seed)),
+            TownNameStyle::Swedish => Some(swedish(seed)),
+            TownNameStyle::Austrian => Some(austrian(seed)),
+            _ => None,
+        }
+    }
+
+    /// Finds the first of `seeds` that gives a name none of `taken` has, like OpenTTD does when
+    /// founding a town. The seeds would usually come from a random number generator. None if
+    /// no seed works or the style isn't one of [`TownNameStyle::GENERATED`].
+    pub fn unique_seed(
+        self,
+        seeds: impl IntoIterator<Item = u32>,
+        taken: &[String],
+    ) -> Option<(u32, String)> {
+        seeds.into_iter().find_map(|seed| {
+            let name = self.generate(seed)?;
+            let valid = name.chars().count() < MAX_LENGTH_TOWN_NAME_CHARS && !taken.contains(&name);
+            valid.then_some((seed, name))
+        })
+    }
+}
+
+/// Town names have to be shorter than this to be used
+const MAX_LENGTH_TOWN_NAME_CHARS: usize = 32;
+
+const ENGLISH_1: &[&str] = &["Great ", "Little ", "New ", "Fort "];
+
+const ENGLISH_2: &[&str] = &[
+    "Wr", "B", "C", "Ch", "Br", "D", "Dr", "F", "Fr", "Fl", "G", "Gr", "H", "L", "M", "N", "P",
+    "Pr", "Pl", "R", "S", "S", "Sl", "T", "Tr", "W",
+];
+
+const ENGLISH_3: &[&str] = &["ar", "a", "e", "in", "on", "u", "un", "en"];
+
+const ENGLISH_4: &[&str] = &["n", "ning", "ding", "d", "", "t", "fing"];
+
+const ENGLISH_5: &[&str] = &[
+    "ville", "ham", "field", "ton", "town", "bridge", "bury", "wood", "ford", "hall", "ston",
+    "way", "stone", "borough", "ley", "head", "bourne", "pool", "worth", "hill", "well", "hattan",
+    "burg",
+];
+
+const ENGLISH_6: &[&str] = &[
+    "-on-sea", " Bay", " Market", " Cross", " Bridge", " Falls", " City", " Ridge", " Springs",
+];
+
+/// Words the English generators can make that they replace. The original generator can't make
+/// "Fart", and replaces "Wrar" with "Inve" instead.
+const ENGLISH_REPLACEMENTS: &[(&str, &str)] = &[
+    ("Cunt", "East"),
+    ("Slag", "Pits"),
+    ("Slut", "Edin"),
+    ("Fart", "Boot"),
+    ("Drar", "Quan"),
+    ("Dreh", "Bash"),
+    ("Frar", "Shor"),
+    ("Grar", "Aber"),
+    ("Brar", "Over"),
+    ("Wrar", "Stan"),
+];
+
+const ENGLISH_ADDITIONAL_PREFIX: &[&str] = &["Great ", "Little ", "New ", "Fort ", "St. ", "Old "];
+
+const ENGLISH_ADDITIONAL_1A: &[&str] = &[
+    "Pen", "Lough", "Stam", "Aber", "Acc", "Ex", "Ax", "Bre", "Cum", "Dun", "Fin", "Inver", "Kin",
+    "Mon", "Nan", "Nant", "Pit", "Pol", "Pont", "Strath", "Tre", "Tilly", "Beck", "Canter", "Bath",
+    "Liver", "Mal", "Ox", "Bletch", "Maccles", "Grim", "Wind", "Sher", "Gates", "Orp", "Brom",
+    "Lewis", "Whit", "White", "Worm", "Tyne", "Avon", "Stan",
+];
+
+const ENGLISH_ADDITIONAL_1B1: &[&str] = &[
+    "Wr", "B", "C", "Ch", "Br", "D", "Dr", "F", "Fr", "Fl", "G", "Gr", "H", "L", "M", "N", "P",
+    "Pr", "Pl", "R", "S", "S", "Sl", "T", "Tr", "W",
+];
+
+const ENGLISH_ADDITIONAL_1B2: &[&str] = &[
+    "ar", "a", "e", "in", "on", "u", "o", "ee", "es", "ea", "un", "en",
+];
+
+const ENGLISH_ADDITIONAL_1B3A: &[&str] = &["n", "d", "", "t", "", ""];
+
+const ENGLISH_ADDITIONAL_1B3B: &[&str] = &["ning", "ding", "fing"];
+
+const ENGLISH_ADDITIONAL_2: &[&str] = &[
+    "ville", "ham", "field", "ton", "town", "borough", "bridge", "bury", "wood", "ditch", "ford",
+    "hall", "dean", "leigh", "dore", "ston", "stow", "church", "wich", "low", "way", "stone",
+    "minster", "ley", "head", "bourne", "pool", "worth", "hill", "well", "hattan", "burg", "berg",
+    "burgh", "port", "stoke", "haven", "stable", "stock", "side", "brook", "don", "den", "down",
+    "nor", "grove", "combe", "by", "say", "ney", "chester", "dale", "ness", "shaw", "thwaite",
+];
+
+const ENGLISH_ADDITIONAL_3: &[&str] = &[
+    "-on-sea", " Bay", " Market", " Beeches", " Common", " Park", " Heath", " Marsh", " Green",
+    " Castle", " End", " Rivers", " Cross", " Bridge", " Falls", " City", " Ridge", " Springs",
+];
+
+const FRENCH_REAL: &[&str] = &[
+    "Agincourt",
+    "Lille",
+    "Dinan",
+    "Aubusson",
+    "Rodez",
+    "Bergerac",
+    "Bordeaux",
+    "Bayonne",
+    "Montpellier",
+    "Mont\u{e9}limar",
+    "Valence",
+    "Digne",
+    "Nice",
+    "Cannes",
+    "St. Tropez",
+    "Marseille",
+    "Narbonne",
+    "S\u{e8}te",
+    "Aurillac",
+    "Gu\u{e9}ret",
+    "Le Creusot",
+    "Nevers",
+    "Auxerre",
+    "Versailles",
+    "Meaux",
+    "Ch\u{e2}lons",
+    "Compi\u{e8}gne",
+    "Metz",
+    "Chaumont",
+    "Langres",
+    "Bourg",
+    "Lyon",
+    "Vienne",
+    "Grenoble",
+    "Toulon",
+    "Rennes",
+    "Le Mans",
+    "Angers",
+    "Nantes",
+    "Ch\u{e2}teauroux",
+    "Orl\u{e9}ans",
+    "Lisieux",
+    "Cherbourg",
+    "Morlaix",
+    "Cognac",
+    "Agen",
+    "Tulle",
+    "Blois",
+    "Troyes",
+    "Charolles",
+    "Cahors",
+    "Rochefort",
+    "Chamonix",
+    "Bonneville",
+    "Paris",
+    "Cavaillon",
+    "Moissac",
+    "Mantes",
+    "Sens",
+    "Laval",
+    "Avignon",
+    "Vernon",
+    "Pau",
+    "Dieppe",
+    "Limoges",
+    "Caen",
+    "Brest",
+    "Nancy",
+    "Royan",
+    "Calais",
+];
+
+/// Real names that stand on their own, picked together with the first part of made up names
+const GERMAN_REAL: &[&str] = &[
+    "Berlin",
+    "Bonn",
+    "Bremen",
+    "Cottbus",
+    "Chemnitz",
+    "Dortmund",
+    "Dresden",
+    "Erfurt",
+    "Erlangen",
+    "Essen",
+    "Fulda",
+    "Gera",
+    "Kassel",
+    "Kiel",
+    "K\u{f6}ln",
+    "L\u{fc}beck",
+    "Magdeburg",
+    "M\u{fc}nchen",
+    "Potsdam",
+    "Stuttgart",
+    "Wiesbaden",
+];
+
+const GERMAN_PRE: &[&str] = &["Bad ", "Klein ", "Neu "];
+
+const GERMAN_1: &[&str] = &[
+    "Alb",
+    "Als",
+    "Ander",
+    "Arns",
+    "Bruns",
+    "Bam",
+    "Biele",
+    "Cloppen",
+    "Co",
+    "Duis",
+    "D\u{fc}ssel",
+    "Dannen",
+    "Elb",
+    "Els",
+    "Elster",
+    "Eichen",
+    "Ems",
+    "Fahr",
+    "Falken",
+    "Flens",
+    "Frank",
+    "Frei",
+    "Freuden",
+    "Fried",
+    "F\u{fc}rsten",
+    "Hahn",
+    "Ham",
+    "Harz",
+    "Heidel",
+    "Hers",
+    "Herz",
+    "Holz",
+    "Hildes",
+    "Inns",
+    "Ilsen",
+    "Ingols",
+    "Kel",
+    "Kies",
+    "Korn",
+    "Kor",
+    "Kreuz",
+    "Kulm",
+    "Langen",
+    "Lim",
+    "Lohr",
+    "L\u{fc}ne",
+    "Mel",
+    "Michels",
+    "M\u{fc}hl",
+    "Naum",
+    "Nest",
+    "Nord",
+    "Nort",
+    "Nien",
+    "Nidda",
+    "Nieder",
+    "N\u{fc}rn",
+    "Ober",
+    "Offen",
+    "Osna",
+    "Olden",
+    "Ols",
+    "Oranien",
+    "Pader",
+    "Quedlin",
+    "Quer",
+    "Ravens",
+    "Regens",
+    "Rott",
+    "Ros",
+    "R\u{fc}ssels",
+    "Saal",
+    "Saar",
+    "Salz",
+    "Sch\u{f6}ne",
+    "Schwein",
+    "Sonder",
+    "Sonnen",
+    "Stein",
+    "Strals",
+    "Straus",
+    "S\u{fc}d",
+    "Ton",
+    "Unter",
+    "Ur",
+    "Vor",
+    "Wald",
+    "War",
+    "Wert",
+    "Wester",
+    "Witten",
+    "Wolfs",
+    "W\u{fc}rz",
+];
+
+const GERMAN_2: &[&str] = &[
+    "bach",
+    "berg",
+    "br\u{fc}ck",
+    "br\u{fc}cken",
+    "burg",
+    "dorf",
+    "feld",
+    "furt",
+    "hausen",
+    "haven",
+    "heim",
+    "horst",
+    "mund",
+    "m\u{fc}nster",
+    "stadt",
+    "stedt",
+    "stein",
+];
+
+/// Rivers for names like "Frankfurt an der Oder"
+const GERMAN_AN_DER: &[&str] = &["Oder", "Spree", "Donau", "Saale", "Elbe"];
+
+const GERMAN_AM: &[&str] = &["Main"];
+
+const SWEDISH_1: &[&str] = &["Gamla ", "Lilla ", "Nya ", "Stora "];
+
+const SWEDISH_2: &[&str] = &[
+    "Boll",
+    "Bor",
+    "Ed",
+    "En",
+    "Erik",
+    "Es",
+    "Fin",
+    "Fisk",
+    "Gr\u{f6}n",
+    "Hag",
+    "Halm",
+    "Karl",
+    "Kram",
+    "Kung",
+    "Land",
+    "Lid",
+    "Lin",
+    "Mal",
+    "Malm",
+    "Marie",
+    "Ner",
+    "Norr",
+    "Oskar",
+    "Sand",
+    "Skog",
+    "Stock",
+    "Stor",
+    "Str\u{f6}m",
+    "Sund",
+    "S\u{f6}der",
+    "Tall",
+    "Tratt",
+    "Troll",
+    "Upp",
+    "Var",
+    "V\u{e4}ster",
+    "\u{c4}ngel",
+    "\u{d6}ster",
+];
+
+const SWEDISH_2A: &[&str] = &[
+    "B", "Br", "D", "Dr", "Dv", "F", "Fj", "Fl", "Fr", "G", "Gl", "Gn", "Gr", "H", "J", "K", "Kl",
+    "Kn", "Kr", "Kv", "L", "M", "N", "P", "Pl", "Pr", "R", "S", "Sk", "Skr", "Sl", "Sn", "Sp",
+    "Spr", "St", "Str", "Sv", "T", "Tr", "Tv", "V", "Vr",
+];
+
+const SWEDISH_2B: &[&str] = &["a", "e", "i", "o", "u", "y", "\u{e5}", "\u{e4}", "\u{f6}"];
+
+const SWEDISH_2C: &[&str] = &[
+    "ck", "d", "dd", "g", "gg", "l", "ld", "m", "n", "nd", "ng", "nn", "p", "pp", "r", "rd", "rk",
+    "rp", "rr", "rt", "s", "sk", "st", "t", "tt", "v",
+];
+
+const SWEDISH_3: &[&str] = &[
+    "arp",
+    "berg",
+    "boda",
+    "borg",
+    "bro",
+    "bukten",
+    "by",
+    "byn",
+    "fors",
+    "hammar",
+    "hamn",
+    "holm",
+    "hus",
+    "h\u{e4}ttan",
+    "k\u{e4}lla",
+    "lund",
+    "l\u{f6}v",
+    "sala",
+    "skog",
+    "sl\u{e4}tt",
+    "sp\u{e5}ng",
+    "stad",
+    "sund",
+    "svall",
+    "svik",
+    "s\u{e5}ker",
+    "udde",
+    "valla",
+    "viken",
+    "\u{e4}lv",
+    "\u{e5}s",
+];
+
+const AUSTRIAN_A1: &[&str] = &["Bad ", "Deutsch ", "Gross ", "Klein ", "Markt ", "Maria "];
+
+const AUSTRIAN_A2: &[&str] = &[
+    "Aus",
+    "Alten",
+    "Braun",
+    "V\u{f6}sl",
+    "Mittern",
+    "Nuss",
+    "Neu",
+    "Walters",
+    "Breiten",
+    "Eisen",
+    "Feld",
+    "Mittern",
+    "Gall",
+    "Obern",
+    "Grat",
+    "Heiligen",
+    "Hof",
+    "Holla",
+    "Stein",
+    "Eber",
+    "Eggen",
+    "Enzers",
+    "Frauen",
+    "Herren",
+    "Hof",
+    "H\u{fc}tt",
+    "Kaisers",
+    "K\u{f6}nigs",
+    "Knittel",
+    "Lang",
+    "Ober",
+    "Ollers",
+    "Pfaffen",
+    "Potten",
+    "Salz",
+    "Schwarz",
+    "Stocker",
+    "Unter",
+    "Utten",
+    "V\u{f6}sen",
+    "Vill",
+    "Weissen",
+];
+
+const AUSTRIAN_A3: &[&str] = &[
+    "see",
+    "bach",
+    "dorf",
+    "ach",
+    "stein",
+    "hofen",
+    "au",
+    "ach",
+    "egg",
+    "wang",
+    "kirchen",
+    "feld",
+    "thal",
+    "berg",
+    "ach",
+    "kofen",
+    "stetten",
+    "brunn",
+    "d\u{f6}rfl",
+    "kirch",
+    "t\u{e4}tten",
+    "n\u{f6}tting",
+    "bruck",
+    "feld",
+    "berg",
+    "k\u{f6}ln",
+    "rett",
+    "see",
+    "dorf",
+    "stadt",
+    "hart",
+    "zell",
+    "kirchen",
+    "burg",
+    "dorf",
+    "hofen",
+    "stein",
+    "markt",
+    "see",
+    "beck",
+    "hausen",
+    "brunn",
+];
+
+const AUSTRIAN_A4: &[&str] = &[
+    "Alten",
+    "Anger",
+    "Arn",
+    "Asch",
+    "Bad",
+    "Bach",
+    "Berg",
+    "Brunn",
+    "Burg",
+    "Dorf",
+    "Eck",
+    "Egg",
+    "Feld",
+    "Gm\u{fc}nd",
+    "Haag",
+    "Hall",
+    "Hart",
+    "Hausen",
+    "Hof",
+    "Imst",
+    "Kirch",
+    "Krems",
+    "Linz",
+    "Markt",
+    "Mauer",
+    "Melk",
+    "M\u{f6}dling",
+    "Rust",
+    "See",
+    "Stein",
+    "Steyr",
+    "Tamsweg",
+    "Wels",
+    "Zell",
+];
+
+const AUSTRIAN_A5: &[&str] = &["Bad ", "Sankt ", "St. "];
+
+const AUSTRIAN_A6: &[&str] = &[
+    "Aegyd",
+    "Andr\u{e4}",
+    "Georgen",
+    "Jakob",
+    "Johann",
+    "Leonhard",
+    "Marein",
+    "Lorenzen",
+    "Margarethen",
+    "Martin",
+    "Michael",
+    "Nikolai",
+    "Oswald",
+    "Peter",
+    "P\u{f6}lten",
+    "Stefan",
+    "Stephan",
+    "Thomas",
+    "Veit",
+    "Wolfgang",
+];
+
+const AUSTRIAN_F1: &[&str] = &[" an der ", " ob der "];
+
+/// Rivers
+const AUSTRIAN_F2: &[&str] = &[
+    "Donau", "Steyr", "Lafnitz", "Leitha", "Thaya", "Gail", "Drau", "Salzach", "Ybbs", "Traisen",
+    "Enns", "Mur", "Ill",
+];
+
+const AUSTRIAN_B1: &[&str] = &[" am "];
+
+/// Mountains
+const AUSTRIAN_B2: &[&str] = &[
+    "Brenner",
+    "Dachstein",
+    "Gebirge",
+    "Grossglockner",
+    "Hausruck",
+    "Semmering",
+    "Wagram",
+    "Wechsel",
+    "Wilden Kaiser",
+    "Ziller",
+];
+
+/// Picks from `max` items with 16 bits of the seed
+fn seed_chance(shift: u32, max: usize, seed: u32) -> usize {
+    (gb(seed, shift, 16) as usize * max) >> 16
+}
+
+/// Like `seed_chance` but the first `bias` picks give None
+fn seed_chance_bias(shift: u32, max: usize, seed: u32, bias: usize) -> Option<usize> {
+    seed_chance(shift, max + bias, seed).checked_sub(bias)
+}
+
+/// Replaces the first four letters of `name` if they're one of the [`ENGLISH_REPLACEMENTS`]
+fn replace_english_words(name: &mut String, original: bool) {
+    for &(from, to) in ENGLISH_REPLACEMENTS {
+        let to = if original && from == "Wrar" {
+            "Inve"
+        } else {
+            to
+        };
+        if name.starts_with(from) {
+            name.replace_range(0..from.len(), to);
+        }
+    }
+}
+
+fn english_original(seed: u32) -> String {
+    let mut name = String::new();
+    if let Some(i) = seed_chance_bias(0, ENGLISH_1.len(), seed, 50) {
+        name += ENGLISH_1[i];
+    }
+    name += ENGLISH_2[seed_chance(4, ENGLISH_2.len(), seed)];
+    name += ENGLISH_3[seed_chance(7, ENGLISH_3.len(), seed)];
+    name += ENGLISH_4[seed_chance(10, ENGLISH_4.len(), seed)];
+    name += ENGLISH_5[seed_chance(13, ENGLISH_5.len(), seed)];
+    if let Some(i) = seed_chance_bias(15, ENGLISH_6.len(), seed, 60) {
+        name += ENGLISH_6[i];
+    }
+
+    // Only the start of the name is checked, so names with a prefix are kept as they are
+    if name.starts_with("Ce") || name.starts_with("Ci") {
+        name.replace_range(0..1, "K");
+    }
+    replace_english_words(&mut name, true);
+    name
+}
+
+fn english_additional(seed: u32) -> String {
+    let mut name = String::new();
+    if let Some(i) = seed_chance_bias(0, ENGLISH_ADDITIONAL_PREFIX.len(), seed, 50) {
+        name += ENGLISH_ADDITIONAL_PREFIX[i];
+    }
+    if seed_chance(3, 20, seed) >= 14 {
+        name += ENGLISH_ADDITIONAL_1A[seed_chance(6, ENGLISH_ADDITIONAL_1A.len(), seed)];
+    } else {
+        name += ENGLISH_ADDITIONAL_1B1[seed_chance(6, ENGLISH_ADDITIONAL_1B1.len(), seed)];
+        name += ENGLISH_ADDITIONAL_1B2[seed_chance(9, ENGLISH_ADDITIONAL_1B2.len(), seed)];
+        if seed_chance(11, 20, seed) >= 4 {
+            name += ENGLISH_ADDITIONAL_1B3A[seed_chance(12, ENGLISH_ADDITIONAL_1B3A.len(), seed)];
+        } else {
+            name += ENGLISH_ADDITIONAL_1B3B[seed_chance(12, ENGLISH_ADDITIONAL_1B3B.len(), seed)];
+        }
+    }
+    name += ENGLISH_ADDITIONAL_2[seed_chance(14, ENGLISH_ADDITIONAL_2.len(), seed)];
+    if let Some(i) = seed_chance_bias(15, ENGLISH_ADDITIONAL_3.len(), seed, 60) {
+        name += ENGLISH_ADDITIONAL_3[i];
+    }
+
+    replace_english_words(&mut name, false);
+    name
+}
+
+fn french(seed: u32) -> String {
+    FRENCH_REAL[seed_chance(0, FRENCH_REAL.len(), seed)].to_string()
+}
+
+fn german(seed: u32) -> String {
+    let mut name = String::new();
+    let seed_derivative = seed_chance(7, 28, seed);
+    if seed_derivative == 12 || seed_derivative == 19 {
+        name += GERMAN_PRE[seed_chance(2, GERMAN_PRE.len(), seed)];
+    }
+
+    let i = seed_chance(3, GERMAN_REAL.len() + GERMAN_1.len(), seed);
+    match i.checked_sub(GERMAN_REAL.len()) {
+        None => name += GERMAN_REAL[i],
+        Some(i) => {
+            name += GERMAN_1[i];
+            name += GERMAN_2[seed_chance(5, GERMAN_2.len(), seed)];
+        }
+    }
+
+    if seed_derivative == 24 {
+        let i = seed_chance(9, GERMAN_AN_DER.len() + GERMAN_AM.len(), seed);
+        match i.checked_sub(GERMAN_AN_DER.len()) {
+            None => name = name + " an der " + GERMAN_AN_DER[i],
+            Some(i) => name = name + " am " + GERMAN_AM[i],
+        }
+    }
+    name
+}
+
+fn swedish(seed: u32) -> String {
+    let mut name = String::new();
+    if let Some(i) = seed_chance_bias(0, SWEDISH_1.len(), seed, 50) {
+        name += SWEDISH_1[i];
+    }
+    if seed_chance(4, 5, seed) >= 3 {
+        name += SWEDISH_2[seed_chance(7, SWEDISH_2.len(), seed)];
+    } else {
+        name += SWEDISH_2A[seed_chance(7, SWEDISH_2A.len(), seed)];
+        name += SWEDISH_2B[seed_chance(10, SWEDISH_2B.len(), seed)];
+        name += SWEDISH_2C[seed_chance(13, SWEDISH_2C.len(), seed)];
+    }
+    name += SWEDISH_3[seed_chance(16, SWEDISH_3.len(), seed)];
+    name
+}
+
+fn austrian(seed: u32) -> String {
+    let mut name = String::new();
+    if let Some(i) = seed_chance_bias(0, AUSTRIAN_A1.len(), seed, 15) {
+        name += AUSTRIAN_A1[i];
+    }
+
+    // Saints' towns are more likely to be on a river or by a mountain
+    let mut saint = 0;
+    let i = seed_chance(4, 6, seed);
+    if i >= 4 {
+        name += AUSTRIAN_A2[seed_chance(7, AUSTRIAN_A2.len(), seed)];
+        name += AUSTRIAN_A3[seed_chance(13, AUSTRIAN_A3.len(), seed)];
+    } else if i >= 2 {
+        name += AUSTRIAN_A5[seed_chance(7, AUSTRIAN_A5.len(), seed)];
+        name += AUSTRIAN_A6[seed_chance(9, AUSTRIAN_A6.len(), seed)];
+        saint = 1;
+    } else {
+        name += AUSTRIAN_A4[seed_chance(7, AUSTRIAN_A4.len(), seed)];
+    }
+
+    let i = seed_chance(1, 6, seed);
+    if i >= 4 - saint {
+        name += AUSTRIAN_F1[seed_chance(4, AUSTRIAN_F1.len(), seed)];
+        name += AUSTRIAN_F2[seed_chance(5, AUSTRIAN_F2.len(), seed)];
+    } else if i >= 2 - saint {
+        name += AUSTRIAN_B1[seed_chance(4, AUSTRIAN_B1.len(), seed)];
+        name += AUSTRIAN_B2[seed_chance(5, AUSTRIAN_B2.len(), seed)];
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::error::Result;
+    use crate::save::Save;
+    use crate::town::Town;
+    use crate::townname::TownNameStyle;
+
+    #[test]
+    fn generate_town_names() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let mut towns = Town::from_save(&save)?;
+        let names: Vec<String> = towns.iter().filter_map(Town::generated_name).collect();
+        assert_eq!(names, ["Bedtown", "Darthill"]);
+
+        // Prefixes, suffixes and the replaced words
+        let english = TownNameStyle::English;
+        assert_eq!(english.generate(0xdeadbeef).as_deref(), Some("Sleburg"));
+        assert_eq!(english.generate(1).as_deref(), Some("Invenville"));
+        assert!((0..1000)
+            .filter_map(|seed| english.generate(seed << 8))
+            .all(|name| {
+                name.len() >= 4
+                    && !["Cunt", "Slag", "Slut", "Fart"]
+                        .iter()
+                        .any(|x| name.starts_with(x))
+            }));
+
+        // The other ported styles, with their prefixes, suffixes and replaced words
+        let generated = [
+            (TownNameStyle::EnglishAdditional, 1, "Stanningville"),
+            (TownNameStyle::EnglishAdditional, 0xdeadbeef, "Grimpool"),
+            (TownNameStyle::French, 0xdeadbeef, "Chamonix"),
+            (TownNameStyle::German, 1, "Berlin"),
+            (TownNameStyle::German, 0x9e3779b1, "Neu Vormund"),
+            (TownNameStyle::German, 0x3c6ef362, "Steinfurt an der Spree"),
+            (TownNameStyle::Swedish, 0xdeadbeef, "Kungudde"),
+            (TownNameStyle::Swedish, 0xcafebabe, "\u{d6}stersvik"),
+            (TownNameStyle::Austrian, 0x12345678, "Gm\u{fc}nd"),
+            (
+                TownNameStyle::Austrian,
+                0xcafebabe,
+                "Bad Weissenach am Ziller",
+            ),
+        ];
+        for (style, seed, name) in generated {
+            assert_eq!(
+                style.generate(seed).as_deref(),
+                Some(name),
+                "{style:?} {seed:#x}"
+            );
+        }
+        for raw in 0..=20 {
+            let style = TownNameStyle::from_raw(raw).unwrap();
+            assert_eq!(
+                style.generate(1).is_some(),
+                TownNameStyle::GENERATED.contains(&style),
+                "{style:?}"
+            );
+        }
+        assert_eq!(TownNameStyle::Czech.unique_seed(0..10, &[]), None);
+
+        // Re-seeding skips names that are taken
+        let (seed, name) = english
+            .unique_seed([towns[1].townnameparts, 1], &names)
+            .unwrap();
+        assert_eq!((seed, name.as_str()), (1, "Invenville"));
+        towns[0].name = "Springfield".to_string();
+        towns[0].reseed(seed);
+        assert!(towns[0].name.is_empty());
+        assert_eq!(towns[0].generated_name(), Some(name));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,328 @@
+//! Turns JGR's Patch Pack saves into ones vanilla OpenTTD can load.
+//!
+//! Chunks that only exist for a JGR feature are removed, and the fields JGR adds to the array
+//! layouts this crate knows are dropped. Everything that loses data, and every feature whose
+//! changes aren't known, is listed in the [`VanillaReport`].
+
+use serde::{Deserialize, Serialize};
+
+use crate::chtable::{field, TableData};
+use crate::error::{Error, Result};
+use crate::jgr::SLXI;
+use crate::save::{ChArrayElement, Chunk, ChunkValue, Save};
+use crate::schema::ChunkSchema;
+
+/// Chunks that only JGR's Patch Pack saves, for trace restrict, template replacement, plans and
+/// its tunnel pool. Features usually list their chunks in `SLXI` too, this covers saves where
+/// they don't. Any other chunk is kept, so chunks of newer vanilla versions survive.
+pub const JGR_CHUNKS: &[[u8; 4]] = &[
+    *b"TRRM", *b"TRRP", *b"TRRS", *b"TRRC", *b"TMPL", *b"PLAN", *b"TUNN",
+];
+
+/// Features whose changes `convert` undoes
+const CONVERTED_FEATURES: &[&str] = &[
+    "town_multi_building",
+    "town_setting_override",
+    "animated_tile_extra",
+];
+
+/// What converting a save to vanilla lost or couldn't do
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VanillaReport {
+    /// Tags of the chunks that were removed, with everything in them
+    pub removed_chunks: Vec<String>,
+    /// Fields that were dropped while some element had a value other than 0, like
+    /// `CITY.church_count`
+    pub dropped_fields: Vec<String>,
+    /// Features whose changes to other chunks aren't known, so the save may still not load
+    pub unconverted_features: Vec<String>,
+}
+
+impl VanillaReport {
+    /// Whether the save was converted without losing anything
+    pub fn is_lossless(&self) -> bool {
+        self.removed_chunks.is_empty()
+            && self.dropped_fields.is_empty()
+            && self.unconverted_features.is_empty()
+    }
+
+    fn drop_field(&mut self, name: String) {
+        if !self.dropped_fields.contains(&name) {
+            self.dropped_fields.push(name);
+        }
+    }
+}
+
+/// Converts a JGR save to vanilla in place. Saves without `SLXI` are already vanilla and are left
+/// as they are, and so is a save that fails to convert.
+pub fn convert(save: &mut Save) -> Result<VanillaReport> {
+    let mut report = VanillaReport::default();
+    if save.get(b"SLXI").is_none() {
+        return Ok(report);
+    }
+    let slxi = SLXI::from_save(save)?;
+    let version = save.base_version();
+
+    // Features that list their own chunks can still have changed the layout of others
+    report.unconverted_features = slxi
+        .features()
+        .iter()
+        .filter(|feature| !CONVERTED_FEATURES.contains(&feature.name.as_str()))
+        .map(|feature| feature.name.clone())
+        .collect();
+
+    let mut removed: Vec<[u8; 4]> = slxi
+        .features()
+        .iter()
+        .flat_map(|feature| feature.chunk_tags())
+        .collect();
+    removed.extend_from_slice(JGR_CHUNKS);
+    removed.push(*b"SLXI");
+
+    // Convert copies of the chunks first so an error leaves the save untouched
+    let mut converted = Vec::new();
+    for chunk in save.chunks.iter().filter(|c| !removed.contains(&c.tag)) {
+        let mut value = chunk.value.clone();
+        let result = match &chunk.tag {
+            b"CITY" => convert_array(&mut value, *b"CITY", version, &slxi, &mut report),
+            b"ANIT" if slxi.has_feature("animated_tile_extra") => {
+                convert_animated_tiles(&mut value, &mut report)
+            }
+            _ => continue,
+        };
+        result.map_err(|e| e.in_chunk(chunk.tag, 0))?;
+        converted.push(Chunk {
+            tag: chunk.tag,
+            value,
+        });
+    }
+
+    save.version = version;
+    save.chunks.retain(|chunk| {
+        let keep = !removed.contains(&chunk.tag);
+        if !keep && chunk.tag != *b"SLXI" {
+            report
+                .removed_chunks
+                .push(String::from_utf8_lossy(&chunk.tag).to_string());
+        }
+        keep
+    });
+    for chunk in converted {
+        save.set(chunk);
+    }
+    Ok(report)
+}
+
+/// Rewrites every element in the vanilla layout, which is the schema without JGR features
+fn convert_array(
+    chunk: &mut ChunkValue,
+    tag: [u8; 4],
+    version: u16,
+    slxi: &SLXI,
+    report: &mut VanillaReport,
+) -> Result<()> {
+    let ChunkValue::ChArray { elements } = chunk else {
+        // Tables name their fields, JGR's extra ones would be in the header
+        return Ok(());
+    };
+    let schema = ChunkSchema::find(tag, version)?;
+    let vanilla = SLXI::default();
+    for element in elements.iter_mut().filter(|e| !e.data.is_empty()) {
+        let row = schema.read_all(&element.data, version, slxi)?;
+        for jgr_field in schema.fields(version, slxi).filter(|f| f.feature.is_some()) {
+            let value = field(&row, jgr_field.name).and_then(TableData::as_i64);
+            if value.is_some_and(|x| x != 0) {
+                report.drop_field(format!(
+                    "{}.{}",
+                    String::from_utf8_lossy(&tag),
+                    jgr_field.name
+                ));
+            }
+        }
+        *element = ChArrayElement {
+            data: schema.write(&row, version, &vanilla)?,
+        };
+    }
+    Ok(())
+}
+
+/// JGR stores an animation speed byte after each animated tile
+fn convert_animated_tiles(chunk: &mut ChunkValue, report: &mut VanillaReport) -> Result<()> {
+    let ChunkValue::ChRiff { data } = chunk else {
+        return Ok(());
+    };
+    if data.len() % 5 != 0 {
+        return Err(Error::Malformed {
+            message: "animated tiles aren't a multiple of 5 bytes".to_string(),
+        });
+    }
+    if data.chunks_exact(5).any(|entry| entry[4] != 0) {
+        report.drop_field("ANIT.speed".to_string());
+    }
+    *data = data
+        .chunks_exact(5)
+        .flat_map(|entry| &entry[..4])
+        .copied()
+        .collect();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::cargo::CargoPacket;
+    use crate::chtable::TableData;
+    use crate::company::Company;
+    use crate::error::Result;
+    use crate::industry::Industry;
+    use crate::jgr::{ExtendedChunk, SLXI};
+    use crate::map::TileMap;
+    use crate::newgrf::GrfConfig;
+    use crate::order::{Order, OrderBackup, OrderList};
+    use crate::save::{ChArrayElement, Chunk, ChunkValue, Save, SAVEGAME_VERSION_EXT};
+    use crate::schema::ChunkSchema;
+    use crate::settings::Settings;
+    use crate::station::Station;
+    use crate::town::Town;
+    use crate::vanilla::convert;
+    use crate::vehicle::Vehicle;
+
+    #[test]
+    fn convert_jgr_save() -> Result<()> {
+        let vanilla = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let mut save = vanilla.clone();
+        assert!(convert(&mut save)?.is_lossless());
+
+        // Make a JGR save from the vanilla one
+        let mut slxi = SLXI::default();
+        slxi.set_feature(ExtendedChunk::new("town_multi_building", 1));
+        slxi.set_feature(ExtendedChunk::new("town_setting_override", 1));
+        slxi.set_feature(ExtendedChunk::new("animated_tile_extra", 1));
+        let mut feature = ExtendedChunk::new("some_patch", 1);
+        feature.chunk_list = Some(vec![u32::from_be_bytes(*b"XPAT")]);
+        slxi.set_feature(feature);
+        slxi.set_feature(ExtendedChunk::new("unknown_layout", 2));
+        slxi.write_to_save(&mut save)?;
+        save.version |= SAVEGAME_VERSION_EXT;
+        // A chunk a feature lists, one only JGR has and one from a newer vanilla version
+        for tag in [*b"XPAT", *b"PLAN", *b"NEWC"] {
+            save.set(Chunk {
+                tag,
+                value: ChunkValue::ChRiff { data: vec![1, 2] },
+            });
+        }
+
+        let schema = ChunkSchema::find(*b"CITY", vanilla.version)?;
+        let Some(ChunkValue::ChArray { elements }) = save.get(b"CITY").cloned() else {
+            panic!("CITY isn't an array");
+        };
+        let elements = elements
+            .iter()
+            .map(|element| {
+                let mut row = schema.read_all(&element.data, vanilla.version, &SLXI::default())?;
+                row.extend(
+                    [
+                        "override_flags",
+                        "override_values",
+                        "build_tunnels",
+                        "max_road_slope",
+                    ]
+                    .map(|key| (key.to_string(), TableData::UInt8(0))),
+                );
+                row.push(("church_count".to_string(), TableData::UInt16(2)));
+                row.push(("stadium_count".to_string(), TableData::UInt16(0)));
+                let data = schema.write(&row, vanilla.version, &slxi)?;
+                Ok(ChArrayElement { data })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        save.set(Chunk {
+            tag: *b"CITY",
+            value: ChunkValue::ChArray { elements },
+        });
+        if let Some(ChunkValue::ChRiff { data }) = save.get(b"ANIT").cloned() {
+            let data = data
+                .chunks_exact(4)
+                .flat_map(|tile| [tile, &[0]].concat())
+                .collect();
+            save.set(Chunk {
+                tag: *b"ANIT",
+                value: ChunkValue::ChRiff { data },
+            });
+        }
+
+        // The models see through JGR's version flag
+        assert_eq!(save.base_version(), vanilla.version);
+        assert_eq!(Town::from_save(&save)?, Town::from_save(&vanilla)?);
+
+        // A save that fails to convert is left as it was
+        let mut broken = save.clone();
+        if let Some(ChunkValue::ChArray { mut elements }) = broken.get(b"CITY").cloned() {
+            elements[0].data.truncate(10);
+            broken.set(Chunk {
+                tag: *b"CITY",
+                value: ChunkValue::ChArray { elements },
+            });
+        }
+        let before = format!("{broken:?}");
+        assert!(convert(&mut broken).is_err());
+        assert_eq!(format!("{broken:?}"), before);
+
+        let report = convert(&mut save)?;
+        assert_eq!(report.removed_chunks, ["XPAT", "PLAN"]);
+        assert_eq!(report.dropped_fields, ["CITY.church_count"]);
+        assert_eq!(
+            report.unconverted_features,
+            ["some_patch", "unknown_layout"]
+        );
+        assert!(!report.is_lossless());
+
+        assert_eq!(save.version, vanilla.version);
+        let tags = |save: &Save| save.chunks.iter().map(|c| c.tag).collect::<Vec<_>>();
+        assert_eq!(tags(&save), [tags(&vanilla), vec![*b"NEWC"]].concat());
+        for tag in [b"CITY", b"ANIT"] {
+            assert_eq!(
+                format!("{:?}", save.get(tag)),
+                format!("{:?}", vanilla.get(tag))
+            );
+        }
+        assert_models_match(&save, &vanilla)?;
+        // Settings before the table layout aren't named, so there are none to compare
+        assert!(Settings::from_save(&vanilla).is_err());
+
+        // Tables keep their layout, only the chunks and version change
+        let vanilla = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let mut save = vanilla.clone();
+        slxi.write_to_save(&mut save)?;
+        save.version |= SAVEGAME_VERSION_EXT;
+        convert(&mut save)?;
+        assert_eq!(save.version, vanilla.version);
+        assert_models_match(&save, &vanilla)?;
+        assert_eq!(Settings::from_save(&save)?, Settings::from_save(&vanilla)?);
+
+        Ok(())
+    }
+
+    /// Checks every model reads the converted save like the vanilla one
+    fn assert_models_match(save: &Save, vanilla: &Save) -> Result<()> {
+        assert_eq!(Town::from_save(save)?, Town::from_save(vanilla)?);
+        assert_eq!(Company::from_save(save)?, Company::from_save(vanilla)?);
+        assert_eq!(Industry::from_save(save)?, Industry::from_save(vanilla)?);
+        assert_eq!(Station::from_save(save)?, Station::from_save(vanilla)?);
+        assert_eq!(Vehicle::from_save(save)?, Vehicle::from_save(vanilla)?);
+        assert_eq!(Order::from_save(save)?, Order::from_save(vanilla)?);
+        assert_eq!(OrderList::from_save(save)?, OrderList::from_save(vanilla)?);
+        assert_eq!(
+            OrderBackup::from_save(save)?,
+            OrderBackup::from_save(vanilla)?
+        );
+        assert_eq!(
+            CargoPacket::from_save(save)?,
+            CargoPacket::from_save(vanilla)?
+        );
+        assert_eq!(GrfConfig::from_save(save)?, GrfConfig::from_save(vanilla)?);
+        assert_eq!(TileMap::from_save(save)?, TileMap::from_save(vanilla)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,656 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::chtable::{ChSparseTableElement, TableHeaderProperty};
+use crate::error::{Error, Result};
+use crate::jgr::SLXI;
+use crate::save::{Chunk, ChunkValue, LazySave, Save};
+use crate::schema::ChunkSchema;
+use crate::table::{from_table, to_row, update_table};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VehicleType {
+    Train = 0,
+    Road = 1,
+    Ship = 2,
+    Aircraft = 3,
+    Effect = 4,
+    Disaster = 5,
+}
+
+/// A vehicle from the `VEHS` chunk, read from either the sparse array layout of savegame
+/// versions 182 to 292 or the sparse table layout of OpenTTD 13 and later.
+///
+/// Every part of a train, articulated road vehicle or aircraft is a vehicle of its own, linked
+/// through `next`. See [`Consist`] for grouping them back together.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Vehicle {
+    Train(Train),
+    Road(RoadVehicle),
+    Ship(Ship),
+    Aircraft(Aircraft),
+    Effect(EffectVehicle),
+    Disaster(DisasterVehicle),
+}
+
+/// Fields every train, road vehicle, ship and aircraft has, with the keys OpenTTD uses
+///
+/// References to other pools are stored as the index plus one, with 0 for none.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VehicleCommon {
+    pub subtype: u8,
+    /// Next part of the consist, as a reference
+    pub next: u32,
+    pub name: String,
+    pub unitnumber: u16,
+    pub owner: u8,
+    pub tile: u32,
+    pub dest_tile: u32,
+    pub x_pos: u32,
+    pub y_pos: u32,
+    pub z_pos: i32,
+    pub direction: u8,
+    pub spritenum: u8,
+    pub engine_type: u16,
+    pub cur_speed: u16,
+    pub subspeed: u8,
+    pub acceleration: u8,
+    /// Only in savegame version 288 and later
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion_counter: Option<u32>,
+    pub progress: u8,
+    pub vehstatus: u8,
+    pub last_station_visited: u16,
+    pub last_loading_station: u16,
+    pub cargo_type: u8,
+    pub cargo_subtype: u8,
+    pub cargo_cap: u16,
+    pub refit_cap: u16,
+    /// Cargo packets in the `CAPA` chunk, as references
+    #[serde(rename = "cargo.packets")]
+    pub cargo_packets: Vec<u32>,
+    #[serde(rename = "cargo.action_counts")]
+    pub cargo_action_counts: Vec<u32>,
+    pub cargo_age_counter: u16,
+    pub day_counter: u8,
+    pub tick_counter: u8,
+    pub running_ticks: u8,
+    pub cur_implicit_order_index: u8,
+    pub cur_real_order_index: u8,
+    #[serde(rename = "current_order.type")]
+    pub current_order_type: u8,
+    #[serde(rename = "current_order.flags")]
+    pub current_order_flags: u8,
+    #[serde(rename = "current_order.dest")]
+    pub current_order_dest: u16,
+    #[serde(rename = "current_order.refit_cargo")]
+    pub current_order_refit_cargo: u8,
+    #[serde(rename = "current_order.wait_time")]
+    pub current_order_wait_time: u16,
+    #[serde(rename = "current_order.travel_time")]
+    pub current_order_travel_time: u16,
+    #[serde(rename = "current_order.max_speed")]
+    pub current_order_max_speed: u16,
+    pub timetable_start: i32,
+    /// Order list in the `ORDL` chunk, as a reference
+    pub orders: u32,
+    /// Age in days
+    pub age: i32,
+    pub max_age: i32,
+    pub date_of_last_service: i32,
+    pub service_interval: u16,
+    pub reliability: u16,
+    pub reliability_spd_dec: u16,
+    pub breakdown_ctr: u8,
+    pub breakdown_delay: u8,
+    pub breakdowns_since_last_service: u8,
+    pub breakdown_chance: u8,
+    pub build_year: i32,
+    pub load_unload_ticks: u16,
+    pub cargo_paid_for: u16,
+    pub vehicle_flags: u16,
+    /// Profit in pounds shifted left by 8, only kept on the front of a consist
+    pub profit_this_year: i64,
+    pub profit_last_year: i64,
+    pub value: i64,
+    pub random_bits: u8,
+    pub waiting_triggers: u8,
+    /// Next vehicle sharing the same orders, as a reference
+    pub next_shared: u32,
+    pub group_id: u16,
+    pub current_order_time: u32,
+    /// Only in savegame version 301 and later, which are always tables
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_loading_tick: Option<u64>,
+    pub lateness_counter: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Train {
+    pub common: VehicleCommon,
+    pub crash_anim_pos: u16,
+    pub force_proceed: u8,
+    pub railtype: u8,
+    pub track: u8,
+    pub flags: u16,
+    pub wait_counter: u16,
+    pub gv_flags: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoadVehicle {
+    pub common: VehicleCommon,
+    pub state: u8,
+    pub frame: u8,
+    pub blocked_ctr: u16,
+    pub overtaking: u8,
+    pub overtaking_ctr: u8,
+    pub crashed_ctr: u16,
+    pub reverse_ctr: u8,
+    /// Directions of the cached path, empty before savegame version 211
+    #[serde(rename = "path.td", default)]
+    pub path_td: Vec<u8>,
+    /// Tiles of the cached path
+    #[serde(rename = "path.tile", default)]
+    pub path_tile: Vec<u32>,
+    pub gv_flags: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ship {
+    pub common: VehicleCommon,
+    pub state: u8,
+    /// Directions of the cached path, empty before savegame version 203
+    #[serde(default)]
+    pub path: Vec<u8>,
+    /// Direction the ship is drawn in while turning, 0 before savegame version 204 where the
+    /// game uses `direction` instead
+    #[serde(default)]
+    pub rotation: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Aircraft {
+    pub common: VehicleCommon,
+    pub crashed_counter: u16,
+    pub pos: u8,
+    pub targetairport: u16,
+    pub state: u8,
+    pub previous_pos: u8,
+    pub last_direction: u8,
+    pub number_consecutive_turns: u8,
+    pub turn_counter: u8,
+    pub flags: u8,
+}
+
+/// Smoke, sparks, explosions and other animations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EffectVehicle {
+    pub subtype: u8,
+    pub tile: u32,
+    pub x_pos: i32,
+    pub y_pos: i32,
+    pub z_pos: i32,
+    #[serde(rename = "sprite_cache.sprite_seq.seq[0].sprite")]
+    pub sprite: u16,
+    pub progress: u8,
+    pub vehstatus: u8,
+    pub animation_state: u16,
+    pub animation_substate: u8,
+    pub spritenum: u8,
+}
+
+/// UFOs, submarines and the other vehicles of disasters
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisasterVehicle {
+    /// The shadow of the disaster vehicle, as a reference
+    pub next: u32,
+    pub subtype: u8,
+    pub tile: u32,
+    pub dest_tile: u32,
+    pub x_pos: i32,
+    pub y_pos: i32,
+    pub z_pos: i32,
+    pub direction: u8,
+    pub owner: u8,
+    pub vehstatus: u8,
+    #[serde(rename = "current_order.dest")]
+    pub current_order_dest: u16,
+    #[serde(rename = "sprite_cache.sprite_seq.seq[0].sprite")]
+    pub sprite: u16,
+    pub age: i32,
+    pub tick_counter: u8,
+    pub image_override: u32,
+    pub big_ufo_destroyer_target: u32,
+    /// 0 before savegame version 194
+    #[serde(default)]
+    pub flags: u8,
+}
+
+/// A vehicle with the keys OpenTTD uses, where every type has its own struct and only the one
+/// for the vehicle's type has a row. The array layout only has that struct.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct VehicleRow {
+    #[serde(rename = "type")]
+    vehicle_type: u8,
+    train: Option<Train>,
+    roadveh: Option<RoadVehicle>,
+    ship: Option<Ship>,
+    aircraft: Option<Aircraft>,
+    effect: Option<EffectVehicle>,
+    disaster: Option<DisasterVehicle>,
+}
+
+impl Vehicle {
+    pub fn vehicle_type(&self) -> VehicleType {
+        match self {
+            Vehicle::Train(_) => VehicleType::Train,
+            Vehicle::Road(_) => VehicleType::Road,
+            Vehicle::Ship(_) => VehicleType::Ship,
+            Vehicle::Aircraft(_) => VehicleType::Aircraft,
+            Vehicle::Effect(_) => VehicleType::Effect,
+            Vehicle::Disaster(_) => VehicleType::Disaster,
+        }
+    }
+
+    /// The fields shared by all vehicles companies own, None for effects and disasters
+    pub fn common(&self) -> Option<&VehicleCommon> {
+        match self {
+            Vehicle::Train(train) => Some(&train.common),
+            Vehicle::Road(road) => Some(&road.common),
+            Vehicle::Ship(ship) => Some(&ship.common),
+            Vehicle::Aircraft(aircraft) => Some(&aircraft.common),
+            Vehicle::Effect(_) | Vehicle::Disaster(_) => None,
+        }
+    }
+
+    pub fn common_mut(&mut self) -> Option<&mut VehicleCommon> {
+        match self {
+            Vehicle::Train(train) => Some(&mut train.common),
+            Vehicle::Road(road) => Some(&mut road.common),
+            Vehicle::Ship(ship) => Some(&mut ship.common),
+            Vehicle::Aircraft(aircraft) => Some(&mut aircraft.common),
+            Vehicle::Effect(_) | Vehicle::Disaster(_) => None,
+        }
+    }
+
+    /// Index of the next part of the vehicle, if any
+    pub fn next(&self) -> Option<u32> {
+        match self {
+            Vehicle::Disaster(disaster) => from_ref(disaster.next),
+            _ => self.common().and_then(|common| from_ref(common.next)),
+        }
+    }
+
+    pub fn from_save(save: &Save) -> Result<BTreeMap<u32, Vehicle>> {
+        let chunk = save
+            .get(b"VEHS")
+            .ok_or(Error::MissingChunk { tag: *b"VEHS" })?;
+        Vehicle::from_chunk(chunk, save.base_version())
+    }
+
+    pub fn from_lazy_save(save: &LazySave) -> Result<BTreeMap<u32, Vehicle>> {
+        let chunk = save
+            .get(b"VEHS")
+            .ok_or(Error::MissingChunk { tag: *b"VEHS" })?
+            .decode()?;
+        Vehicle::from_chunk(&chunk.value, save.base_version())
+    }
+
+    /// Parses every vehicle in the contents of the `VEHS` chunk, by their index in the pool.
+    /// `version` is the savegame version, which decides the fields of the array layout.
+    pub fn from_chunk(chunk: &ChunkValue, version: u16) -> Result<BTreeMap<u32, Vehicle>> {
+        let vehicles = match chunk {
+            ChunkValue::ChSparseArray { elements } => ChunkSchema::find(*b"VEHS", version)
+                .and_then(|schema| {
+                    elements
+                        .iter()
+                        .map(|element| {
+                            let row = schema.read_all(&element.data, version, &SLXI::default())?;
+                            Ok((element.index, Vehicle::from_row(from_table(&row)?)?))
+                        })
+                        .collect()
+                }),
+            ChunkValue::ChSparseTable { elements, .. } => elements
+                .iter()
+                .map(|element| {
+                    Ok((
+                        element.index,
+                        Vehicle::from_row(from_table(&element.data)?)?,
+                    ))
+                })
+                .collect(),
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        vehicles.map_err(|e| e.in_chunk(*b"VEHS", 0))
+    }
+
+    fn from_row(row: VehicleRow) -> Result<Vehicle> {
+        let vehicle = match row.vehicle_type {
+            0 => row.train.map(Vehicle::Train),
+            1 => row.roadveh.map(Vehicle::Road),
+            2 => row.ship.map(Vehicle::Ship),
+            3 => row.aircraft.map(Vehicle::Aircraft),
+            4 => row.effect.map(Vehicle::Effect),
+            5 => row.disaster.map(Vehicle::Disaster),
+            _ => None,
+        };
+        vehicle.ok_or_else(|| Error::Malformed {
+            message: format!("no data for vehicle type {}", row.vehicle_type),
+        })
+    }
+
+    fn to_row(&self) -> VehicleRow {
+        let mut row = VehicleRow {
+            vehicle_type: self.vehicle_type() as u8,
+            ..Default::default()
+        };
+        match self.clone() {
+            Vehicle::Train(train) => row.train = Some(train),
+            Vehicle::Road(road) => row.roadveh = Some(road),
+            Vehicle::Ship(ship) => row.ship = Some(ship),
+            Vehicle::Aircraft(aircraft) => row.aircraft = Some(aircraft),
+            Vehicle::Effect(effect) => row.effect = Some(effect),
+            Vehicle::Disaster(disaster) => row.disaster = Some(disaster),
+        }
+        row
+    }
+
+    pub fn write_to_save(vehicles: &BTreeMap<u32, Vehicle>, save: &mut Save) -> Result<()> {
+        let mut chunk = save
+            .get(b"VEHS")
+            .ok_or(Error::MissingChunk { tag: *b"VEHS" })?
+            .clone();
+        Vehicle::write_to_chunk(vehicles, &mut chunk, save.base_version())?;
+        save.set(Chunk {
+            tag: *b"VEHS",
+            value: chunk,
+        });
+        Ok(())
+    }
+
+    pub fn write_to_lazy_save(
+        vehicles: &BTreeMap<u32, Vehicle>,
+        save: &mut LazySave,
+    ) -> Result<()> {
+        let mut chunk = save
+            .get(b"VEHS")
+            .ok_or(Error::MissingChunk { tag: *b"VEHS" })?
+            .decode()?;
+        Vehicle::write_to_chunk(vehicles, &mut chunk.value, save.base_version())?;
+        save.set(&chunk)
+    }
+
+    /// Writes the vehicles back into the contents of the `VEHS` chunk in the layout it already has.
+    ///
+    /// Each vehicle replaces the element with its index, vehicles that aren't in `vehicles` are
+    /// kept. Vehicles can't be added this way since the save has to have an element for them.
+    pub fn write_to_chunk(
+        vehicles: &BTreeMap<u32, Vehicle>,
+        chunk: &mut ChunkValue,
+        version: u16,
+    ) -> Result<()> {
+        let result = match chunk {
+            ChunkValue::ChSparseArray { elements } => {
+                ChunkSchema::find(*b"VEHS", version).and_then(|schema| {
+                    let slxi = SLXI::default();
+                    vehicles.iter().try_for_each(|(&index, vehicle)| {
+                        let element = elements
+                            .iter_mut()
+                            .find(|element| element.index == index)
+                            .ok_or_else(|| no_vehicle(index))?;
+                        // Checks the existing element is in a layout we can write. The vehicle
+                        // may have changed type, so it's written from its own row.
+                        schema.read_all(&element.data, version, &slxi)?;
+                        element.data = schema.write(&to_row(&vehicle.to_row())?, version, &slxi)?;
+                        Ok(())
+                    })
+                })
+            }
+            ChunkValue::ChSparseTable { header, elements } => {
+                vehicles.iter().try_for_each(|(&index, vehicle)| {
+                    let element = elements
+                        .iter_mut()
+                        .find(|element| element.index == index)
+                        .ok_or_else(|| no_vehicle(index))?;
+                    vehicle.update_table(element, header)
+                })
+            }
+            _ => Err(Error::BadChunkType {
+                chunk_type: chunk.chunk_type(),
+            }),
+        };
+
+        result.map_err(|e| e.in_chunk(*b"VEHS", 0))
+    }
+
+    fn update_table(
+        &self,
+        element: &mut ChSparseTableElement,
+        header: &[TableHeaderProperty],
+    ) -> Result<()> {
+        update_table(&mut element.data, header, &self.to_row())
+    }
+}
+
+/// A vehicle as players see it: the front part and every part linked after it, like the
+/// wagons of a train, the parts of an articulated vehicle or the shadow of an aircraft
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Consist {
+    /// Index of the front part, which holds the unit number, orders and profit
+    pub front: u32,
+    /// Indexes of all parts in order, starting with the front
+    pub parts: Vec<u32>,
+}
+
+impl Consist {
+    /// Groups the trains, road vehicles, ships and aircraft into consists, in the order of
+    /// their front parts. Effects and disasters aren't included.
+    pub fn from_vehicles(vehicles: &BTreeMap<u32, Vehicle>) -> Vec<Consist> {
+        let owned = |vehicle: &Vehicle| vehicle.common().is_some();
+        let linked: HashSet<u32> = vehicles
+            .values()
+            .filter(|vehicle| owned(vehicle))
+            .filter_map(Vehicle::next)
+            .collect();
+
+        vehicles
+            .iter()
+            .filter(|(index, vehicle)| owned(vehicle) && !linked.contains(index))
+            .map(|(&front, _)| {
+                let mut parts = vec![front];
+                let mut next = vehicles.get(&front).and_then(Vehicle::next);
+                // A broken chain could loop, so stop once every vehicle was visited
+                while let Some(index) = next.filter(|_| parts.len() <= vehicles.len()) {
+                    parts.push(index);
+                    next = vehicles.get(&index).and_then(Vehicle::next);
+                }
+                Consist { front, parts }
+            })
+            .collect()
+    }
+
+    /// The vehicle of the front part
+    pub fn front<'a>(&self, vehicles: &'a BTreeMap<u32, Vehicle>) -> Option<&'a Vehicle> {
+        vehicles.get(&self.front)
+    }
+
+    /// The vehicles of every part
+    pub fn vehicles<'a>(
+        &'a self,
+        vehicles: &'a BTreeMap<u32, Vehicle>,
+    ) -> impl Iterator<Item = &'a Vehicle> + 'a {
+        self.parts.iter().filter_map(|index| vehicles.get(index))
+    }
+
+    /// Total capacity of the parts per cargo type
+    pub fn capacity(&self, vehicles: &BTreeMap<u32, Vehicle>) -> BTreeMap<u8, u32> {
+        let mut capacity = BTreeMap::new();
+        for common in self.vehicles(vehicles).filter_map(Vehicle::common) {
+            if common.cargo_cap > 0 {
+                *capacity.entry(common.cargo_type).or_default() += common.cargo_cap as u32;
+            }
+        }
+        capacity
+    }
+}
+
+/// Pool references are stored as the index plus one, with 0 for none
+fn from_ref(value: u32) -> Option<u32> {
+    value.checked_sub(1)
+}
+
+fn no_vehicle(index: u32) -> Error {
+    Error::Malformed {
+        message: format!("there is no vehicle {index} to write to"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use binrw::io::Cursor;
+    use std::fs::File;
+
+    use crate::error::Result;
+    use crate::jgr::SLXI;
+    use crate::save::{ChSparseArrayElement, ChunkValue, CompressionType, Save, WriteOptions};
+    use crate::schema::ChunkSchema;
+    use crate::table::{from_table, to_row, to_table};
+    use crate::vehicle::{Consist, Vehicle, VehicleType};
+
+    #[test]
+    fn read_vehicles() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let vehicles = Vehicle::from_save(&save)?;
+        assert_eq!(vehicles.len(), 1);
+        assert_eq!(vehicles[&0].vehicle_type(), VehicleType::Effect);
+
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let vehicles = Vehicle::from_save(&save)?;
+        assert_eq!(vehicles.len(), 18);
+
+        // A train of an engine and 4 wagons, a ship, two aircraft with their shadow and the
+        // rotor of the helicopter, and a bus
+        let consists = Consist::from_vehicles(&vehicles);
+        let parts: Vec<&[u32]> = consists.iter().map(|x| x.parts.as_slice()).collect();
+        assert_eq!(
+            parts,
+            [&[1, 2, 3, 4, 5][..], &[7], &[8, 9], &[10, 11, 12], &[13]]
+        );
+        for consist in &consists {
+            let front = consist.front(&vehicles).unwrap().common().unwrap();
+            assert!(front.unitnumber > 0);
+        }
+        assert_eq!(
+            consists[0]
+                .capacity(&vehicles)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [(0, 120), (2, 30)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_vehicles() -> Result<()> {
+        for path in ["tests/tiny.sav", "tests/TinyVanillaTest.sav"] {
+            let mut save = Save::from_reader(&mut File::open(path)?)?;
+            let options = WriteOptions::new(CompressionType::OTTN);
+            let mut expected = vec![];
+            save.write_with_options(&mut Cursor::new(&mut expected), &options)?;
+
+            // Writing the vehicles back unchanged keeps the save the same
+            let mut vehicles = Vehicle::from_save(&save)?;
+            Vehicle::write_to_save(&vehicles, &mut save)?;
+            let mut d = vec![];
+            save.write_with_options(&mut Cursor::new(&mut d), &options)?;
+            assert_eq!(d, expected);
+
+            for vehicle in vehicles.values_mut() {
+                match vehicle {
+                    Vehicle::Effect(effect) => effect.progress = 3,
+                    vehicle => {
+                        if let Some(common) = vehicle.common_mut() {
+                            common.name = "Express".to_string();
+                            common.reliability = 0xFFFF;
+                        }
+                    }
+                }
+            }
+            Vehicle::write_to_save(&vehicles, &mut save)?;
+            assert_eq!(Vehicle::from_save(&save)?, vehicles);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn vehicle_table_round_trip() -> Result<()> {
+        // The table save only has an effect, so move the vehicles of the array save into its layout
+        let save = Save::from_reader(&mut File::open("tests/TinyVanillaTest.sav")?)?;
+        let Some(ChunkValue::ChSparseTable { header, .. }) = save.get(b"VEHS") else {
+            panic!("VEHS isn't a sparse table");
+        };
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        for mut vehicle in Vehicle::from_save(&save)?.into_values() {
+            if let Some(common) = vehicle.common_mut() {
+                common.last_loading_tick = Some(1234);
+            }
+            let data = to_table(&vehicle.to_row(), header)?;
+            assert_eq!(Vehicle::from_row(from_table(&data)?)?, vehicle);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn old_vehicle_layouts() -> Result<()> {
+        let save = Save::from_reader(&mut File::open("tests/tiny.sav")?)?;
+        let slxi = SLXI::default();
+        let schema = ChunkSchema::find(*b"VEHS", 190)?;
+        let mut vehicles = Vehicle::from_save(&save)?;
+
+        // Version 190 has no cached paths, ship rotations or motion counters
+        let mut elements = vec![];
+        for (&index, vehicle) in vehicles.iter_mut() {
+            match vehicle {
+                Vehicle::Road(road) => {
+                    road.path_td.clear();
+                    road.path_tile.clear();
+                }
+                Vehicle::Ship(ship) => {
+                    ship.path.clear();
+                    ship.rotation = 0;
+                }
+                _ => {}
+            }
+            if let Some(common) = vehicle.common_mut() {
+                common.motion_counter = None;
+            }
+            elements.push(ChSparseArrayElement {
+                index,
+                data: schema.write(&to_row(&vehicle.to_row())?, 190, &slxi)?,
+            });
+        }
+        let mut chunk = ChunkValue::ChSparseArray { elements };
+        assert_eq!(Vehicle::from_chunk(&chunk, 190)?, vehicles);
+
+        // A vehicle can be written as another type
+        let ship = vehicles
+            .values()
+            .find(|vehicle| vehicle.vehicle_type() == VehicleType::Ship)
+            .cloned()
+            .unwrap();
+        vehicles.insert(1, ship);
+        Vehicle::write_to_chunk(&vehicles, &mut chunk, 190)?;
+        assert_eq!(Vehicle::from_chunk(&chunk, 190)?, vehicles);
+        assert!(Vehicle::from_chunk(&chunk, 181).is_err());
+
+        Ok(())
+    }
+}